@@ -1,20 +1,30 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use fs_bench::durability::{DurabilityBench, SyncPolicy};
+use fs_bench::engine::{IoEngine, UringConfig};
 use fs_bench::error::Error;
+use fs_bench::interrupt;
+use fs_bench::micro::directory::DirectoryBench;
 use fs_bench::micro::offline::OfflineBench;
 use fs_bench::micro::real_time::RealTimeBench;
 use fs_bench::micro::throughput::Throughput;
-use fs_bench::micro::BenchFn;
+use fs_bench::micro::{BenchFn, FileLayout};
+use fs_bench::plotter::{ColorScheme, PlotFormat, PlotStyle};
+use fs_bench::replot::Replotter;
+use fs_bench::report::Report;
+use fs_bench::results::Manifest;
+use fs_bench::stats::CiMethod;
 use fs_bench::trace_workload::TraceWorkloadRunner;
-use fs_bench::{Bench, BenchMode};
+use fs_bench::write_sync::{SyncInterval, SyncMethod, WriteSyncPolicy};
+use fs_bench::{Bench, BenchMode, Config};
 use std::path::PathBuf;
 
 /// A library for benchmarking filesystem operations
 #[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
+#[clap(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
-    /// The bench mode: static, realtime, trace, throughput
-    #[clap(short, long)]
-    bench_mode: BenchMode,
+    /// The bench mode: static, realtime, trace, throughput, durability, directory
+    #[clap(short, long, required = true)]
+    bench_mode: Option<BenchMode>,
 
     /// The I/O size, default: 4 KiB
     #[clap(short, long)]
@@ -41,8 +51,8 @@ struct Args {
     fs_name: Vec<String>,
 
     /// The path to store benchmark results
-    #[clap(short = 'p', long)]
-    log_path: PathBuf,
+    #[clap(short = 'p', long, required = true)]
+    log_path: Option<PathBuf>,
 
     /// The path to the trace log file
     #[clap(short, long, required_if_eq("bench-mode", "trace"))]
@@ -52,13 +62,259 @@ struct Args {
     #[clap(short = 'j', long)]
     parallelism_degree: Option<usize>,
 
-    /// The benchmark function to be run in real-time
-    #[clap(short = 'f', long, required_if_eq("bench-mode", "realtime"))]
-    bench_fn: Option<BenchFn>,
+    /// The benchmark functions to run, separated by commas, default in static mode: all except reflink, in throughput mode: read,write (only one in real-time)
+    #[clap(
+        short = 'f',
+        long,
+        use_value_delimiter = true,
+        required_if_eq("bench-mode", "realtime")
+    )]
+    bench_fn: Vec<BenchFn>,
+
+    /// The benchmark functions to skip in static mode, separated by commas
+    #[clap(short = 'x', long, use_value_delimiter = true)]
+    exclude_fn: Vec<BenchFn>,
+
+    /// When to sync the records in durability mode: always, never, every:<n>, default: always
+    #[clap(long)]
+    sync_policy: Option<SyncPolicy>,
+
+    /// Only verify the records written by a previous durability run, e.g. after a remount
+    #[clap(long)]
+    verify_only: bool,
+
+    /// Fill the files with content derived from the seed and verify the content of every read
+    #[clap(long)]
+    verify: bool,
+
+    /// The seed of the generated content when verifying the reads, default: 0
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// Abort a benchmark if more operations than this fail, default: no limit
+    #[clap(long)]
+    max_failures: Option<u64>,
+
+    /// The number of runs of each benchmark function in static mode, at least 3 to compare the runs, default: 1
+    #[clap(short = 'r', long)]
+    repetitions: Option<usize>,
+
+    /// The significance level of comparing the filesystems in static mode, default: 0.05
+    #[clap(long)]
+    significance_level: Option<f64>,
+
+    /// The confidence level of the confidence intervals, default: 0.95
+    #[clap(long)]
+    confidence_level: Option<f64>,
+
+    /// The number of bootstrap resamples, default: 1000
+    #[clap(long)]
+    bootstrap_iterations: Option<usize>,
+
+    /// The method of calculating the confidence intervals: percentile, bca, t, default: percentile
+    #[clap(long)]
+    ci_method: Option<CiMethod>,
+
+    /// Run each static benchmark function until the relative half-width of the ops/s CI is below this, e.g. 0.02
+    #[clap(long)]
+    precision: Option<f64>,
+
+    /// Keep the fileset on the mounted filesystem when a benchmark is interrupted
+    #[clap(long)]
+    keep_fileset: bool,
+
+    /// Leave the outlier ops/s windows out of the mean and confidence interval
+    #[clap(long)]
+    exclude_outliers: bool,
+
+    /// The length of the time windows the ops/s are counted in, in milliseconds, default: chosen from the running time
+    #[clap(long)]
+    window: Option<u64>,
+
+    /// The engine running the reads, writes and syncs in static and throughput modes: sync, io_uring, default: sync
+    #[clap(long)]
+    io_engine: Option<IoEngine>,
+
+    /// The maximum number of io_uring operations in flight, default: 32
+    #[clap(long)]
+    queue_depth: Option<u32>,
+
+    /// The number of io_uring operations submitted together, default: 8
+    #[clap(long)]
+    batch: Option<u32>,
+
+    /// Register the io_uring buffers with the kernel
+    #[clap(long)]
+    registered_buffers: bool,
+
+    /// Register the io_uring files with the kernel
+    #[clap(long)]
+    registered_files: bool,
+
+    /// The number of threads reading and writing each file in io_size chunks in throughput mode, default: one read or write per file
+    #[clap(long)]
+    io_threads: Option<usize>,
+
+    /// How the writes of write_sync are synced: fsync, fdatasync, sync_file_range, o_dsync, default: fdatasync
+    #[clap(long)]
+    sync_method: Option<SyncMethod>,
+
+    /// When the writes of write_sync are synced: writes:<n>, bytes:<size>, ms:<t>, default: writes:1
+    #[clap(long)]
+    sync_interval: Option<SyncInterval>,
+
+    /// Fsync the parent directory after each mkdir and mknod
+    #[clap(long)]
+    dir_sync: bool,
+
+    /// How the files of the fileset are created: written, fallocate, sparse, default: written
+    #[clap(long)]
+    file_layout: Option<FileLayout>,
+
+    /// The number of entries the directory is filled with in directory mode, default: 1000000
+    #[clap(long)]
+    dir_entries: Option<usize>,
+
+    /// The number of entries created between the measurements in directory mode, default: a 20th of the entries
+    #[clap(long)]
+    dir_step: Option<usize>,
+
+    /// Don't show the progress bars or print the results, only log them
+    #[clap(short = 'q', long)]
+    quiet: bool,
+
+    #[clap(flatten)]
+    plot_style: PlotStyleArgs,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+/// The style of the plots
+#[derive(clap::Args, Debug)]
+struct PlotStyleArgs {
+    /// The file format of the plots: svg, png, pdf (requires rsvg-convert), default: svg
+    #[clap(long)]
+    plot_format: Option<PlotFormat>,
+
+    /// The size of the plots in pixels, as WIDTHxHEIGHT, default: 800x500
+    #[clap(long)]
+    plot_size: Option<String>,
+
+    /// The font size of the plot axis descriptions, that the captions and labels scale with, default: 20
+    #[clap(long)]
+    font_size: Option<f64>,
+
+    /// Plot the ops/s and times on a logarithmic y axis
+    #[clap(long)]
+    log_scale: bool,
+
+    /// The colour scheme of the plots: default, colorblind, grayscale, default: default
+    #[clap(long)]
+    color_scheme: Option<ColorScheme>,
+}
+
+impl PlotStyleArgs {
+    fn plot_style(&self) -> Result<PlotStyle, Error> {
+        let mut plot_style = PlotStyle::new();
+        if let Some(plot_format) = self.plot_format {
+            if !plot_format.is_supported() {
+                return Err(Error::InvalidConfig(format!(
+                    "The {} plots require rsvg-convert to be installed",
+                    plot_format
+                )));
+            }
+            plot_style.format = Some(plot_format);
+        }
+        if let Some(plot_size) = &self.plot_size {
+            let size = plot_size.split_once('x').and_then(|(width, height)| {
+                Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?))
+            });
+            match size {
+                Some((width, height)) if width >= 100 && height >= 100 => {
+                    plot_style.width = width;
+                    plot_style.height = height;
+                }
+                _ => {
+                    return Err(Error::InvalidConfig(
+                        "The plot size should be WIDTHxHEIGHT, of at least 100x100 pixels"
+                            .to_string(),
+                    ))
+                }
+            }
+        }
+        if let Some(font_size) = self.font_size {
+            if font_size <= 0f64 {
+                return Err(Error::InvalidConfig(
+                    "The font size should be positive".to_string(),
+                ));
+            }
+            plot_style.font_size = font_size;
+        }
+        plot_style.log_scale = self.log_scale;
+        if let Some(color_scheme) = self.color_scheme {
+            plot_style.color_scheme = color_scheme;
+        }
+
+        Ok(plot_style)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Re-render the charts of earlier static and realtime results, combining the result directories
+    Plot(PlotArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct PlotArgs {
+    /// A result directory to plot, repeated to combine the results of several runs
+    #[clap(short = 'd', long = "dir", required = true)]
+    dirs: Vec<PathBuf>,
+
+    /// A label per result directory, added to the filesystem names to tell the runs apart
+    #[clap(long = "label")]
+    labels: Vec<String>,
+
+    /// Relabel a filesystem, as OLD=NEW
+    #[clap(long = "rename")]
+    renames: Vec<String>,
+
+    /// The filesystems to plot, default: all
+    #[clap(short = 'n', long)]
+    fs_name: Vec<String>,
+
+    /// The benchmark functions to plot, separated by commas, default: all
+    #[clap(short = 'f', long, use_value_delimiter = true)]
+    bench_fn: Vec<BenchFn>,
+
+    /// The title of the charts, followed by the name of each chart
+    #[clap(long)]
+    title: Option<String>,
+
+    /// The path to write the charts to
+    #[clap(short = 'p', long)]
+    log_path: PathBuf,
+
+    #[clap(flatten)]
+    plot_style: PlotStyleArgs,
 }
 
 fn main() -> Result<(), Error> {
     let args = Args::parse();
+    if let Some(Command::Plot(plot_args)) = args.command {
+        return plot(plot_args);
+    }
+
+    // the bench mode and log path are required without a subcommand
+    let (bench_mode, log_path) = match (args.bench_mode, args.log_path) {
+        (Some(bench_mode), Some(log_path)) => (bench_mode, log_path),
+        _ => {
+            return Err(Error::InvalidConfig(
+                "The bench mode and log path are required".to_string(),
+            ))
+        }
+    };
 
     let fs_names = args.fs_name.into_iter().collect::<Vec<_>>();
     let mount_paths = args.mount.into_iter().collect::<Vec<_>>();
@@ -69,70 +325,196 @@ fn main() -> Result<(), Error> {
         ));
     }
 
-    match args.bench_mode {
-        BenchMode::Static => {
-            OfflineBench::configure(
-                args.io_size,
-                args.file_size,
-                args.fileset_size,
-                args.time,
-                args.workload,
-                mount_paths,
-                fs_names,
-                args.log_path,
-                args.parallelism_degree,
-            )?
-            .run(None)?;
+    if let BenchMode::Trace = bench_mode {
+        if args.workload.is_none() {
+            return Err(Error::InvalidConfig(
+                "a valid trace_path not provided".to_string(),
+            ));
         }
-        BenchMode::RealTime => {
-            RealTimeBench::configure(
-                args.io_size,
-                args.file_size,
-                args.fileset_size,
-                args.time,
-                args.workload,
-                mount_paths,
-                fs_names,
-                args.log_path,
-                args.parallelism_degree,
-            )?
-            .run(args.bench_fn)?;
+    }
+
+    let mut builder = Config::builder(&log_path)
+        .keep_fileset(args.keep_fileset)
+        .verify_only(args.verify_only)
+        .verify(args.verify)
+        .exclude_outliers(args.exclude_outliers)
+        .plot_style(args.plot_style.plot_style()?)
+        .quiet(args.quiet);
+    if let Some(io_size) = args.io_size {
+        builder = builder.io_size(io_size);
+    }
+    if let Some(file_size) = args.file_size {
+        builder = builder.file_size(file_size);
+    }
+    if let Some(fileset_size) = args.fileset_size {
+        builder = builder.fileset_size(fileset_size);
+    }
+    if let Some(time) = args.time {
+        builder = builder.run_time(time);
+    }
+    if let Some(workload) = args.workload {
+        builder = builder.workload(workload);
+    }
+    for (fs_name, mount_path) in fs_names.into_iter().zip(mount_paths) {
+        builder = builder.filesystem(fs_name, mount_path);
+    }
+    if let Some(parallelism_degree) = args.parallelism_degree {
+        builder = builder.parallelism_degree(parallelism_degree);
+    }
+    if let Some(sync_policy) = args.sync_policy {
+        builder = builder.sync_policy(sync_policy);
+    }
+    if let Some(seed) = args.seed {
+        builder = builder.seed(seed);
+    }
+    if let Some(max_failures) = args.max_failures {
+        builder = builder.max_failures(max_failures);
+    }
+    if let Some(repetitions) = args.repetitions {
+        builder = builder.repetitions(repetitions);
+    }
+    if let Some(significance_level) = args.significance_level {
+        builder = builder.significance_level(significance_level);
+    }
+    if let Some(confidence_level) = args.confidence_level {
+        builder = builder.confidence_level(confidence_level);
+    }
+    if let Some(bootstrap_iterations) = args.bootstrap_iterations {
+        builder = builder.bootstrap_iterations(bootstrap_iterations);
+    }
+    if let Some(ci_method) = args.ci_method {
+        builder = builder.ci_method(ci_method);
+    }
+    if let Some(window) = args.window {
+        builder = builder.window(window);
+    }
+    if let Some(precision) = args.precision {
+        builder = builder.precision(precision);
+    }
+    if let Some(io_engine) = args.io_engine {
+        if io_engine == IoEngine::IoUring
+            && !matches!(bench_mode, BenchMode::Static | BenchMode::Throughput)
+        {
+            return Err(Error::InvalidConfig(
+                "The io_uring io engine is only supported in static and throughput modes"
+                    .to_string(),
+            ));
         }
-        BenchMode::Trace => {
-            if args.workload.is_none() {
-                return Err(Error::InvalidConfig(
-                    "a valid trace_path not provided".to_string(),
-                ));
-            }
+        builder = builder.io_engine(io_engine);
+    }
+    let mut uring = UringConfig::default();
+    if let Some(queue_depth) = args.queue_depth {
+        uring.queue_depth = queue_depth;
+    }
+    if let Some(batch) = args.batch {
+        uring.batch = batch;
+    }
+    uring.registered_buffers = args.registered_buffers;
+    uring.registered_files = args.registered_files;
+    builder = builder.uring(uring);
+    if let Some(io_threads) = args.io_threads {
+        builder = builder.io_threads(io_threads);
+    }
+    let mut write_sync = WriteSyncPolicy::default();
+    if let Some(sync_method) = args.sync_method {
+        write_sync.method = sync_method;
+    }
+    if let Some(sync_interval) = args.sync_interval {
+        write_sync.interval = sync_interval;
+    }
+    builder = builder.write_sync(write_sync).dir_sync(args.dir_sync);
+    if let Some(file_layout) = args.file_layout {
+        builder = builder.file_layout(file_layout);
+    }
+    if let Some(dir_entries) = args.dir_entries {
+        builder = builder.dir_entries(dir_entries);
+    }
+    if let Some(dir_step) = args.dir_step {
+        builder = builder.dir_step(dir_step);
+    }
+    let mut bench_fns = if !args.bench_fn.is_empty() {
+        args.bench_fn.clone()
+    } else if let BenchMode::Throughput = bench_mode {
+        // the mmap throughputs are only measured if they are selected
+        vec![BenchFn::Read, BenchFn::Write]
+    } else {
+        BenchFn::defaults()
+    };
+    bench_fns.retain(|bench_fn| !args.exclude_fn.contains(bench_fn));
+    let config = builder.bench_fns(bench_fns).build()?;
+
+    if let BenchMode::RealTime = bench_mode {
+        if args.bench_fn.len() > 1 {
+            return Err(Error::InvalidConfig(
+                "Only one benchmark function can be run in real-time".to_string(),
+            ));
+        }
+    }
 
-            TraceWorkloadRunner::configure(
-                args.io_size,
-                args.file_size,
-                args.fileset_size,
-                args.time,
-                args.workload,
-                mount_paths,
-                fs_names,
-                args.log_path,
-                args.parallelism_degree,
-            )?
-            .run(None)?;
+    // log the results collected so far if the benchmark is interrupted
+    interrupt::install_handler()?;
+
+    // the manifest is started before the benchmark, to only list the files of this run
+    let report = Report::new(&bench_mode, &config);
+    let mut manifest = Manifest::new(&bench_mode, &config.log_path);
+    let quiet = config.quiet;
+
+    let result = match bench_mode {
+        BenchMode::Static => OfflineBench::new(config).and_then(|bench| bench.run(None)),
+        BenchMode::RealTime => {
+            RealTimeBench::new(config).and_then(|bench| bench.run(args.bench_fn.first().cloned()))
         }
-        BenchMode::Throughput => {
-            Throughput::configure(
-                args.io_size,
-                args.file_size,
-                args.fileset_size,
-                args.time,
-                args.workload,
-                mount_paths,
-                fs_names,
-                args.log_path,
-                args.parallelism_degree,
-            )?
-            .run(None)?;
+        BenchMode::Trace => TraceWorkloadRunner::new(config).and_then(|bench| bench.run(None)),
+        BenchMode::Throughput => Throughput::new(config).and_then(|bench| bench.run(None)),
+        BenchMode::Durability => DurabilityBench::new(config).and_then(|bench| bench.run(None)),
+        BenchMode::Directory => DirectoryBench::new(config).and_then(|bench| bench.run(None)),
+    };
+
+    // the manifest and report are generated from whatever was logged, also if the benchmark
+    // failed, whose error is then returned
+    let generated = manifest
+        .generate()
+        .and_then(|manifest_path| Ok((manifest_path, report.generate(&manifest)?)));
+    if let Ok((manifest_path, report_path)) = &generated {
+        if !quiet {
+            println!("manifest written to: {}", manifest_path.display());
+            println!("report generated at: {}", report_path.display());
         }
     }
+    result?;
+    generated?;
+
+    Ok(())
+}
+
+// re-render the charts of earlier results
+fn plot(args: PlotArgs) -> Result<(), Error> {
+    let mut replotter = Replotter::new(args.dirs, args.log_path);
+    replotter.labels = args.labels;
+    replotter.renames = args
+        .renames
+        .iter()
+        .map(|rename| match rename.split_once('=') {
+            Some((fs_name, label)) => Ok((fs_name.to_string(), label.to_string())),
+            None => Err(Error::InvalidConfig(format!(
+                "The rename {} should be OLD=NEW",
+                rename
+            ))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    replotter.fs_names = args.fs_name;
+    if !args.bench_fn.is_empty() {
+        replotter.bench_fns = args.bench_fn;
+    }
+    replotter.title = args.title;
+    replotter.plot_style = args.plot_style.plot_style()?;
+
+    let charts = replotter.run()?;
+    println!(
+        "{} charts written to: {}",
+        charts,
+        replotter.log_path.display()
+    );
 
     Ok(())
 }
@@ -0,0 +1,68 @@
+// the CRC-32 (IEEE 802.3) lookup table, generated at compile time
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut idx = 0;
+    while idx < 256 {
+        let mut crc = idx as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            if crc & 1 == 1 {
+                crc = (crc >> 1) ^ 0xEDB8_8320;
+            } else {
+                crc >>= 1;
+            }
+            bit += 1;
+        }
+        table[idx] = crc;
+        idx += 1;
+    }
+
+    table
+}
+
+///
+/// Calculate the CRC-32 checksum of the input bytes
+///
+pub fn crc32(bytes: &[u8]) -> u32 {
+    crc32_update(0, bytes)
+}
+
+///
+/// Continue a CRC-32 checksum calculation over more bytes, so that the checksum of
+/// non-contiguous pieces of data can be calculated without copying them together
+///
+pub fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for byte in bytes {
+        crc = CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+
+    !crc
+}
+
+///
+/// Calculate the 64-bit FNV-1a hash of the input bytes, which is stable across runs and platforms
+///
+pub fn fnv1a64(bytes: &[u8]) -> u64 {
+    let mut hash = 0xCBF2_9CE4_8422_2325u64;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod test {
+    use crate::checksum::{crc32, crc32_update};
+
+    #[test]
+    fn crc32_test() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32_update(crc32(b"1234"), b"56789"), 0xCBF4_3926);
+    }
+}
@@ -0,0 +1,113 @@
+use std::ops::Add;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// the calibrated timer overhead in nanoseconds, u64::MAX until calibrated
+static OVERHEAD: AtomicU64 = AtomicU64::new(u64::MAX);
+
+// the number of calibration rounds, and the timer reads per round
+const CALIBRATION_ROUNDS: u32 = 100;
+const CALIBRATION_READS: u32 = 1000;
+
+///
+/// A point in time of the raw monotonic clock. Unlike SystemTime, the raw monotonic clock is
+/// neither stepped nor slewed by NTP, so the time between two timestamps is never negative and
+/// is not stretched while the clock is being adjusted.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp(Duration);
+
+impl Timestamp {
+    ///
+    /// The current time of the raw monotonic clock
+    ///
+    pub fn now() -> Self {
+        let mut time = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // clock_gettime only fails for an invalid clock id or an invalid pointer
+        unsafe { libc::clock_gettime(CLOCK, &mut time) };
+
+        Self(Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+    }
+
+    ///
+    /// The time elapsed since this timestamp
+    ///
+    pub fn elapsed(&self) -> Duration {
+        Self::now().duration_since(*self)
+    }
+
+    ///
+    /// The time from the earlier timestamp to this one, or zero if the earlier one is later
+    ///
+    pub fn duration_since(&self, earlier: Timestamp) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    ///
+    /// The timestamp the duration before this one, if it is not before the clock's start
+    ///
+    pub fn checked_sub(&self, duration: Duration) -> Option<Timestamp> {
+        self.0.checked_sub(duration).map(Self)
+    }
+}
+
+impl Add<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, duration: Duration) -> Self::Output {
+        Self(self.0 + duration)
+    }
+}
+
+// CLOCK_MONOTONIC_RAW is Linux specific
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const CLOCK: libc::clockid_t = libc::CLOCK_MONOTONIC_RAW;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const CLOCK: libc::clockid_t = libc::CLOCK_MONOTONIC;
+
+///
+/// The time it takes to read the clock, which is included in every measured operation time.
+/// It is calibrated on the first call as the lowest average time of back-to-back clock reads,
+/// as the lowest average is the least disturbed by preemptions and interrupts.
+///
+pub fn overhead() -> Duration {
+    let overhead = OVERHEAD.load(Ordering::Relaxed);
+    if overhead != u64::MAX {
+        return Duration::from_nanos(overhead);
+    }
+
+    let overhead = (0..CALIBRATION_ROUNDS)
+        .map(|_| {
+            let start = Timestamp::now();
+            for _ in 0..CALIBRATION_READS {
+                Timestamp::now();
+            }
+            start.elapsed() / CALIBRATION_READS
+        })
+        .min()
+        .unwrap_or_default();
+    OVERHEAD.store(overhead.as_nanos() as u64, Ordering::Relaxed);
+
+    overhead
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timestamp_test() {
+        let start = Timestamp::now();
+        std::thread::sleep(Duration::from_millis(10));
+        let end = Timestamp::now();
+
+        assert!(end > start);
+        assert!(end.duration_since(start) >= Duration::from_millis(10));
+        assert_eq!(start.duration_since(end), Duration::ZERO);
+        assert_eq!(start + end.duration_since(start), end);
+        assert!(overhead() < Duration::from_millis(1));
+    }
+}
@@ -0,0 +1,544 @@
+use crate::checksum::{crc32, crc32_update};
+use crate::clock::Timestamp;
+use crate::error::Error;
+use crate::fs::{FileHandle, FileSystem, Fs, StdFs};
+use crate::interrupt;
+use crate::output::{self, outputln};
+use crate::progress::Progress;
+use crate::results::RunResults;
+use crate::{Bench, BenchFn, BenchResult, Config, Record};
+use indicatif::ProgressStyle;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use std::fmt::{Display, Formatter};
+use std::fs::{remove_file, File, OpenOptions};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+const RECORD_MAGIC: u32 = 0x4653_4244; // "FSBD"
+
+// the record header includes the magic (4 bytes), sequence number (8 bytes), payload length
+// (4 bytes) and crc (4 bytes)
+const RECORD_HEADER_SIZE: usize = 20;
+
+///
+/// The policy for syncing the written records to the disk
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncPolicy {
+    Always,       // sync after each record
+    Every(usize), // sync after every n records
+    Never,        // leave syncing to the filesystem
+}
+
+impl FromStr for SyncPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = "valid sync policies are: always, never, every:<n>".to_string();
+        match s {
+            "always" => Ok(SyncPolicy::Always),
+            "never" => Ok(SyncPolicy::Never),
+            _ => match s.strip_prefix("every:") {
+                Some(n) => match n.parse::<usize>() {
+                    Ok(n) if n > 0 => Ok(SyncPolicy::Every(n)),
+                    _ => Err(err),
+                },
+                None => Err(err),
+            },
+        }
+    }
+}
+
+impl Display for SyncPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyncPolicy::Always => write!(f, "always"),
+            SyncPolicy::Every(n) => write!(f, "every:{}", n),
+            SyncPolicy::Never => write!(f, "never"),
+        }
+    }
+}
+
+impl SyncPolicy {
+    // whether to sync after writing the record with the sequence number seq
+    fn should_sync(&self, seq: u64) -> bool {
+        match self {
+            SyncPolicy::Always => true,
+            SyncPolicy::Every(n) => (seq + 1) % *n as u64 == 0,
+            SyncPolicy::Never => false,
+        }
+    }
+}
+
+///
+/// The state of a record found on the disk at verification
+///
+#[derive(Debug, Clone, PartialEq)]
+enum RecordStatus {
+    Intact,
+    Lost, // the record is not on the disk at all
+    Torn, // the record is partially written or corrupted
+}
+
+impl Display for RecordStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordStatus::Intact => write!(f, "intact"),
+            RecordStatus::Lost => write!(f, "lost"),
+            RecordStatus::Torn => write!(f, "torn"),
+        }
+    }
+}
+
+///
+/// The durability verification: records with sequence numbers and checksums are written and
+/// synced based on the sync policy, and every acknowledged record (a record that a successful
+/// sync has covered) is logged to a journal in the log path. The records on the disk are then
+/// verified against the journal, either right after writing them or in a separate run after
+/// the filesystem is remounted or the machine is crashed.
+///
+pub struct DurabilityBench {
+    config: Config,
+}
+
+impl Bench for DurabilityBench {
+    fn new(config: Config) -> Result<Self, Error> {
+        output::set_quiet(config.quiet);
+        if config.io_size <= RECORD_HEADER_SIZE {
+            return Err(Error::InvalidConfig(format!(
+                "The io size ({}) should be larger than the record header size ({})",
+                config.io_size, RECORD_HEADER_SIZE
+            )));
+        }
+
+        Ok(Self { config })
+    }
+
+    fn setup(&self, path: &PathBuf, _invalidate_cache: bool) -> Result<(), Error> {
+        Fs::cleanup(self.config.backend.as_ref(), path)?;
+        self.config.backend.mkdir_all(path)?;
+
+        Ok(())
+    }
+
+    fn run(&self, _bench_fn: Option<BenchFn>) -> Result<RunResults, Error> {
+        let progress_style = ProgressStyle::default_bar().template("[{elapsed_precise}] {msg}");
+
+        let summary_header = [
+            "records".to_string(),
+            "acknowledged".to_string(),
+            "intact".to_string(),
+            "lost".to_string(),
+            "torn".to_string(),
+            "acknowledged_lost".to_string(),
+            "acknowledged_torn".to_string(),
+        ]
+        .to_vec();
+        let records_header = [
+            "sequence".to_string(),
+            "acknowledged".to_string(),
+            "status".to_string(),
+        ]
+        .to_vec();
+
+        for (idx, mount_path) in self.config.mount_paths.iter().enumerate() {
+            let fs_name = &self.config.fs_names[idx];
+
+            let mut root_path = mount_path.clone();
+            root_path.push("durability");
+            let mut data_path = root_path.clone();
+            data_path.push("records");
+
+            // the journal is kept in the log path, which is expected to be on a different
+            // filesystem than the one being verified
+            let mut journal_path = self.config.log_path.clone();
+            journal_path.push(format!("{}_durability_journal.csv", fs_name));
+
+            if !self.config.verify_only {
+                self.setup(&root_path, false)?;
+                self.write_records(&data_path, &journal_path, fs_name, progress_style.clone())?;
+            }
+
+            let (summary, damaged_records) =
+                self.verify_records(&data_path, &journal_path, fs_name, progress_style.clone())?;
+
+            let mut summary_results = BenchResult::new(summary_header.clone());
+            summary_results.add_record(summary)?;
+            let mut file_name = self.config.log_path.clone();
+            file_name.push(format!("{}_durability_summary.csv", fs_name));
+            summary_results.log(&file_name)?;
+
+            let mut records_results = BenchResult::new(records_header.clone());
+            records_results.add_records(damaged_records)?;
+            let mut file_name = self.config.log_path.clone();
+            file_name.push(format!("{}_durability.csv", fs_name));
+            records_results.log(&file_name)?;
+        }
+
+        outputln!(
+            "results logged to: {}",
+            Fs::path_to_str(&self.config.log_path)?
+        );
+
+        // the durability results are not of the typed result modes, they are only logged
+        Ok(RunResults::new())
+    }
+}
+
+impl DurabilityBench {
+    fn write_records(
+        &self,
+        data_path: &PathBuf,
+        journal_path: &PathBuf,
+        fs_name: &str,
+        style: ProgressStyle,
+    ) -> Result<(), Error> {
+        let bar = output::spinner();
+        bar.set_style(style);
+        bar.set_message(format!(
+            "writing records with sync policy {} ({})",
+            self.config.sync_policy, fs_name
+        ));
+        let progress = Progress::start(bar);
+
+        let record_size = self.config.io_size;
+
+        if journal_path.exists() {
+            remove_file(journal_path)?;
+        }
+        let journal = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(journal_path)?;
+        // the journal is recreated, so it only survives a crash once the log path is synced
+        if let Some(dir) = journal_path.parent() {
+            DurabilityBench::sync_dir(&StdFs, dir)?;
+        }
+        let mut journal = csv::Writer::from_writer(journal);
+        journal.write_record(&["event", "value"])?;
+        journal.write_record(&["record_size".to_string(), record_size.to_string()])?;
+        journal.write_record(&[
+            "sync_policy".to_string(),
+            self.config.sync_policy.to_string(),
+        ])?;
+        DurabilityBench::sync_journal(&mut journal)?;
+
+        let mut file = self.config.backend.create(data_path)?;
+        // the new records file and its directory are only durable once their parent directories
+        // are synced, otherwise a crash can lose the whole file, which is not the lost records'
+        // fault
+        for dir in data_path.ancestors().skip(1).take(2) {
+            DurabilityBench::sync_dir(self.config.backend.as_ref(), dir)?;
+        }
+
+        let run_time = Duration::from_secs_f64(self.config.run_time);
+        let start = Timestamp::now();
+        let mut seq = 0u64;
+        let mut acknowledged = 0u64;
+        while start.elapsed() < run_time && !interrupt::interrupted() {
+            let record = DurabilityBench::record(seq, record_size);
+            file.write_all_at(&record, seq * record_size as u64)?;
+
+            if self.config.sync_policy.should_sync(seq) {
+                file.sync_data()?;
+                // the sync has returned, so the filesystem has acknowledged all the records so far
+                journal.write_record(&["acknowledged".to_string(), seq.to_string()])?;
+                DurabilityBench::sync_journal(&mut journal)?;
+                acknowledged = seq + 1;
+            }
+
+            seq += 1;
+        }
+
+        journal.write_record(&["written".to_string(), seq.to_string()])?;
+        DurabilityBench::sync_journal(&mut journal)?;
+
+        progress.finish_with_message(&format!("writing records ({}) finished", fs_name))?;
+        outputln!("{:22} {}", "records written:", seq);
+        outputln!("{:22} {}", "records acknowledged:", acknowledged);
+        outputln!();
+
+        Ok(())
+    }
+
+    fn verify_records(
+        &self,
+        data_path: &PathBuf,
+        journal_path: &PathBuf,
+        fs_name: &str,
+        style: ProgressStyle,
+    ) -> Result<(Record, Vec<Record>), Error> {
+        let bar = output::spinner();
+        bar.set_style(style);
+        bar.set_message(format!("verifying records ({})", fs_name));
+        let progress = Progress::start(bar);
+
+        let (record_size, acknowledged, written) = DurabilityBench::read_journal(journal_path)?;
+
+        // a missing data file means all the records are lost
+        let mut file = match self.config.backend.open(data_path) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(Error::IO(e)),
+        };
+        let records_on_disk = match file.as_mut() {
+            Some(file) => {
+                let len = file.len()?;
+                (len + record_size as u64 - 1) / record_size as u64
+            }
+            None => 0,
+        };
+
+        // check every record that was written, acknowledged, or can be found on the disk
+        let records = written.max(acknowledged).max(records_on_disk);
+
+        let mut intact = 0u64;
+        let mut lost = 0u64;
+        let mut torn = 0u64;
+        let mut acknowledged_lost = 0u64;
+        let mut acknowledged_torn = 0u64;
+        let mut damaged_records = vec![];
+        for seq in 0..records {
+            let mut buffer = vec![0u8; record_size];
+            let read = match file.as_mut() {
+                Some(file) => {
+                    DurabilityBench::read_record(file, &mut buffer, seq * record_size as u64)?
+                }
+                None => 0,
+            };
+            buffer.truncate(read);
+
+            let is_acknowledged = seq < acknowledged;
+            let status = DurabilityBench::record_status(seq, &buffer, record_size);
+            match status {
+                RecordStatus::Intact => intact += 1,
+                RecordStatus::Lost => {
+                    lost += 1;
+                    if is_acknowledged {
+                        acknowledged_lost += 1;
+                    }
+                }
+                RecordStatus::Torn => {
+                    torn += 1;
+                    if is_acknowledged {
+                        acknowledged_torn += 1;
+                    }
+                }
+            }
+
+            if status != RecordStatus::Intact {
+                damaged_records.push(
+                    vec![
+                        seq.to_string(),
+                        is_acknowledged.to_string(),
+                        status.to_string(),
+                    ]
+                    .into(),
+                );
+            }
+        }
+
+        progress.finish_with_message(&format!("verifying records ({}) finished", fs_name))?;
+        outputln!("{:22} {}", "records:", records);
+        outputln!("{:22} {}", "acknowledged:", acknowledged);
+        outputln!("{:22} {}", "intact:", intact);
+        outputln!(
+            "{:22} {} ({} acknowledged)",
+            "lost:",
+            lost,
+            acknowledged_lost
+        );
+        outputln!(
+            "{:22} {} ({} acknowledged)",
+            "torn:",
+            torn,
+            acknowledged_torn
+        );
+        if acknowledged_lost + acknowledged_torn > 0 {
+            outputln!(
+                "{} acknowledged records did not survive",
+                acknowledged_lost + acknowledged_torn
+            );
+        }
+        outputln!();
+
+        let summary = Record {
+            fields: [
+                records.to_string(),
+                acknowledged.to_string(),
+                intact.to_string(),
+                lost.to_string(),
+                torn.to_string(),
+                acknowledged_lost.to_string(),
+                acknowledged_torn.to_string(),
+            ]
+            .to_vec(),
+        };
+
+        Ok((summary, damaged_records))
+    }
+
+    // generate the record with the sequence number seq. The payload is derived from the sequence
+    // number, and the crc covers both the header and the payload
+    fn record(seq: u64, record_size: usize) -> Vec<u8> {
+        let mut record = vec![0u8; record_size];
+        StdRng::seed_from_u64(seq).fill_bytes(&mut record[RECORD_HEADER_SIZE..]);
+
+        let payload_len = (record_size - RECORD_HEADER_SIZE) as u32;
+        record[0..4].copy_from_slice(&RECORD_MAGIC.to_le_bytes());
+        record[4..12].copy_from_slice(&seq.to_le_bytes());
+        record[12..16].copy_from_slice(&payload_len.to_le_bytes());
+        let crc = crc32_update(crc32(&record[0..16]), &record[RECORD_HEADER_SIZE..]);
+        record[16..20].copy_from_slice(&crc.to_le_bytes());
+
+        record
+    }
+
+    fn record_status(seq: u64, record: &[u8], record_size: usize) -> RecordStatus {
+        // nothing has been written at the record's place
+        if record.iter().all(|byte| *byte == 0) {
+            return RecordStatus::Lost;
+        }
+        if record.len() < record_size {
+            return RecordStatus::Torn;
+        }
+
+        let magic = u32::from_le_bytes([record[0], record[1], record[2], record[3]]);
+        let mut record_seq = [0u8; 8];
+        record_seq.copy_from_slice(&record[4..12]);
+        let record_seq = u64::from_le_bytes(record_seq);
+        let payload_len = u32::from_le_bytes([record[12], record[13], record[14], record[15]]);
+        let crc = u32::from_le_bytes([record[16], record[17], record[18], record[19]]);
+
+        if magic != RECORD_MAGIC
+            || record_seq != seq
+            || payload_len as usize != record_size - RECORD_HEADER_SIZE
+            || crc != crc32_update(crc32(&record[0..16]), &record[RECORD_HEADER_SIZE..])
+        {
+            return RecordStatus::Torn;
+        }
+
+        RecordStatus::Intact
+    }
+
+    // read a record, which may be shorter than the buffer if the file ends in the middle of it
+    fn read_record(
+        file: &mut Box<dyn FileHandle>,
+        buffer: &mut [u8],
+        offset: u64,
+    ) -> Result<usize, Error> {
+        let mut read = 0;
+        while read < buffer.len() {
+            match file.read_at(&mut buffer[read..], offset + read as u64)? {
+                0 => break,
+                n => read += n,
+            }
+        }
+
+        Ok(read)
+    }
+
+    // read the record size, the number of acknowledged records and the number of written records
+    // from the journal
+    fn read_journal(journal_path: &PathBuf) -> Result<(usize, u64, u64), Error> {
+        if !journal_path.exists() {
+            return Err(Error::InvalidConfig(format!(
+                "no durability journal found at {}, the records should be written first",
+                Fs::path_to_str(journal_path)?
+            )));
+        }
+
+        let mut reader = csv::Reader::from_path(journal_path)?;
+        let mut record_size = None;
+        let mut acknowledged = 0;
+        let mut written = 0;
+        for row in reader.records() {
+            // the last row could be partially written if the machine crashed while writing it,
+            // which means that the row was not synced and so should be ignored
+            let row = match row {
+                Ok(row) if row.len() == 2 => row,
+                _ => break,
+            };
+            match &row[0] {
+                "record_size" => record_size = Some(row[1].parse::<usize>()?),
+                "acknowledged" => acknowledged = row[1].parse::<u64>()? + 1,
+                "written" => written = row[1].parse::<u64>()?,
+                _ => {}
+            }
+        }
+
+        let record_size = record_size.ok_or(Error::format(
+            "durability journal",
+            "the record size is missing",
+        ))?;
+        if record_size <= RECORD_HEADER_SIZE {
+            return Err(Error::format(
+                "durability journal",
+                format!("invalid record size: {}", record_size),
+            ));
+        }
+
+        Ok((record_size, acknowledged, written))
+    }
+
+    // sync the directory, failing for a filesystem that cannot sync directories, as the new files
+    // of the run would not be durable and the lost records could not be told apart from them
+    fn sync_dir(fs: &dyn FileSystem, dir: &Path) -> Result<(), Error> {
+        match fs.sync_dir(dir) {
+            Err(e) if e.kind() == ErrorKind::Unsupported => Err(Error::InvalidConfig(format!(
+                "The durability mode needs directory syncs, which are not supported for {}",
+                dir.display()
+            ))),
+            result => Ok(result?),
+        }
+    }
+
+    fn sync_journal(journal: &mut csv::Writer<File>) -> Result<(), Error> {
+        journal.flush()?;
+        journal.get_ref().sync_data()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::durability::{DurabilityBench, RecordStatus};
+
+    #[test]
+    fn record_status_test() {
+        let record = DurabilityBench::record(7, 128);
+        assert_eq!(
+            DurabilityBench::record_status(7, &record, 128),
+            RecordStatus::Intact
+        );
+        // a record found at another record's place
+        assert_eq!(
+            DurabilityBench::record_status(8, &record, 128),
+            RecordStatus::Torn
+        );
+        // a partially written record
+        assert_eq!(
+            DurabilityBench::record_status(7, &record[..64], 128),
+            RecordStatus::Torn
+        );
+        let mut corrupted = record.clone();
+        corrupted[100] ^= 0xFF;
+        assert_eq!(
+            DurabilityBench::record_status(7, &corrupted, 128),
+            RecordStatus::Torn
+        );
+        assert_eq!(
+            DurabilityBench::record_status(7, &[0u8; 128], 128),
+            RecordStatus::Lost
+        );
+        assert_eq!(
+            DurabilityBench::record_status(7, &[], 128),
+            RecordStatus::Lost
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+#[cfg(target_os = "linux")]
+pub mod uring;
+
+///
+/// The engine the reads, writes and syncs of the micro and throughput benchmarks are run by
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IoEngine {
+    Sync,    // one blocking call at a time, through the filesystem backend
+    IoUring, // up to the queue depth of operations in flight, by io_uring
+}
+
+impl IoEngine {
+    ///
+    /// Whether the engine is supported on this platform
+    ///
+    pub fn is_supported(&self) -> bool {
+        match self {
+            IoEngine::Sync => true,
+            IoEngine::IoUring => cfg!(target_os = "linux"),
+        }
+    }
+}
+
+impl FromStr for IoEngine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sync" => Ok(IoEngine::Sync),
+            "io_uring" => Ok(IoEngine::IoUring),
+            _ => Err("valid io engines are: sync, io_uring".to_string()),
+        }
+    }
+}
+
+impl Display for IoEngine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IoEngine::Sync => write!(f, "sync"),
+            IoEngine::IoUring => write!(f, "io_uring"),
+        }
+    }
+}
+
+///
+/// The options of the io_uring engine
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UringConfig {
+    pub queue_depth: u32,         // the maximum number of operations in flight
+    pub batch: u32,               // the number of operations submitted together
+    pub registered_buffers: bool, // register the buffers of the operations with the kernel
+    pub registered_files: bool,   // register the files of the operations with the kernel
+}
+
+impl Default for UringConfig {
+    fn default() -> Self {
+        Self {
+            queue_depth: 32,
+            batch: 8,
+            registered_buffers: false,
+            registered_files: false,
+        }
+    }
+}
@@ -0,0 +1,378 @@
+use crate::engine::UringConfig;
+use crate::error::Error;
+use io_uring::{opcode, squeue, types, IoUring};
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+// set in the user data of the sync following a write, to tell its completion from the write's
+const SYNC_FLAG: u64 = 1 << 32;
+
+// the open files kept for the rest of the process, e.g. the standard streams and the logs
+const RESERVED_FILES: libc::rlim_t = 64;
+
+// build an entry on a file, by its index in the registered files or by its descriptor
+macro_rules! on_file {
+    ($uring:ident, $file:expr, |$fd:ident| $entry:expr) => {
+        if $uring.config.registered_files {
+            let $fd = types::Fixed($file as u32);
+            $entry
+        } else {
+            let $fd = types::Fd($uring.files[$file].as_raw_fd());
+            $entry
+        }
+    };
+}
+
+///
+/// A finished operation. The content of a read is kept in the buffer of its slot until the next
+/// operation is pushed.
+///
+#[derive(Debug)]
+pub struct Completion {
+    pub slot: usize,
+    pub file: usize,
+    pub result: io::Result<usize>,
+}
+
+// an operation in flight
+#[derive(Debug, Default)]
+struct Slot {
+    file: usize,
+    len: usize,
+    pending: usize, // the completions still expected, two for a write followed by a sync
+    result: Option<io::Result<usize>>, // the result of the read or write, or the first error
+}
+
+///
+/// The io_uring engine, keeping up to the queue depth of reads and writes in flight on a set of
+/// open files, and submitting them in batches
+///
+pub struct Uring {
+    ring: IoUring,
+    config: UringConfig,
+    files: Vec<File>,
+    buffers: Vec<Vec<u8>>, // the buffer of each slot, registered with the ring if configured
+    slots: Vec<Slot>,
+    free: Vec<usize>, // the slots not in flight
+    queued: u32,      // the operations pushed but not submitted yet
+}
+
+impl Uring {
+    pub fn new(config: UringConfig, files: Vec<File>, buffer_size: usize) -> Result<Self, Error> {
+        if config.queue_depth == 0 || config.batch == 0 {
+            return Err(Error::InvalidConfig(
+                "The io_uring queue depth and batch should be at least 1".to_string(),
+            ));
+        }
+
+        // a write followed by a sync takes two entries
+        let ring = IoUring::new((2 * config.queue_depth).next_power_of_two())?;
+        let depth = config.queue_depth as usize;
+        let mut buffers = (0..depth)
+            .map(|_| vec![0u8; buffer_size])
+            .collect::<Vec<_>>();
+        if config.registered_buffers {
+            let iovecs = buffers
+                .iter_mut()
+                .map(|buffer| libc::iovec {
+                    iov_base: buffer.as_mut_ptr() as *mut libc::c_void,
+                    iov_len: buffer.len(),
+                })
+                .collect::<Vec<_>>();
+            // the buffers are neither moved nor dropped before the ring
+            unsafe { ring.submitter().register_buffers(&iovecs)? };
+        }
+        if config.registered_files {
+            let fds = files
+                .iter()
+                .map(|file| file.as_raw_fd())
+                .collect::<Vec<_>>();
+            ring.submitter().register_files(&fds)?;
+        }
+
+        Ok(Self {
+            ring,
+            config,
+            files,
+            buffers,
+            slots: (0..depth).map(|_| Slot::default()).collect(),
+            free: (0..depth).rev().collect(),
+            queued: 0,
+        })
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.free.is_empty()
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn buffer(&self, slot: usize) -> &[u8] {
+        &self.buffers[slot]
+    }
+
+    ///
+    /// Queue a read of len bytes from the file at the offset, and return its slot
+    ///
+    pub fn push_read(&mut self, file: usize, offset: u64, len: usize) -> Result<usize, Error> {
+        let slot = self.slot(file, len)?;
+        let buffer = self.buffers[slot].as_mut_ptr();
+        let entry = on_file!(self, file, |fd| if self.config.registered_buffers {
+            opcode::ReadFixed::new(fd, buffer, len as u32, slot as u16)
+                .offset(offset)
+                .build()
+        } else {
+            opcode::Read::new(fd, buffer, len as u32)
+                .offset(offset)
+                .build()
+        });
+        self.slots[slot].pending = 1;
+        self.queue(&[entry.user_data(slot as u64)])?;
+
+        Ok(slot)
+    }
+
+    ///
+    /// Queue a write of the content to the file at the offset, followed by an fdatasync of the
+    /// file if sync is set, and return its slot
+    ///
+    pub fn push_write(
+        &mut self,
+        file: usize,
+        offset: u64,
+        content: &[u8],
+        sync: bool,
+    ) -> Result<usize, Error> {
+        let len = content.len();
+        let slot = self.slot(file, len)?;
+        self.buffers[slot][..len].copy_from_slice(content);
+        let buffer = self.buffers[slot].as_ptr();
+        let write = on_file!(self, file, |fd| if self.config.registered_buffers {
+            opcode::WriteFixed::new(fd, buffer, len as u32, slot as u16)
+                .offset(offset)
+                .build()
+        } else {
+            opcode::Write::new(fd, buffer, len as u32)
+                .offset(offset)
+                .build()
+        })
+        .user_data(slot as u64);
+
+        if sync {
+            // the sync is linked to the write to only start once the write has finished
+            let sync = on_file!(self, file, |fd| opcode::Fsync::new(fd)
+                .flags(types::FsyncFlags::DATASYNC)
+                .build())
+            .user_data(slot as u64 | SYNC_FLAG);
+            self.slots[slot].pending = 2;
+            self.queue(&[write.flags(squeue::Flags::IO_LINK), sync])?;
+        } else {
+            self.slots[slot].pending = 1;
+            self.queue(&[write])?;
+        }
+
+        Ok(slot)
+    }
+
+    ///
+    /// Submit the queued operations, wait for at least the given number of operations in flight
+    /// to finish, and return the finished operations
+    ///
+    pub fn complete(&mut self, wait: usize) -> Result<Vec<Completion>, Error> {
+        let wait = wait.min(self.in_flight());
+        self.ring.submit_and_wait(wait)?;
+        self.queued = 0;
+
+        let entries = self
+            .ring
+            .completion()
+            .map(|entry| (entry.user_data(), entry.result()))
+            .collect::<Vec<_>>();
+        let mut completions = vec![];
+        for (user_data, result) in entries {
+            let slot_idx = (user_data & !SYNC_FLAG) as usize;
+            let slot = &mut self.slots[slot_idx];
+            let result = if result < 0 {
+                Err(io::Error::from_raw_os_error(-result))
+            } else {
+                Ok(result as usize)
+            };
+            // the sync only changes the result if it fails, and the first error is kept
+            match (slot.result.as_ref(), user_data & SYNC_FLAG != 0) {
+                (None, false) => slot.result = Some(result),
+                (None, true) | (Some(Ok(_)), _) if result.is_err() => slot.result = Some(result),
+                _ => {}
+            }
+
+            slot.pending -= 1;
+            if slot.pending == 0 {
+                let result = match slot.result.take() {
+                    Some(Ok(n)) if n < slot.len => Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("{} of {} bytes transferred", n, slot.len),
+                    )),
+                    Some(result) => result,
+                    None => Ok(0),
+                };
+                completions.push(Completion {
+                    slot: slot_idx,
+                    file: slot.file,
+                    result,
+                });
+                self.free.push(slot_idx);
+            }
+        }
+
+        Ok(completions)
+    }
+
+    ///
+    /// Wait for all the operations in flight to finish, and return them
+    ///
+    pub fn drain(&mut self) -> Result<Vec<Completion>, Error> {
+        let mut completions = vec![];
+        while self.in_flight() > 0 {
+            completions.append(&mut self.complete(1)?);
+        }
+
+        Ok(completions)
+    }
+
+    // take a free slot for an operation of len bytes on the file
+    fn slot(&mut self, file: usize, len: usize) -> Result<usize, Error> {
+        if file >= self.files.len() || len > self.buffers[0].len() {
+            return Err(Error::InvalidConfig(format!(
+                "invalid io_uring operation of {} bytes on file {}",
+                len, file
+            )));
+        }
+        let slot = self
+            .free
+            .pop()
+            .ok_or_else(|| Error::SyncError("the io_uring queue is full".to_string()))?;
+        self.slots[slot] = Slot {
+            file,
+            len,
+            pending: 0,
+            result: None,
+        };
+
+        Ok(slot)
+    }
+
+    // push the entries to the submission queue, and submit them once there is a batch
+    fn queue(&mut self, entries: &[squeue::Entry]) -> Result<(), Error> {
+        let space = {
+            let submission = self.ring.submission();
+            submission.capacity() - submission.len()
+        };
+        if space < entries.len() {
+            self.ring.submit()?;
+            self.queued = 0;
+        }
+        // the buffers of the entries are kept in the slots until the operations finish
+        unsafe {
+            self.ring
+                .submission()
+                .push_multiple(entries)
+                .map_err(|err| Error::SyncError(err.to_string()))?;
+        }
+
+        self.queued += 1;
+        if self.queued >= self.config.batch {
+            self.ring.submit()?;
+            self.queued = 0;
+        }
+
+        Ok(())
+    }
+}
+
+///
+/// Raise the limit of open files of the process, up to its hard limit, to keep the files open
+/// together with the ring
+///
+pub fn reserve_open_files(files: usize) -> Result<(), Error> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    let needed = files as libc::rlim_t + RESERVED_FILES;
+    if needed <= limit.rlim_cur {
+        return Ok(());
+    }
+    if needed > limit.rlim_max {
+        return Err(Error::InvalidConfig(format!(
+            "The io_uring io engine keeps the {} files of the fileset open, above the limit of {} open files",
+            files, limit.rlim_max
+        )));
+    }
+    limit.rlim_cur = needed;
+    if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::OpenOptions;
+
+    #[test]
+    fn uring_test() -> Result<(), Error> {
+        let path = std::env::temp_dir().join(format!("fs-bench-uring-{}", std::process::id()));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+
+        for registered in [false, true] {
+            let config = UringConfig {
+                queue_depth: 4,
+                batch: 2,
+                registered_buffers: registered,
+                registered_files: registered,
+            };
+            let mut uring = match Uring::new(config, vec![file.try_clone()?], 8) {
+                Ok(uring) => uring,
+                // io_uring may be disabled, e.g. in containers
+                Err(Error::IO(_)) => break,
+                Err(err) => return Err(err),
+            };
+
+            for idx in 0..4u8 {
+                uring.push_write(0, idx as u64 * 8, &[idx; 8], idx == 3)?;
+            }
+            assert!(uring.is_full());
+            let completions = uring.drain()?;
+            assert_eq!(completions.len(), 4);
+            assert!(completions
+                .iter()
+                .all(|completion| completion.result.is_ok()));
+
+            let slot = uring.push_read(0, 16, 8)?;
+            let completion = uring.complete(1)?.pop().unwrap();
+            assert_eq!(completion.result?, 8);
+            assert_eq!(uring.buffer(slot), &[2u8; 8]);
+
+            // a read past the end of the file is short
+            uring.push_read(0, 28, 8)?;
+            assert!(uring.complete(1)?.pop().unwrap().result.is_err());
+        }
+
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+}
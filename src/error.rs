@@ -37,6 +37,8 @@ pub enum Error {
 
     CsvError(String),
 
+    JsonError(String),
+
     SystemTimeError(String),
 
     Unknown(String),
@@ -52,6 +54,9 @@ pub enum Error {
 
     /// There is no time record for a benchmarked operation
     NoTimeRecord(String),
+
+    /// More operations have failed than the failure threshold allows
+    FailureThreshold(String),
 }
 
 impl Error {
@@ -105,8 +110,9 @@ impl From<Error> for std::io::Error {
     }
 }
 
-impl From<DrawingAreaErrorKind<std::io::Error>> for Error {
-    fn from(err: DrawingAreaErrorKind<std::io::Error>) -> Self {
+// the drawing errors of all the plotting backends, e.g. SVG and bitmap
+impl<E: std::error::Error + Send + Sync> From<DrawingAreaErrorKind<E>> for Error {
+    fn from(err: DrawingAreaErrorKind<E>) -> Self {
         Error::PlottersError(err.to_string())
     }
 }
@@ -123,6 +129,12 @@ impl From<csv::Error> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::JsonError(err.to_string())
+    }
+}
+
 impl From<SystemTimeError> for Error {
     fn from(err: SystemTimeError) -> Self {
         Error::SystemTimeError(err.to_string())
@@ -159,6 +171,12 @@ impl<T> From<std::sync::PoisonError<T>> for Error {
     }
 }
 
+impl From<ctrlc::Error> for Error {
+    fn from(err: ctrlc::Error) -> Error {
+        Error::SyncError(err.to_string())
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -176,6 +194,7 @@ impl fmt::Display for Error {
             &Error::InvalidPath(ref path) => write!(f, "Invalid path: '{}'", path),
             &Error::PlottersError(ref detail) => write!(f, "Plotters error: {}", detail),
             &Error::CsvError(ref detail) => write!(f, "Csv error: {}", detail),
+            &Error::JsonError(ref detail) => write!(f, "Json error: {}", detail),
             &Error::SystemTimeError(ref detail) => write!(f, "SystemTime error: {}", detail),
             &Error::Unknown(ref detail) => write!(f, "Unknown error: {}", detail),
             &Error::ParseError(ref detail) => write!(f, "Parse error: {}", detail),
@@ -187,6 +206,9 @@ impl fmt::Display for Error {
             &Error::NoTimeRecord(ref detail) => {
                 write!(f, "there is not time recorded for {}", detail)
             }
+            &Error::FailureThreshold(ref detail) => {
+                write!(f, "the failure threshold is exceeded: {}", detail)
+            }
         }
     }
 }
@@ -0,0 +1,160 @@
+use crate::error::Error;
+use crate::output::outputln;
+use crate::{BenchResult, Record};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+///
+/// The failed operations, counted per operation and error number
+///
+#[derive(Debug, Clone, Default)]
+pub struct Failures {
+    // key: the operation name and the error number, if the error is an OS error
+    // value: a pair of (the error description, number of failures)
+    counts: BTreeMap<(String, Option<i32>), (String, u64)>,
+}
+
+impl Failures {
+    pub fn new() -> Self {
+        Self {
+            counts: BTreeMap::new(),
+        }
+    }
+
+    ///
+    /// Count a failed operation
+    ///
+    pub fn add(&mut self, op: &str, err: &Error) {
+        match err {
+            Error::IO(err) => self.add_io(op, err),
+            _ => self.insert(op, None, err.to_string()),
+        }
+    }
+
+    ///
+    /// Count an operation failed with an IO error
+    ///
+    pub fn add_io(&mut self, op: &str, err: &std::io::Error) {
+        match err.raw_os_error() {
+            // describe the error by its error number only, not the context of this failure
+            Some(errno) => self.insert(
+                op,
+                Some(errno),
+                std::io::Error::from_raw_os_error(errno).to_string(),
+            ),
+            None => self.insert(op, None, err.to_string()),
+        }
+    }
+
+    ///
+    /// Add the failures counted somewhere else, e.g. by another thread
+    ///
+    pub fn merge(&mut self, other: &Failures) {
+        for ((op, errno), (description, count)) in other.counts.iter() {
+            let (_, total) = self
+                .counts
+                .entry((op.clone(), *errno))
+                .or_insert((description.clone(), 0));
+            *total += count;
+        }
+    }
+
+    ///
+    /// The total number of failed operations
+    ///
+    pub fn count(&self) -> u64 {
+        self.counts.values().map(|(_, count)| count).sum()
+    }
+
+    ///
+    /// The number of failures of an operation
+    ///
+    pub fn op_count(&self, op: &str) -> u64 {
+        self.counts
+            .iter()
+            .filter(|((name, _), _)| name == op)
+            .map(|(_, (_, count))| count)
+            .sum()
+    }
+
+    ///
+    /// The name of the operations that have failed
+    ///
+    pub fn ops(&self) -> Vec<String> {
+        let mut ops = self
+            .counts
+            .keys()
+            .map(|(op, _)| op.clone())
+            .collect::<Vec<_>>();
+        ops.dedup();
+        ops
+    }
+
+    ///
+    /// Return an error if the number of failed operations is more than the maximum
+    ///
+    pub fn check(&self, max_failures: Option<u64>) -> Result<(), Error> {
+        if let Some(max_failures) = max_failures {
+            let count = self.count();
+            if count > max_failures {
+                return Err(Error::FailureThreshold(format!(
+                    "{} operations failed (maximum: {})",
+                    count, max_failures
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Print the failures per operation and error
+    ///
+    pub fn print(&self) {
+        for ((op, _), (description, count)) in self.counts.iter() {
+            outputln!("{:>7} {:12} {}", count, op, description);
+        }
+    }
+
+    ///
+    /// Log the failures to the specified path
+    ///
+    pub fn log<P: AsRef<Path> + std::convert::AsRef<std::ffi::OsStr>>(
+        &self,
+        file_name: &P,
+    ) -> Result<(), Error> {
+        let header = [
+            "operation".to_string(),
+            "errno".to_string(),
+            "error".to_string(),
+            "count".to_string(),
+        ]
+        .to_vec();
+        let mut results = BenchResult::new(header);
+        results.add_records(self.records())?;
+        results.log(file_name)
+    }
+
+    fn records(&self) -> Vec<Record> {
+        self.counts
+            .iter()
+            .map(|((op, errno), (description, count))| {
+                vec![
+                    op.clone(),
+                    errno.map(|errno| errno.to_string()).unwrap_or_default(),
+                    description.clone(),
+                    count.to_string(),
+                ]
+                .into()
+            })
+            .collect()
+    }
+
+    fn insert(&mut self, op: &str, errno: Option<i32>, description: String) {
+        let (_, count) = self
+            .counts
+            .entry((op.to_string(), errno))
+            .or_insert((description, 0));
+        *count += 1;
+    }
+}
@@ -1,143 +1,448 @@
 use crate::error::Error;
+use crate::mmap::Mmap;
+use crate::output;
 use crate::progress::Progress;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::fs::{create_dir, create_dir_all, remove_dir_all, remove_file, File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use indicatif::ProgressStyle;
+use std::fs::{
+    create_dir, create_dir_all, read_dir, remove_dir_all, remove_file, File, OpenOptions,
+};
+use std::io;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
 
 ///
-/// The fs operations
+/// The filesystem operations the benchmarks run, so that the operations can go to another backend
+/// than the mounted filesystem, e.g. a userspace filesystem library or a mock. The paths are those
+/// of the benchmarks, e.g. under the mount path, which a backend is free to map.
 ///
-pub struct Fs;
+pub trait FileSystem: Send + Sync {
+    fn mkdir(&self, path: &Path) -> io::Result<()>;
 
-impl Fs {
-    pub fn make_dir<P: AsRef<Path>>(path: P) -> Result<(), std::io::Error> {
-        create_dir(path)
+    fn mkdir_all(&self, path: &Path) -> io::Result<()>;
+
+    ///
+    /// Create an empty file, or truncate it if it exists, and open it for reading and writing. The
+    /// parent directory should exist.
+    ///
+    fn create(&self, path: &Path) -> io::Result<Box<dyn FileHandle>>;
+
+    ///
+    /// Open an existing file for reading and writing
+    ///
+    fn open(&self, path: &Path) -> io::Result<Box<dyn FileHandle>>;
+
+    ///
+    /// Open an existing file for reading and writing with O_DSYNC, so that every write returns
+    /// once its data is on the storage
+    ///
+    fn open_dsync(&self, path: &Path) -> io::Result<Box<dyn FileHandle>> {
+        let _ = path;
+        Err(unsupported("O_DSYNC"))
     }
 
-    pub fn make_dir_all<P: AsRef<Path>>(path: P) -> Result<(), std::io::Error> {
-        create_dir_all(path)
+    fn open_dir(&self, path: &Path) -> io::Result<()>;
+
+    ///
+    /// Flush the entries of the directory to the storage, like an fsync of the directory. The
+    /// durability mode and `--dir-sync` depend on it, so a backend without directories to sync
+    /// returns Ok.
+    ///
+    fn sync_dir(&self, path: &Path) -> io::Result<()>;
+
+    fn stat(&self, path: &Path) -> io::Result<FileStat>;
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    ///
+    /// Remove the directory and its content
+    ///
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+
+    fn readdir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    ///
+    /// Copy an existing file to a new file in the kernel, like copy_file_range, without reading
+    /// the content to the user space
+    ///
+    fn copy_range(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let _ = (from, to);
+        Err(unsupported("copy_file_range"))
+    }
+
+    ///
+    /// Clone an existing file to a new file sharing the extents of the existing one, like a
+    /// reflink copy
+    ///
+    fn reflink(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let _ = (from, to);
+        Err(unsupported("reflinks"))
+    }
+
+    fn truncate(&self, path: &Path, len: u64) -> io::Result<()> {
+        self.open(path)?.set_len(len)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.stat(path).is_ok()
+    }
+}
+
+///
+/// An open file of a filesystem backend
+///
+pub trait FileHandle: Send {
+    fn read_at(&mut self, buffer: &mut [u8], offset: u64) -> io::Result<usize>;
+
+    fn write_at(&mut self, buffer: &[u8], offset: u64) -> io::Result<usize>;
+
+    ///
+    /// Flush the data and metadata of the file to the storage, like fsync
+    ///
+    fn sync_all(&mut self) -> io::Result<()>;
+
+    ///
+    /// Flush the data of the file to the storage, like fdatasync
+    ///
+    fn sync_data(&mut self) -> io::Result<()>;
+
+    ///
+    /// Write the data of the range back to the storage and wait for it, like sync_file_range. It
+    /// syncs the data of the whole file by default.
+    ///
+    fn sync_range(&mut self, offset: u64, len: u64) -> io::Result<()> {
+        let _ = (offset, len);
+        self.sync_data()
+    }
+
+    fn set_len(&mut self, len: u64) -> io::Result<()>;
+
+    ///
+    /// Allocate the storage of the range, like fallocate, extending the file if needed
+    ///
+    fn allocate(&mut self, offset: u64, len: u64) -> io::Result<()> {
+        let _ = (offset, len);
+        Err(unsupported("fallocate"))
+    }
+
+    ///
+    /// Deallocate the storage of the range, which then reads as zeros, keeping the file's size
+    ///
+    fn punch_hole(&mut self, offset: u64, len: u64) -> io::Result<()> {
+        let _ = (offset, len);
+        Err(unsupported("punching holes"))
+    }
+
+    ///
+    /// Map the first len bytes of the file into memory, shared with the file, so that the accesses
+    /// to the mapping are those of the file's pages
+    ///
+    fn map(&mut self, len: usize) -> io::Result<Mmap> {
+        let _ = len;
+        Err(unsupported("mmap"))
     }
 
-    pub fn make_file<P: AsRef<Path> + std::convert::AsRef<std::ffi::OsStr>>(
-        path: P,
-    ) -> Result<File, std::io::Error> {
-        // create the parent directory hierarchy if needed
-        let path = Path::new(&path);
-        let path = PathBuf::from(path);
-        let mut parents = path.clone();
-        parents.pop();
-        if !parents.exists() {
-            Fs::make_dir_all(&parents)?;
+    ///
+    /// A std file of the same open file, for the io engines that submit the operations to its
+    /// descriptor rather than call the handle
+    ///
+    fn try_clone_file(&self) -> io::Result<File> {
+        Err(unsupported("io engines other than the sync engine"))
+    }
+
+    fn len(&mut self) -> io::Result<u64>;
+
+    fn is_empty(&mut self) -> io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    fn read_exact_at(&mut self, mut buffer: &mut [u8], mut offset: u64) -> io::Result<()> {
+        while !buffer.is_empty() {
+            match self.read_at(buffer, offset)? {
+                0 => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "failed to fill the whole buffer",
+                    ))
+                }
+                n => {
+                    buffer = &mut buffer[n..];
+                    offset += n as u64;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_all_at(&mut self, mut buffer: &[u8], mut offset: u64) -> io::Result<()> {
+        while !buffer.is_empty() {
+            match self.write_at(buffer, offset)? {
+                0 => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write the whole buffer",
+                    ))
+                }
+                n => {
+                    buffer = &buffer[n..];
+                    offset += n as u64;
+                }
+            }
         }
 
-        File::create(path)
+        Ok(())
+    }
+}
+
+///
+/// The metadata of a file or directory
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileStat {
+    pub len: u64,
+    pub is_dir: bool,
+}
+
+///
+/// The default backend, running the operations on the mounted filesystems by std::fs
+///
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdFs;
+
+impl FileSystem for StdFs {
+    fn mkdir(&self, path: &Path) -> io::Result<()> {
+        create_dir(path)
+    }
+
+    fn mkdir_all(&self, path: &Path) -> io::Result<()> {
+        create_dir_all(path)
     }
 
-    pub fn open_file<P: AsRef<Path>>(path: P) -> Result<File, std::io::Error> {
-        OpenOptions::new()
+    fn create(&self, path: &Path) -> io::Result<Box<dyn FileHandle>> {
+        let file = OpenOptions::new()
             .write(true)
             .read(true)
-            .append(false)
-            .open(path)
-    }
+            .create(true)
+            .truncate(true)
+            .open(path)?;
 
-    pub fn open_dir<P: AsRef<Path>>(path: P) -> Result<File, std::io::Error> {
-        OpenOptions::new().read(true).open(path)
+        Ok(Box::new(file))
     }
 
-    pub fn open_write<P: AsRef<Path>>(
-        path: P,
-        content: &mut Vec<u8>,
-    ) -> Result<usize, std::io::Error> {
-        let mut file = OpenOptions::new()
+    fn open(&self, path: &Path) -> io::Result<Box<dyn FileHandle>> {
+        let file = OpenOptions::new()
             .write(true)
             .read(true)
             .append(false)
             .open(path)?;
 
-        let size = file.write(&content)?;
-        file.flush()?;
-        Ok(size)
+        Ok(Box::new(file))
     }
 
-    pub fn open_write_at<P: AsRef<Path>>(
-        path: P,
-        content: &mut Vec<u8>,
-        offset: u64,
-    ) -> Result<usize, std::io::Error> {
-        let mut file = OpenOptions::new()
+    fn open_dsync(&self, path: &Path) -> io::Result<Box<dyn FileHandle>> {
+        let file = OpenOptions::new()
             .write(true)
             .read(true)
-            .append(false)
+            .custom_flags(libc::O_DSYNC)
             .open(path)?;
-        file.seek(SeekFrom::Start(offset))?;
 
-        let size = file.write(&content)?;
-        file.flush()?;
-        Ok(size)
+        Ok(Box::new(file))
     }
 
-    pub fn write_at(
-        file: &mut File,
-        content: &mut Vec<u8>,
-        offset: u64,
-    ) -> Result<usize, std::io::Error> {
-        file.seek(SeekFrom::Start(offset))?;
+    fn open_dir(&self, path: &Path) -> io::Result<()> {
+        OpenOptions::new().read(true).open(path)?;
 
-        file.write_all(&content)?;
-        file.flush()?;
-        Ok(content.len())
+        Ok(())
     }
 
-    pub fn open_read<P: AsRef<Path>>(
-        path: P,
-        read_buffer: &mut Vec<u8>,
-    ) -> Result<usize, std::io::Error> {
-        let mut file = OpenOptions::new().read(true).open(path)?;
-        file.read(read_buffer)
+    fn sync_dir(&self, path: &Path) -> io::Result<()> {
+        File::open(path)?.sync_all()
     }
 
-    pub fn open_read_at<P: AsRef<Path>>(
-        path: P,
-        read_buffer: &mut Vec<u8>,
-        offset: u64,
-    ) -> Result<usize, std::io::Error> {
-        let mut file = OpenOptions::new().read(true).open(path)?;
-        file.seek(SeekFrom::Start(offset))?;
-        file.read(read_buffer)
+    fn stat(&self, path: &Path) -> io::Result<FileStat> {
+        let metadata = std::fs::metadata(path)?;
+
+        Ok(FileStat {
+            len: metadata.len(),
+            is_dir: metadata.is_dir(),
+        })
     }
 
-    pub fn read_at(
-        file: &mut File,
-        read_buffer: &mut Vec<u8>,
-        offset: u64,
-    ) -> Result<usize, std::io::Error> {
-        file.seek(SeekFrom::Start(offset))?;
-        file.read(read_buffer)
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        std::fs::rename(from, to)
     }
 
-    pub fn remove_file<P: AsRef<Path>>(path: P) -> Result<(), std::io::Error> {
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
         remove_file(path)
     }
 
-    pub fn remove_dir<P: AsRef<Path>>(path: P) -> Result<(), std::io::Error> {
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
         remove_dir_all(path)
     }
 
-    pub fn metadata<P: AsRef<Path>>(path: P) -> Result<std::fs::Metadata, std::io::Error> {
-        std::fs::metadata(path)
+    fn readdir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
     }
 
-    pub fn rename<F: AsRef<Path>, T: AsRef<Path>>(from: F, to: T) -> Result<(), std::io::Error> {
-        std::fs::rename(from, to)
+    #[cfg(target_os = "linux")]
+    fn copy_range(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let from = File::open(from)?;
+        let to = new_file(to)?;
+        let mut len = from.metadata()?.len() as usize;
+        // the copy is done from and to the file offsets, which copy_file_range moves forward
+        while len > 0 {
+            let copied = unsafe {
+                libc::copy_file_range(
+                    from.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    to.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    len,
+                    0,
+                )
+            };
+            match copied {
+                -1 => return Err(io::Error::last_os_error()),
+                0 => break,
+                copied => len -= copied as usize,
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn reflink(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let from = File::open(from)?;
+        let to = new_file(to)?;
+        if unsafe { libc::ioctl(to.as_raw_fd(), FICLONE as _, from.as_raw_fd()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+// the ioctl request of cloning a file, _IOW(0x94, 9, int)
+#[cfg(target_os = "linux")]
+const FICLONE: libc::c_ulong = 0x40049409;
+
+// create a new file to copy to, or truncate it if it exists
+#[cfg(target_os = "linux")]
+fn new_file(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+}
+
+// the error of an operation the backend does not support
+fn unsupported(operation: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("the backend does not support {}", operation),
+    )
+}
+
+impl FileHandle for File {
+    fn read_at(&mut self, buffer: &mut [u8], offset: u64) -> io::Result<usize> {
+        FileExt::read_at(self, buffer, offset)
+    }
+
+    fn write_at(&mut self, buffer: &[u8], offset: u64) -> io::Result<usize> {
+        FileExt::write_at(self, buffer, offset)
     }
 
-    pub fn truncate<P: AsRef<Path>>(path: P) -> Result<(), std::io::Error> {
-        let file = Fs::open_file(path)?;
-        file.set_len(0)
+    fn sync_all(&mut self) -> io::Result<()> {
+        File::sync_all(self)
     }
 
+    fn sync_data(&mut self) -> io::Result<()> {
+        File::sync_data(self)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn sync_range(&mut self, offset: u64, len: u64) -> io::Result<()> {
+        let flags = libc::SYNC_FILE_RANGE_WAIT_BEFORE
+            | libc::SYNC_FILE_RANGE_WRITE
+            | libc::SYNC_FILE_RANGE_WAIT_AFTER;
+        let result = unsafe {
+            libc::sync_file_range(
+                self.as_raw_fd(),
+                offset as libc::off64_t,
+                len as libc::off64_t,
+                flags,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        File::set_len(self, len)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn allocate(&mut self, offset: u64, len: u64) -> io::Result<()> {
+        fallocate(self, 0, offset, len)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn punch_hole(&mut self, offset: u64, len: u64) -> io::Result<()> {
+        fallocate(
+            self,
+            libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+            offset,
+            len,
+        )
+    }
+
+    fn map(&mut self, len: usize) -> io::Result<Mmap> {
+        Mmap::map(self, len)
+    }
+
+    fn try_clone_file(&self) -> io::Result<File> {
+        self.try_clone()
+    }
+
+    fn len(&mut self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn fallocate(file: &File, mode: libc::c_int, offset: u64, len: u64) -> io::Result<()> {
+    let result = unsafe {
+        libc::fallocate(
+            file.as_raw_fd(),
+            mode,
+            offset as libc::off_t,
+            len as libc::off_t,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+///
+/// The helpers of the paths and filesets of the benchmarks
+///
+pub struct Fs;
+
+impl Fs {
     pub fn copy<F: AsRef<Path> + std::convert::AsRef<std::ffi::OsStr>, T: AsRef<Path>>(
         from: F,
         to: T,
@@ -166,8 +471,11 @@ impl Fs {
         Ok(())
     }
 
-    pub fn cleanup(path: &PathBuf) -> Result<(), Error> {
-        let spinner = ProgressBar::new_spinner();
+    ///
+    /// Remove the fileset of a benchmark from the filesystem backend
+    ///
+    pub fn cleanup(fs: &dyn FileSystem, path: &PathBuf) -> Result<(), Error> {
+        let spinner = output::spinner();
         spinner.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}"));
         spinner.set_message(format!(
             "clean up {}",
@@ -177,8 +485,8 @@ impl Fs {
         ));
         let progress = Progress::start(spinner);
 
-        if path.exists() {
-            remove_dir_all(path).unwrap();
+        if fs.exists(path) {
+            fs.remove_dir(path)?;
         }
         // finish the progress
         progress.finish_and_clear()?;
@@ -208,3 +516,90 @@ impl Fs {
         Ok(new_path)
     }
 }
+
+///
+/// A directory of a test under the temporary directory, removed with its content when dropped,
+/// even if the test fails
+///
+#[cfg(test)]
+pub(crate) struct TempDir(pub PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub fn new(name: &str) -> io::Result<Self> {
+        let path = std::env::temp_dir().join(format!("fs-bench-{}-{}", name, std::process::id()));
+        create_dir_all(&path)?;
+
+        Ok(Self(path))
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn std_fs_test() -> Result<(), Error> {
+        let fs: &dyn FileSystem = &StdFs;
+        let dir = TempDir::new("fs")?;
+        let path = dir.0.join("a").join("0");
+
+        fs.mkdir_all(&dir.0.join("a"))?;
+        let mut file = fs.create(&path)?;
+        file.write_all_at(b"fs-bench", 2)?;
+        assert_eq!(file.len()?, 10);
+        let mut buffer = [0u8; 5];
+        file.read_exact_at(&mut buffer, 5)?;
+        assert_eq!(&buffer, b"bench");
+        assert!(file.read_exact_at(&mut buffer, 8).is_err());
+
+        let to = dir.0.join("a").join("1");
+        fs.rename(&path, &to)?;
+        assert_eq!(fs.readdir(&dir.0.join("a"))?, vec![to.clone()]);
+        assert_eq!(
+            fs.stat(&to)?,
+            FileStat {
+                len: 10,
+                is_dir: false
+            }
+        );
+        fs.truncate(&to, 0)?;
+        assert_eq!(fs.stat(&to)?.len, 0);
+
+        let mut file = fs.open_dsync(&to)?;
+        file.write_all_at(b"fs-bench", 0)?;
+        file.sync_range(0, 8)?;
+        fs.sync_dir(&dir.0.join("a"))?;
+
+        // a hole reads as zeros, and the copy has the same content, unless the filesystem of the
+        // temporary directory supports neither
+        let copy = dir.0.join("a").join("2");
+        match file
+            .allocate(0, 8192)
+            .and_then(|_| file.punch_hole(0, 4096))
+            .and_then(|_| fs.copy_range(&to, &copy))
+        {
+            Ok(_) => {
+                assert_eq!(file.len()?, 8192);
+                let mut buffer = [1u8; 8];
+                fs.open(&copy)?.read_exact_at(&mut buffer, 0)?;
+                assert_eq!(buffer, [0u8; 8]);
+                assert_eq!(fs.stat(&copy)?.len, 8192);
+            }
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        Fs::cleanup(fs, &dir.0)?;
+        assert!(!fs.exists(&dir.0));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+use crate::checksum::fnv1a64;
+use crate::error::Error;
+use crate::{BenchResult, Record};
+use log::error;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+// the content is generated in blocks, so that any range of a file can be generated independently
+const BLOCK_SIZE: u64 = 4096;
+
+///
+/// Generates deterministic, seed-derived file content and verifies the content read back.
+///
+/// Each byte of a file is a function of the seed, the file key and the byte offset, so the content
+/// written to any range of a file is the same as what the setup has generated for that range. This
+/// way the reads can be verified regardless of the order of the reads and writes.
+///
+#[derive(Clone)]
+pub struct Verifier {
+    seed: u64,
+    // the renamed files: the current file key mapped to the key its content was generated with
+    renames: Arc<Mutex<HashMap<String, String>>>,
+}
+
+///
+/// The content read from a file that differs from the expected content
+///
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub file: String,
+    pub offset: u64, // the offset of the first mismatching byte
+    pub len: usize,  // the length of the read
+}
+
+impl From<Mismatch> for Record {
+    fn from(mismatch: Mismatch) -> Self {
+        Self {
+            fields: [
+                mismatch.file,
+                mismatch.offset.to_string(),
+                mismatch.len.to_string(),
+            ]
+            .to_vec(),
+        }
+    }
+}
+
+impl Verifier {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            renames: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    ///
+    /// Generate the content of the file with the key from the offset with the length len
+    ///
+    pub fn content(&self, key: &str, offset: u64, len: usize) -> Result<Vec<u8>, Error> {
+        let file_seed = self.seed ^ fnv1a64(self.original_key(key)?.as_bytes());
+
+        let end = offset + len as u64;
+        let mut content = Vec::with_capacity(len);
+        let mut block = offset / BLOCK_SIZE;
+        while block * BLOCK_SIZE < end {
+            let block_start = block * BLOCK_SIZE;
+            let from = (offset.max(block_start) - block_start) as usize;
+            let to = (end.min(block_start + BLOCK_SIZE) - block_start) as usize;
+
+            let mut state = file_seed ^ block.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            let mut block_content = Vec::with_capacity(BLOCK_SIZE as usize);
+            while block_content.len() < to {
+                block_content.extend_from_slice(&splitmix64(&mut state).to_le_bytes());
+            }
+            content.extend_from_slice(&block_content[from..to]);
+
+            block += 1;
+        }
+
+        Ok(content)
+    }
+
+    ///
+    /// Verify the data read from the offset of the file with the key
+    ///
+    pub fn verify(&self, key: &str, offset: u64, data: &[u8]) -> Result<Option<Mismatch>, Error> {
+        let expected = self.content(key, offset, data.len())?;
+        let mismatch = data
+            .iter()
+            .zip(expected.iter())
+            .position(|(read, expected)| read != expected)
+            .map(|pos| Mismatch {
+                file: key.to_string(),
+                offset: offset + pos as u64,
+                len: data.len(),
+            });
+
+        if let Some(mismatch) = mismatch.as_ref() {
+            error!(
+                "data mismatch: {} at offset {} (read of {} bytes)",
+                mismatch.file, mismatch.offset, mismatch.len
+            );
+        }
+
+        Ok(mismatch)
+    }
+
+    ///
+    /// Keep track of a renamed file, so that its content is still generated with its original key
+    ///
+    pub fn renamed(&self, from: &str, to: &str) -> Result<(), Error> {
+        let original = self.original_key(from)?;
+        let mut renames = self.renames.lock()?;
+        renames.remove(from);
+        renames.insert(to.to_string(), original);
+
+        Ok(())
+    }
+
+    ///
+    /// Forget the renames to the key of a newly created file
+    ///
+    pub fn created(&self, key: &str) -> Result<(), Error> {
+        self.renames.lock()?.remove(key);
+
+        Ok(())
+    }
+
+    fn original_key(&self, key: &str) -> Result<String, Error> {
+        Ok(self
+            .renames
+            .lock()?
+            .get(key)
+            .cloned()
+            .unwrap_or_else(|| key.to_string()))
+    }
+}
+
+///
+/// Log the mismatches to the specified path
+///
+pub fn log_mismatches<P: AsRef<Path> + std::convert::AsRef<std::ffi::OsStr>>(
+    mismatches: Vec<Mismatch>,
+    file_name: &P,
+) -> Result<(), Error> {
+    let header = [
+        "file".to_string(),
+        "offset".to_string(),
+        "length".to_string(),
+    ]
+    .to_vec();
+    let mut results = BenchResult::new(header);
+    results.add_records(mismatches.into_iter().map(Record::from).collect())?;
+    results.log(file_name)
+}
+
+// the SplitMix64 generator, which is fast and generates the same sequence on every platform
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::integrity::Verifier;
+
+    #[test]
+    fn verifier_test() {
+        let verifier = Verifier::new(42);
+        let content = verifier.content("file", 0, 10_000).unwrap();
+
+        // any range generates the same bytes as the whole file
+        assert_eq!(
+            verifier.content("file", 4000, 300).unwrap(),
+            content[4000..4300].to_vec()
+        );
+        assert!(verifier
+            .verify("file", 100, &content[100..9000])
+            .unwrap()
+            .is_none());
+
+        let mut corrupted = content[100..9000].to_vec();
+        corrupted[5000] ^= 0xFF;
+        let mismatch = verifier.verify("file", 100, &corrupted).unwrap().unwrap();
+        assert_eq!(mismatch.offset, 5100);
+
+        assert_ne!(verifier.content("other", 0, 100).unwrap(), content[..100]);
+        verifier.renamed("file", "other").unwrap();
+        assert_eq!(verifier.content("other", 0, 100).unwrap(), content[..100]);
+    }
+}
@@ -0,0 +1,54 @@
+use crate::clock::Timestamp;
+use crate::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+// set when a SIGINT or SIGTERM is received
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// how often a waiting benchmark checks for an interruption
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+///
+/// Install a SIGINT/SIGTERM handler that asks the running benchmark to stop, so that the results
+/// collected so far can be logged. A second signal terminates the process immediately.
+///
+pub fn install_handler() -> Result<(), Error> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+        eprintln!("\ninterrupted, logging the collected results...");
+    })?;
+
+    Ok(())
+}
+
+///
+/// Whether the benchmark has been interrupted
+///
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+///
+/// Sleep for the duration or until the benchmark is interrupted, and return the time slept
+///
+pub fn sleep(duration: Duration) -> Duration {
+    sleep_until(duration, || false)
+}
+
+///
+/// Sleep for the duration, until the benchmark is interrupted, or until done returns true, e.g.
+/// when a worker has stopped by itself, and return the time slept
+///
+pub fn sleep_until<F: Fn() -> bool>(duration: Duration, done: F) -> Duration {
+    let start = Timestamp::now();
+    loop {
+        let elapsed = start.elapsed();
+        if elapsed >= duration || interrupted() || done() {
+            return elapsed;
+        }
+        std::thread::sleep(POLL_INTERVAL.min(duration - elapsed));
+    }
+}
@@ -1,21 +1,43 @@
+mod checksum;
+pub mod clock;
+pub mod durability;
+pub mod engine;
 pub mod error;
+pub mod failures;
 mod format;
 pub mod fs;
+pub mod integrity;
+pub mod interrupt;
 pub mod micro;
+pub mod mmap;
+pub mod output;
 pub mod plotter;
 mod progress;
+pub mod replot;
+pub mod report;
+pub mod results;
 pub mod stats;
 pub mod trace_workload;
+pub mod write_sync;
 
+use crate::durability::SyncPolicy;
+use crate::engine::{IoEngine, UringConfig};
 use crate::error::Error;
-use crate::micro::BenchFn;
+use crate::fs::{FileSystem, StdFs};
+use crate::integrity::Verifier;
+use crate::micro::{BenchFn, FileLayout};
+use crate::plotter::PlotStyle;
+use crate::results::RunResults;
+use crate::stats::{CiMethod, Statistics};
+use crate::write_sync::WriteSyncPolicy;
 use byte_unit::Byte;
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 use std::fs::{remove_file, OpenOptions};
-use std::ops::Add;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::time::{Duration, SystemTime};
+use std::sync::Arc;
+use std::time::Duration;
 
 ///
 /// The Benchmark trait including configurations and common behaviours
@@ -55,7 +77,10 @@ pub trait Bench {
 
     fn setup(&self, path: &PathBuf, invalidate_cache: bool) -> Result<(), Error>;
 
-    fn run(&self, bench_fn: Option<BenchFn>) -> Result<(), Error>;
+    ///
+    /// Run the benchmark, log its results to the log path, and return the typed results
+    ///
+    fn run(&self, bench_fn: Option<BenchFn>) -> Result<RunResults, Error>;
 }
 
 ///
@@ -72,10 +97,36 @@ pub struct Config {
     pub fs_names: Vec<String>,
     pub log_path: PathBuf,
     pub parallelism_degree: usize,
+    pub sync_policy: SyncPolicy, // when to sync the records in the durability verification
+    pub verify_only: bool,       // only verify the records written by a previous durability run
+    pub verify: bool,            // generate deterministic content and verify the content of reads
+    pub seed: u64,               // the seed of the generated content
+    pub max_failures: Option<u64>, // abort a benchmark if more operations than this fail
+    pub keep_fileset: bool,      // keep the fileset when a benchmark is interrupted
+    pub bench_fns: Vec<BenchFn>, // the benchmark functions to run in static mode
+    pub repetitions: usize,      // the number of runs of each benchmark function in static mode
+    pub significance_level: f64, // the level to decide if the difference of filesystems is significant
+    pub confidence_level: f64,   // the confidence level of the confidence intervals
+    pub bootstrap_iterations: usize, // the number of bootstrap resamples
+    pub ci_method: CiMethod,     // the method of calculating the confidence intervals of mean
+    pub precision: Option<f64>,  // run until the ops/s reaches this relative precision, if set
+    pub exclude_outliers: bool,  // leave the outlier windows out of the ops/s mean and CI
+    pub window: Option<u64>, // the time window length in milliseconds, chosen from the run time if not set
+    pub plot_style: PlotStyle, // the output format, size, fonts, scale and colours of the plots
+    pub quiet: bool,         // suppress the progress bars and printing
+    pub backend: Arc<dyn FileSystem>, // the filesystem backend the benchmark operations run on
+    pub io_engine: IoEngine, // the engine the reads, writes and syncs are run by
+    pub uring: UringConfig,  // the queue depth, batch and registrations of the io_uring engine
+    pub io_threads: Option<usize>, // the threads reading and writing the files in chunks in throughput mode
+    pub write_sync: WriteSyncPolicy, // how and when the writes of write_sync are synced
+    pub dir_sync: bool,            // fsync the parent directory after each mkdir and mknod
+    pub file_layout: FileLayout,   // how the files of the fileset are created in the setup
+    pub dir_entries: usize,        // the entries the directory is filled with in directory mode
+    pub dir_step: Option<usize>, // the entries between the measurements in directory mode, a 20th of them if not set
 }
 
 impl Config {
-    fn new<P: AsRef<Path> + std::convert::AsRef<std::ffi::OsStr>>(
+    pub fn new<P: AsRef<Path> + std::convert::AsRef<std::ffi::OsStr>>(
         io_size: Option<String>,
         file_size: Option<String>,
         fileset_size: Option<usize>,
@@ -86,78 +137,447 @@ impl Config {
         log_path: P,
         parallelism_degree: Option<usize>,
     ) -> Result<Self, Error> {
-        let io_size = if let Some(io_size) = io_size {
-            let io_size = Byte::from_str(io_size)?;
-            io_size.get_bytes() as usize
-        } else {
-            4096 // the default io_size: 4 KiB
-        };
+        let mut builder = ConfigBuilder::new(log_path);
+        if let Some(io_size) = io_size {
+            builder = builder.io_size(io_size);
+        }
+        if let Some(file_size) = file_size {
+            builder = builder.file_size(file_size);
+        }
+        if let Some(fileset_size) = fileset_size {
+            builder = builder.fileset_size(fileset_size);
+        }
+        if let Some(run_time) = run_time {
+            builder = builder.run_time(run_time);
+        }
+        if let Some(workload) = workload {
+            builder = builder.workload(workload);
+        }
+        for (mount_path, fs_name) in mount_paths.iter().zip(fs_names) {
+            builder = builder.filesystem(fs_name, mount_path);
+        }
+        if let Some(parallelism_degree) = parallelism_degree {
+            builder = builder.parallelism_degree(parallelism_degree);
+        }
+
+        builder.build()
+    }
 
-        let file_size = if let Some(file_size) = file_size {
-            let file_size = Byte::from_str(file_size)?;
-            file_size.get_bytes() as usize
+    ///
+    /// A builder of the configuration, starting from the defaults
+    ///
+    pub fn builder<P: AsRef<Path>>(log_path: P) -> ConfigBuilder {
+        ConfigBuilder::new(log_path)
+    }
+
+    ///
+    /// The verifier of the content of reads, if the verification is enabled
+    ///
+    pub fn verifier(&self) -> Option<Verifier> {
+        if self.verify {
+            Some(Verifier::new(self.seed))
         } else {
-            // 4096
-            4096
-        };
+            None
+        }
+    }
 
-        if io_size > file_size {
-            return Err(Error::InvalidConfig(format!(
-                "The file size ({}) cannot be smaller than the io size ({})",
-                file_size, io_size
-            )));
+    ///
+    /// The length of the time windows the ops/s are counted in. If it is not set, it is chosen
+    /// from the run time rather than from each run's duration, so that the runs of all the
+    /// filesystems are counted in the same time windows.
+    ///
+    pub fn window(&self) -> Duration {
+        match self.window {
+            Some(window) => Duration::from_millis(window),
+            None => Statistics::window_length(Duration::from_secs_f64(self.run_time)),
         }
+    }
 
-        let fileset_size = if let Some(fileset_size) = fileset_size {
-            fileset_size
-        } else {
-            // 10000 // the default fileset_size: 10000
-            10_000
-        };
+    ///
+    /// The number of entries created between the measurements of the directory mode
+    ///
+    pub fn dir_step(&self) -> usize {
+        self.dir_step.unwrap_or((self.dir_entries / 20).max(1))
+    }
+}
 
-        let parallelism_degree = if let Some(parallelism_degree) = parallelism_degree {
-            parallelism_degree
-        } else {
-            // the default parallelism_degree: 4
-            4
-        };
+///
+/// The builder of the configuration, to configure the benchmarks programmatically, e.g. from the
+/// integration tests of a filesystem. The options not set keep their defaults, and are validated
+/// when the configuration is built.
+///
+pub struct ConfigBuilder {
+    config: Config,
+    io_size: Option<String>, // parsed when the configuration is built, e.g. "4 KiB"
+    file_size: Option<String>, // parsed when the configuration is built
+}
 
-        let run_time = if let Some(run_time) = run_time {
-            run_time
-        } else {
-            60.0 // the default run_time: 60 seconds
+impl ConfigBuilder {
+    pub fn new<P: AsRef<Path>>(log_path: P) -> Self {
+        let config = Config {
+            io_size: 4096,   // the default io_size: 4 KiB
+            file_size: 4096, // the default file_size: 4 KiB
+            fileset_size: 10_000,
+            run_time: 60.0, // the default run_time: 60 seconds
+            warmup_time: 5,
+            workload: PathBuf::new(),
+            mount_paths: vec![],
+            fs_names: vec![],
+            log_path: log_path.as_ref().to_path_buf(),
+            parallelism_degree: 4,
+            sync_policy: SyncPolicy::Always,
+            verify_only: false,
+            verify: false,
+            seed: 0,
+            max_failures: None,
+            keep_fileset: false,
+            bench_fns: BenchFn::defaults(),
+            repetitions: 1,
+            significance_level: 0.05,
+            confidence_level: 0.95,
+            bootstrap_iterations: 1000,
+            ci_method: CiMethod::Percentile,
+            precision: None,
+            exclude_outliers: false,
+            window: None,
+            plot_style: PlotStyle::new(),
+            quiet: false,
+            backend: Arc::new(StdFs),
+            io_engine: IoEngine::Sync,
+            uring: UringConfig::default(),
+            io_threads: None,
+            write_sync: WriteSyncPolicy::default(),
+            dir_sync: false,
+            file_layout: FileLayout::Written,
+            dir_entries: 1_000_000,
+            dir_step: None,
         };
 
-        let workload = if let Some(workload) = workload {
-            let workload = Path::new(&workload);
-            PathBuf::from(workload)
-        } else {
-            PathBuf::new()
-        };
+        Self {
+            config,
+            io_size: None,
+            file_size: None,
+        }
+    }
 
-        let log_path = Path::new(&log_path);
-        let log_path = PathBuf::from(log_path);
+    pub fn io_size<S: Into<String>>(mut self, io_size: S) -> Self {
+        self.io_size = Some(io_size.into());
+        self
+    }
 
-        let mount_paths = mount_paths
-            .iter()
-            .map(|mp| {
-                let mount_path = Path::new(mp);
-                PathBuf::from(mount_path)
-            })
-            .collect::<Vec<PathBuf>>();
+    pub fn file_size<S: Into<String>>(mut self, file_size: S) -> Self {
+        self.file_size = Some(file_size.into());
+        self
+    }
 
-        Ok(Self {
-            io_size,
-            file_size,
-            fileset_size,
-            run_time,
-            warmup_time: 5,
-            workload,
-            mount_paths,
-            fs_names,
-            log_path,
-            parallelism_degree,
-        })
+    pub fn fileset_size(mut self, fileset_size: usize) -> Self {
+        self.config.fileset_size = fileset_size;
+        self
+    }
+
+    pub fn run_time(mut self, run_time: f64) -> Self {
+        self.config.run_time = run_time;
+        self
+    }
+
+    pub fn warmup_time(mut self, warmup_time: u64) -> Self {
+        self.config.warmup_time = warmup_time;
+        self
+    }
+
+    pub fn workload<P: AsRef<Path>>(mut self, workload: P) -> Self {
+        self.config.workload = workload.as_ref().to_path_buf();
+        self
+    }
+
+    ///
+    /// Add a filesystem to benchmark, by its name in the results and its mount path
+    ///
+    pub fn filesystem<S: Into<String>, P: AsRef<Path>>(
+        mut self,
+        fs_name: S,
+        mount_path: P,
+    ) -> Self {
+        self.config.fs_names.push(fs_name.into());
+        self.config
+            .mount_paths
+            .push(mount_path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn parallelism_degree(mut self, parallelism_degree: usize) -> Self {
+        self.config.parallelism_degree = parallelism_degree;
+        self
+    }
+
+    pub fn sync_policy(mut self, sync_policy: SyncPolicy) -> Self {
+        self.config.sync_policy = sync_policy;
+        self
+    }
+
+    pub fn verify_only(mut self, verify_only: bool) -> Self {
+        self.config.verify_only = verify_only;
+        self
+    }
+
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.config.verify = verify;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed = seed;
+        self
+    }
+
+    pub fn max_failures(mut self, max_failures: u64) -> Self {
+        self.config.max_failures = Some(max_failures);
+        self
+    }
+
+    pub fn keep_fileset(mut self, keep_fileset: bool) -> Self {
+        self.config.keep_fileset = keep_fileset;
+        self
+    }
+
+    pub fn bench_fns(mut self, bench_fns: Vec<BenchFn>) -> Self {
+        self.config.bench_fns = bench_fns;
+        self
+    }
+
+    pub fn repetitions(mut self, repetitions: usize) -> Self {
+        self.config.repetitions = repetitions;
+        self
+    }
+
+    pub fn significance_level(mut self, significance_level: f64) -> Self {
+        self.config.significance_level = significance_level;
+        self
+    }
+
+    pub fn confidence_level(mut self, confidence_level: f64) -> Self {
+        self.config.confidence_level = confidence_level;
+        self
+    }
+
+    pub fn bootstrap_iterations(mut self, bootstrap_iterations: usize) -> Self {
+        self.config.bootstrap_iterations = bootstrap_iterations;
+        self
+    }
+
+    pub fn ci_method(mut self, ci_method: CiMethod) -> Self {
+        self.config.ci_method = ci_method;
+        self
+    }
+
+    pub fn precision(mut self, precision: f64) -> Self {
+        self.config.precision = Some(precision);
+        self
+    }
+
+    pub fn exclude_outliers(mut self, exclude_outliers: bool) -> Self {
+        self.config.exclude_outliers = exclude_outliers;
+        self
+    }
+
+    ///
+    /// The time window length in milliseconds
+    ///
+    pub fn window(mut self, window: u64) -> Self {
+        self.config.window = Some(window);
+        self
+    }
+
+    pub fn plot_style(mut self, plot_style: PlotStyle) -> Self {
+        self.config.plot_style = plot_style;
+        self
+    }
+
+    ///
+    /// The filesystem backend the benchmark operations run on, instead of the mounted filesystems
+    ///
+    pub fn backend(mut self, backend: Arc<dyn FileSystem>) -> Self {
+        self.config.backend = backend;
+        self
+    }
+
+    ///
+    /// The engine the reads, writes and syncs of the static and throughput benchmarks are run by.
+    /// The io_uring engine opens the files of the fileset by their paths, bypassing the backend.
+    ///
+    pub fn io_engine(mut self, io_engine: IoEngine) -> Self {
+        self.config.io_engine = io_engine;
+        self
+    }
+
+    pub fn uring(mut self, uring: UringConfig) -> Self {
+        self.config.uring = uring;
+        self
+    }
+
+    ///
+    /// Read and write each file of throughput mode in io_size chunks, issued by this many threads
+    /// of the sync io engine, instead of in one read or write
+    ///
+    pub fn io_threads(mut self, io_threads: usize) -> Self {
+        self.config.io_threads = Some(io_threads);
+        self
+    }
+
+    ///
+    /// How and when the writes of the write_sync benchmark function are synced
+    ///
+    pub fn write_sync(mut self, write_sync: WriteSyncPolicy) -> Self {
+        self.config.write_sync = write_sync;
+        self
+    }
+
+    ///
+    /// Fsync the parent directory after each mkdir and mknod
+    ///
+    pub fn dir_sync(mut self, dir_sync: bool) -> Self {
+        self.config.dir_sync = dir_sync;
+        self
+    }
+
+    ///
+    /// How the files of the fileset are created in the setup: written, preallocated or sparse
+    ///
+    pub fn file_layout(mut self, file_layout: FileLayout) -> Self {
+        self.config.file_layout = file_layout;
+        self
+    }
+
+    ///
+    /// The number of entries the directory is filled with in directory mode
+    ///
+    pub fn dir_entries(mut self, dir_entries: usize) -> Self {
+        self.config.dir_entries = dir_entries;
+        self
+    }
+
+    ///
+    /// The number of entries created between the measurements of the directory mode
+    ///
+    pub fn dir_step(mut self, dir_step: usize) -> Self {
+        self.config.dir_step = Some(dir_step);
+        self
+    }
+
+    ///
+    /// Suppress the progress bars and printing of the benchmarks
+    ///
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.config.quiet = quiet;
+        self
+    }
+
+    ///
+    /// Parse and validate the options, and build the configuration
+    ///
+    pub fn build(self) -> Result<Config, Error> {
+        let mut config = self.config;
+        if let Some(io_size) = self.io_size {
+            config.io_size = Byte::from_str(io_size)?.get_bytes() as usize;
+        }
+        if let Some(file_size) = self.file_size {
+            config.file_size = Byte::from_str(file_size)?.get_bytes() as usize;
+        }
+
+        if config.io_size > config.file_size {
+            return Err(Error::InvalidConfig(format!(
+                "The file size ({}) cannot be smaller than the io size ({})",
+                config.file_size, config.io_size
+            )));
+        }
+        if config.repetitions == 0 || config.repetitions == 2 {
+            return Err(Error::InvalidConfig(
+                "The repetitions should be 1, or at least 3 to compare the runs".to_string(),
+            ));
+        }
+        if config.significance_level <= 0f64 || config.significance_level >= 1f64 {
+            return Err(Error::InvalidConfig(
+                "The significance level should be in range (0, 1)".to_string(),
+            ));
+        }
+        if config.confidence_level <= 0f64 || config.confidence_level >= 1f64 {
+            return Err(Error::InvalidConfig(
+                "The confidence level should be in range (0, 1)".to_string(),
+            ));
+        }
+        if config.bootstrap_iterations < 100 {
+            return Err(Error::InvalidConfig(
+                "There should be at least 100 bootstrap iterations".to_string(),
+            ));
+        }
+        if config.window == Some(0) {
+            return Err(Error::InvalidConfig(
+                "The time window should be at least 1 ms".to_string(),
+            ));
+        }
+        if let Some(precision) = config.precision {
+            if precision <= 0f64 {
+                return Err(Error::InvalidConfig(
+                    "The precision should be positive".to_string(),
+                ));
+            }
+        }
+        if !config.io_engine.is_supported() {
+            return Err(Error::InvalidConfig(format!(
+                "The {} io engine is not supported on this platform",
+                config.io_engine
+            )));
+        }
+        if config.uring.queue_depth == 0
+            || config.uring.batch == 0
+            || config.uring.batch > config.uring.queue_depth
+        {
+            return Err(Error::InvalidConfig(
+                "The io_uring batch should be between 1 and the queue depth".to_string(),
+            ));
+        }
+        match config.io_threads {
+            Some(0) => {
+                return Err(Error::InvalidConfig(
+                    "There should be at least 1 io thread".to_string(),
+                ))
+            }
+            Some(_) if config.io_engine != IoEngine::Sync => {
+                return Err(Error::InvalidConfig(
+                    "The io threads are only used by the sync io engine".to_string(),
+                ))
+            }
+            _ => {}
+        }
+        if config.io_engine == IoEngine::IoUring && config.write_sync != WriteSyncPolicy::default()
+        {
+            return Err(Error::InvalidConfig(
+                "The io_uring io engine only syncs each write with fdatasync".to_string(),
+            ));
+        }
+        if config.verify && config.file_layout != FileLayout::Written {
+            return Err(Error::InvalidConfig(
+                "Only the written files of the fileset have content to verify".to_string(),
+            ));
+        }
+        if config.dir_entries == 0 {
+            return Err(Error::InvalidConfig(
+                "The directory should be filled with at least 1 entry".to_string(),
+            ));
+        }
+        if config.dir_step == Some(0) || config.dir_step() > config.dir_entries {
+            return Err(Error::InvalidConfig(format!(
+                "The directory step should be between 1 and the directory entries ({})",
+                config.dir_entries
+            )));
+        }
+        if config.bench_fns.is_empty() {
+            return Err(Error::InvalidConfig(
+                "All the benchmark functions are excluded".to_string(),
+            ));
+        }
+
+        Ok(config)
     }
 }
 
@@ -170,6 +590,8 @@ pub enum BenchMode {
     RealTime,
     Trace,
     Throughput,
+    Durability,
+    Directory,
 }
 
 impl FromStr for BenchMode {
@@ -181,7 +603,13 @@ impl FromStr for BenchMode {
             "realtime" => Ok(BenchMode::RealTime),
             "trace" => Ok(BenchMode::Trace),
             "throughput" => Ok(BenchMode::Throughput),
-            _ => Err("valid benckmark modes are: static, realtime, trace, throughput".to_string()),
+            "durability" => Ok(BenchMode::Durability),
+            "directory" => Ok(BenchMode::Directory),
+            _ => Err(
+                "valid benckmark modes are: static, realtime, trace, throughput, durability, \
+                directory"
+                    .to_string(),
+            ),
         }
     }
 }
@@ -193,6 +621,8 @@ impl Display for BenchMode {
             BenchMode::RealTime => write!(f, "realtime"),
             BenchMode::Trace => write!(f, "trace"),
             BenchMode::Throughput => write!(f, "throughput"),
+            BenchMode::Durability => write!(f, "durability"),
+            BenchMode::Directory => write!(f, "directory"),
         }
     }
 }
@@ -200,7 +630,8 @@ impl Display for BenchMode {
 ///
 /// Results modes generated by the fs-bench benchmarks
 ///
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ResultMode {
     OpsPerSecond,
     Throughput,
@@ -208,6 +639,9 @@ pub enum ResultMode {
     OpTimes,
     SampleOpsPerSecond,
     AccumulatedTimes,
+    DirectoryScaling,
+    Repetitions,
+    Significance,
 }
 
 impl FromStr for ResultMode {
@@ -221,7 +655,15 @@ impl FromStr for ResultMode {
             "op_times" => Ok(ResultMode::OpTimes),
             "sample_ops_per_second" => Ok(ResultMode::SampleOpsPerSecond),
             "accumulated_times" => Ok(ResultMode::AccumulatedTimes),
-            _ => Err("valid result modes are: ops_per_second, throughput, behaviour".to_string()),
+            "directory_scaling" => Ok(ResultMode::DirectoryScaling),
+            "repetitions" => Ok(ResultMode::Repetitions),
+            "significance" => Ok(ResultMode::Significance),
+            _ => Err(
+                "valid result modes are: ops_per_second, throughput, behaviour, op_times, \
+                sample_ops_per_second, accumulated_times, directory_scaling, repetitions, \
+                significance"
+                    .to_string(),
+            ),
         }
     }
 }
@@ -235,6 +677,9 @@ impl Display for ResultMode {
             ResultMode::OpTimes => write!(f, "op_times"),
             ResultMode::SampleOpsPerSecond => write!(f, "sample_ops_per_second"),
             ResultMode::AccumulatedTimes => write!(f, "accumulated_times"),
+            ResultMode::DirectoryScaling => write!(f, "directory_scaling"),
+            ResultMode::Repetitions => write!(f, "repetitions"),
+            ResultMode::Significance => write!(f, "significance"),
         }
     }
 }
@@ -311,6 +756,31 @@ impl BenchResult {
 
         Ok(())
     }
+
+    ///
+    /// Read the bench results logged to the specified path
+    ///
+    pub fn read<P: AsRef<Path> + std::convert::AsRef<std::ffi::OsStr>>(
+        file_name: &P,
+    ) -> Result<Self, Error> {
+        let mut reader = csv::Reader::from_path(Path::new(file_name))?;
+        let header = reader
+            .headers()?
+            .iter()
+            .map(|field| field.to_string())
+            .collect::<Vec<_>>();
+
+        let mut result = BenchResult::new(header);
+        for record in reader.records() {
+            let fields = record?
+                .iter()
+                .map(|field| field.to_string())
+                .collect::<Vec<_>>();
+            result.add_record(fields.into())?;
+        }
+
+        Ok(result)
+    }
 }
 
 ///
@@ -327,96 +797,72 @@ impl From<Vec<String>> for Record {
     }
 }
 
-impl Record {
-    ///
-    /// count the number of operations in a time window
-    /// the time window length is in milliseconds
-    /// the input times contains the timestamps in unix_time format. The first 10 digits are
-    /// date and time in seconds and the last 9 digits show the milliseconds
-    ///
-    pub fn ops_in_window(
-        times: &Vec<SystemTime>,
-        duration: Duration,
-    ) -> Result<Vec<Record>, Error> {
-        let len = times.len();
-        let first = times[0]; // first timestamp
-        let mut last = times[len - 1]; // last timestamp
-        if last.duration_since(first)? > duration {
-            last = first.add(duration);
-        }
-
-        // decide about the window length in millis
-        let duration = last.duration_since(first)?.as_secs_f64();
-        let window = if duration < 0.5 {
-            2
-        } else if duration < 1f64 {
-            5
-        } else if duration < 3f64 {
-            10
-        } else if duration < 5f64 {
-            20
-        } else if duration < 10f64 {
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn config_builder_test() -> Result<(), Error> {
+        let config = Config::builder("/tmp/results")
+            .filesystem("ext4", "/mnt/ext4")
+            .filesystem("xfs", "/mnt/xfs")
+            .io_size("8 KiB")
+            .file_size("64 KiB")
+            .run_time(10.0)
+            .bench_fns(vec![BenchFn::Mknod])
+            .quiet(true)
+            .build()?;
+        assert_eq!(config.io_size, 8192);
+        assert_eq!(config.file_size, 65536);
+        assert_eq!(config.fs_names, vec!["ext4", "xfs"]);
+        assert_eq!(config.mount_paths[1], PathBuf::from("/mnt/xfs"));
+        assert_eq!(config.fileset_size, 10_000);
+        assert!(config.quiet);
+
+        // the options are validated when the configuration is built
+        assert!(Config::builder("/tmp/results")
+            .io_size("64 KiB")
+            .build()
+            .is_err());
+        assert!(Config::builder("/tmp/results")
+            .repetitions(2)
+            .build()
+            .is_err());
+        assert!(Config::builder("/tmp/results")
+            .bench_fns(vec![])
+            .build()
+            .is_err());
+        assert!(Config::builder("/tmp/results")
+            .uring(UringConfig {
+                queue_depth: 4,
+                batch: 8,
+                ..UringConfig::default()
+            })
+            .build()
+            .is_err());
+        assert!(Config::builder("/tmp/results")
+            .io_engine(IoEngine::IoUring)
+            .io_threads(4)
+            .build()
+            .is_err());
+        assert!(Config::builder("/tmp/results")
+            .verify(true)
+            .file_layout(FileLayout::Sparse)
+            .build()
+            .is_err());
+        assert!(Config::builder("/tmp/results")
+            .dir_entries(1000)
+            .dir_step(2000)
+            .build()
+            .is_err());
+        assert_eq!(
+            Config::builder("/tmp/results")
+                .dir_entries(1000)
+                .build()?
+                .dir_step(),
             50
-        } else if duration < 20f64 {
-            70
-        } else if duration < 50f64 {
-            100
-        } else if duration < 100f64 {
-            150
-        } else if duration < 150f64 {
-            200
-        } else if duration < 200f64 {
-            500
-        } else if duration < 300f64 {
-            1000
-        } else {
-            5000
-        };
+        );
 
-        let mut records = vec![];
-
-        let mut next = first.add(Duration::from_millis(window));
-        let mut idx = 0;
-        let mut ops = 0;
-        while next < last {
-            while times[idx] < next {
-                // count ops in this time window
-                ops += 1;
-                idx += 1;
-            }
-            let time = next.duration_since(first)?.as_secs_f64();
-            let record = Record {
-                fields: [
-                    time.to_string(),
-                    // we have counted ops in a window length milliseconds, so the ops in
-                    // a second is (ops * 1000) / window
-                    ((ops * 1000) / window as usize).to_string(),
-                ]
-                .to_vec(),
-            };
-            records.push(record);
-
-            // go the next time window
-            next = next.add(Duration::from_millis(window));
-            ops = 0;
-        }
-
-        // count the remaining
-        if idx < len {
-            ops = len - idx;
-            let time = last.duration_since(first)?.as_secs_f64();
-            let record = Record {
-                fields: [
-                    time.to_string(),
-                    // we have counted ops in a window length milliseconds, so the ops in
-                    // a second is (ops * 1000) / window
-                    ((ops * 1000) / window as usize).to_string(),
-                ]
-                .to_vec(),
-            };
-            records.push(record);
-        }
-
-        Ok(records)
+        Ok(())
     }
 }
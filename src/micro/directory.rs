@@ -0,0 +1,445 @@
+use crate::clock::{self, Timestamp};
+use crate::failures::Failures;
+use crate::format::time_format;
+use crate::fs::Fs;
+use crate::interrupt;
+use crate::output::{self, outputln};
+use crate::plotter::{Indexes, Plotter};
+use crate::progress::Progress;
+use crate::results::RunResults;
+use crate::stats::percentile;
+use crate::{Bench, BenchFn, BenchResult, Config, Error, Record, ResultMode};
+use indicatif::ProgressStyle;
+use log::error;
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+use std::path::PathBuf;
+
+// the maximum number of random names looked up at each directory size
+const MAX_LOOKUPS: usize = 10_000;
+
+// the charts of the operations: the column of the results, the chart name, title and y label
+const CHARTS: [(usize, &str, &str, &str); 4] = [
+    (1, "create", "Create latency", "Latency (us)"),
+    (3, "lookup", "Lookup latency", "Latency (us)"),
+    (4, "readdir", "Readdir throughput", "Entries/s"),
+    (5, "unlink", "Unlink latency", "Latency (us)"),
+];
+
+///
+/// The large-directory stress benchmark: a single directory is filled with empty files up to the
+/// configured number of entries, and every step entries, the latencies of the creates since the
+/// last step, of stats of random existing names, and the throughput of a readdir of the whole
+/// directory are measured. The directory is then emptied in random order, and the latencies of
+/// the unlinks are measured at the same directory sizes.
+///
+pub struct DirectoryBench {
+    config: Config,
+}
+
+// the costs measured at a directory size, in seconds and entries/s
+struct Step {
+    entries: usize,
+    create: f64,
+    create_p99: f64,
+    lookup: f64,
+    readdir: f64,
+    unlink: Option<f64>,
+}
+
+impl Bench for DirectoryBench {
+    fn new(config: Config) -> Result<Self, Error> {
+        output::set_quiet(config.quiet);
+        Ok(Self { config })
+    }
+
+    fn setup(&self, path: &PathBuf, _invalidate_cache: bool) -> Result<(), Error> {
+        Fs::cleanup(self.config.backend.as_ref(), path)?;
+        self.config.backend.mkdir_all(path)?;
+
+        Ok(())
+    }
+
+    fn run(&self, _bench_fn: Option<BenchFn>) -> Result<RunResults, Error> {
+        let progress_style = ProgressStyle::default_bar().template("[{elapsed_precise}] {msg}");
+
+        let header = [
+            "entries".to_string(),
+            "create (us)".to_string(),
+            "create_p99 (us)".to_string(),
+            "lookup (us)".to_string(),
+            "readdir (entries/s)".to_string(),
+            "unlink (us)".to_string(),
+        ]
+        .to_vec();
+
+        let mut run_results = RunResults::new();
+        // a chart per operation, including all the filesystems
+        let mut plotters = CHARTS
+            .iter()
+            .map(|_| Plotter::with_style(self.config.plot_style.clone()))
+            .collect::<Vec<_>>();
+
+        for (idx, mount_path) in self.config.mount_paths.iter().enumerate() {
+            let fs_name = &self.config.fs_names[idx];
+            let mut root_path = mount_path.clone();
+            root_path.push("directory");
+            self.setup(&root_path, false)?;
+
+            let steps = self.scaling(&root_path, fs_name, progress_style.clone())?;
+            let records = steps
+                .iter()
+                .map(|step| {
+                    vec![
+                        step.entries.to_string(),
+                        (step.create * 1e6).to_string(),
+                        (step.create_p99 * 1e6).to_string(),
+                        (step.lookup * 1e6).to_string(),
+                        step.readdir.to_string(),
+                        step.unlink
+                            .map(|unlink| (unlink * 1e6).to_string())
+                            .unwrap_or_default(),
+                    ]
+                    .into()
+                })
+                .collect::<Vec<Record>>();
+
+            let mut results = BenchResult::new(header.clone());
+            results.add_records(records.clone())?;
+            let mut file_name = self.config.log_path.clone();
+            file_name.push(format!("{}_directory.csv", fs_name));
+            run_results.log(&results, &file_name, ResultMode::DirectoryScaling)?;
+
+            for ((column, _, _, _), plotter) in CHARTS.iter().zip(plotters.iter_mut()) {
+                // the unlinks are not measured if the benchmark is interrupted before them
+                let records = records
+                    .iter()
+                    .filter(|record| !record.fields[*column].is_empty())
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !records.is_empty() {
+                    plotter.add_coordinates(
+                        records,
+                        Some(fs_name.clone()),
+                        Indexes::new(0, false, *column, None, None),
+                    )?;
+                }
+            }
+
+            // the entries left by an interrupted benchmark are removed, unless they are kept
+            if !interrupt::interrupted() || !self.config.keep_fileset {
+                Fs::cleanup(self.config.backend.as_ref(), &root_path)?;
+            }
+            if interrupt::interrupted() {
+                break;
+            }
+        }
+
+        for ((_, name, title, y_label), plotter) in CHARTS.iter().zip(plotters) {
+            if !plotter.is_empty() {
+                let mut file_name = self.config.log_path.clone();
+                file_name.push(format!("directory_{}.svg", name));
+                plotter.line_chart(
+                    Some("Directory entries"),
+                    Some(y_label),
+                    Some(title),
+                    true,
+                    false,
+                    &file_name,
+                )?;
+            }
+        }
+
+        outputln!(
+            "results logged to: {}",
+            Fs::path_to_str(&self.config.log_path)?
+        );
+
+        Ok(run_results)
+    }
+}
+
+impl DirectoryBench {
+    // fill the directory up to the entries and empty it, and return the costs measured every step
+    // entries
+    fn scaling(
+        &self,
+        path: &PathBuf,
+        fs_name: &str,
+        style: ProgressStyle,
+    ) -> Result<Vec<Step>, Error> {
+        let fs = self.config.backend.as_ref();
+        let entries = self.config.dir_entries;
+        let step = self.config.dir_step();
+        let max_failures = self.config.max_failures;
+
+        let bar = output::spinner();
+        bar.set_style(style);
+        bar.set_message(format!("directory ({}): creating", fs_name));
+        let progress = Progress::start(bar.clone());
+
+        let start = Timestamp::now();
+        let mut failures = Failures::new();
+        let mut steps = vec![];
+        let mut names = vec![]; // the names of the entries created so far
+        let mut latencies = vec![]; // the latencies of the operations since the last step
+        while names.len() < entries && !interrupt::interrupted() {
+            let name = names.len().to_string();
+            let op_start = Timestamp::now();
+            match fs.create(&path.join(&name)) {
+                Ok(_) => {
+                    latencies.push(latency(op_start));
+                    names.push(name);
+                }
+                Err(e) => {
+                    // the directory is not filled further, e.g. when the filesystem is full or
+                    // the directory has reached its maximum size
+                    error!("error: {:?}", e);
+                    failures.add_io("create", &e);
+                    failures.check(max_failures)?;
+                    break;
+                }
+            }
+            if names.len() % step != 0 {
+                continue;
+            }
+
+            // stat random names of the directory
+            let mut lookups = vec![];
+            for _ in 0..step.min(MAX_LOOKUPS) {
+                let name = &names[thread_rng().gen_range(0..names.len())];
+                let op_start = Timestamp::now();
+                match fs.stat(&path.join(name)) {
+                    Ok(_) => lookups.push(latency(op_start)),
+                    Err(e) => {
+                        error!("error: {:?}", e);
+                        failures.add_io("lookup", &e);
+                        failures.check(max_failures)?;
+                    }
+                }
+            }
+
+            let op_start = Timestamp::now();
+            let readdir = match fs.readdir(path) {
+                Ok(listed) => listed.len() as f64 / latency(op_start).max(f64::MIN_POSITIVE),
+                Err(e) => {
+                    error!("error: {:?}", e);
+                    failures.add_io("readdir", &e);
+                    failures.check(max_failures)?;
+                    0f64 // a failed readdir lists no entries
+                }
+            };
+
+            steps.push(Step {
+                entries: names.len(),
+                create: mean(&latencies),
+                create_p99: percentile(&latencies, 0.99),
+                lookup: mean(&lookups),
+                readdir,
+                unlink: None,
+            });
+            latencies.clear();
+            bar.set_message(format!(
+                "directory ({}): {} entries created",
+                fs_name,
+                names.len()
+            ));
+        }
+
+        // the unlinks of a step are those from its directory size down to the previous step's
+        names.shuffle(&mut thread_rng());
+        latencies.clear();
+        while !interrupt::interrupted() {
+            let name = match names.pop() {
+                Some(name) => name,
+                None => break,
+            };
+            let op_start = Timestamp::now();
+            match fs.remove_file(&path.join(&name)) {
+                Ok(_) => latencies.push(latency(op_start)),
+                Err(e) => {
+                    error!("error: {:?}", e);
+                    failures.add_io("unlink", &e);
+                    failures.check(max_failures)?;
+                    continue;
+                }
+            }
+            if names.len() % step != 0 {
+                continue;
+            }
+
+            // the entries created after the last step of an interrupted benchmark have no step
+            if let Some(unlinked) = steps.get_mut(names.len() / step) {
+                unlinked.unlink = Some(mean(&latencies));
+            }
+            latencies.clear();
+            bar.set_message(format!(
+                "directory ({}): {} entries left",
+                fs_name,
+                names.len()
+            ));
+        }
+
+        let end = start.elapsed().as_secs_f64();
+        progress.finish_with_message(&format!(
+            "directory ({}) {}",
+            fs_name,
+            if interrupt::interrupted() {
+                "interrupted"
+            } else {
+                "finished"
+            }
+        ))?;
+
+        outputln!("{:18} {}", "run time:", time_format(end));
+        outputln!(
+            "{:18} {}",
+            "timer overhead:",
+            time_format(clock::overhead().as_secs_f64())
+        );
+        for step in steps.iter() {
+            outputln!(
+                "[{:>9} entries] create: {} (p99: {}), lookup: {}, readdir: {:.0} entries/s, unlink: {}",
+                step.entries,
+                time_format(step.create),
+                time_format(step.create_p99),
+                time_format(step.lookup),
+                step.readdir,
+                step.unlink
+                    .map(time_format)
+                    .unwrap_or_else(|| "-".to_string()),
+            );
+        }
+        if failures.count() > 0 {
+            outputln!("{:18} {}", "failed ops:", failures.count());
+            failures.print();
+
+            let mut file_name = self.config.log_path.clone();
+            file_name.push(format!("{}_directory_failures.csv", fs_name));
+            failures.log(&file_name)?;
+        }
+        outputln!();
+
+        Ok(steps)
+    }
+}
+
+// the latency of an operation started at the timestamp, in seconds
+fn latency(start: Timestamp) -> f64 {
+    start
+        .elapsed()
+        .saturating_sub(clock::overhead())
+        .as_secs_f64()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0f64;
+    }
+
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fs::{FileHandle, FileStat, FileSystem, StdFs, TempDir};
+    use std::io;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    // a backend whose first unlinks are slow, to tell which step their latencies go to
+    struct SlowUnlinkFs {
+        slow_unlinks: AtomicUsize,
+    }
+
+    impl FileSystem for SlowUnlinkFs {
+        fn mkdir(&self, path: &Path) -> io::Result<()> {
+            StdFs.mkdir(path)
+        }
+
+        fn mkdir_all(&self, path: &Path) -> io::Result<()> {
+            StdFs.mkdir_all(path)
+        }
+
+        fn create(&self, path: &Path) -> io::Result<Box<dyn FileHandle>> {
+            StdFs.create(path)
+        }
+
+        fn open(&self, path: &Path) -> io::Result<Box<dyn FileHandle>> {
+            StdFs.open(path)
+        }
+
+        fn open_dir(&self, path: &Path) -> io::Result<()> {
+            StdFs.open_dir(path)
+        }
+
+        fn sync_dir(&self, path: &Path) -> io::Result<()> {
+            StdFs.sync_dir(path)
+        }
+
+        fn stat(&self, path: &Path) -> io::Result<FileStat> {
+            StdFs.stat(path)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            StdFs.rename(from, to)
+        }
+
+        fn remove_file(&self, path: &Path) -> io::Result<()> {
+            let slow = self
+                .slow_unlinks
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+            if slow.is_ok() {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            StdFs.remove_file(path)
+        }
+
+        fn remove_dir(&self, path: &Path) -> io::Result<()> {
+            StdFs.remove_dir(path)
+        }
+
+        fn readdir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+            StdFs.readdir(path)
+        }
+    }
+
+    #[test]
+    fn scaling_test() -> Result<(), Error> {
+        let dir = TempDir::new("directory")?;
+        for (dir_entries, dir_step) in [(20, 10), (25, 10)] {
+            // the unlinks of the entries beyond the last step, which come first, are slow
+            let backend = Arc::new(SlowUnlinkFs {
+                slow_unlinks: AtomicUsize::new(dir_entries % dir_step),
+            });
+            let config = Config::builder(&dir.0)
+                .backend(backend.clone())
+                .dir_entries(dir_entries)
+                .dir_step(dir_step)
+                .quiet(true)
+                .build()?;
+            let bench = DirectoryBench::new(config)?;
+            let path = dir.0.join("dir");
+            bench.setup(&path, false)?;
+
+            let steps = bench.scaling(&path, "test", ProgressStyle::default_bar())?;
+            assert_eq!(
+                steps.iter().map(|step| step.entries).collect::<Vec<_>>(),
+                vec![10, 20]
+            );
+            assert!(steps.iter().all(|step| step.readdir > 0f64));
+            // each step has the unlinks from its size down to the previous step's, and the
+            // entries beyond the last step have no step to be counted in
+            for step in steps.iter() {
+                assert!(matches!(step.unlink, Some(unlink) if unlink < 0.005));
+            }
+            assert_eq!(backend.slow_unlinks.load(Ordering::Relaxed), 0);
+            assert!(backend.readdir(&path)?.is_empty());
+        }
+
+        Ok(())
+    }
+}
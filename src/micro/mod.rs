@@ -1,20 +1,32 @@
+use crate::clock::{self, Timestamp};
 use crate::format::time_format;
-use crate::fs::Fs;
+use crate::fs::{FileSystem, Fs};
+use crate::integrity::Verifier;
+use crate::mmap::{page_size, Mmap};
+use crate::output::{self, outputln};
 use crate::progress::Progress;
-use crate::stats::AnalysedData;
-use crate::Error;
+use crate::stats::{
+    moving_average, percentile, AnalysedData, RobustStatistics, TimeWindow, TRIM_PROPORTION,
+};
+use crate::{Error, Record};
 use byte_unit::Byte;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::ProgressStyle;
 use rand::{thread_rng, Rng, RngCore};
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
-use std::io::Write;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 
+pub mod directory;
 pub mod offline;
 pub mod real_time;
 pub mod throughput;
 
+// the number of time windows the moving average of the ops/s is taken over
+const MOVING_AVERAGE_WINDOWS: usize = 10;
+
 ///
 /// Benchmark function that is being run
 ///
@@ -26,6 +38,120 @@ pub enum BenchFn {
     ColdRead,
     Write,
     WriteSync,
+    MmapRead,
+    MmapRandRead,
+    MmapWrite,
+    PageFault,
+    CreateWrite,
+    CreateFallocate,
+    CreateSparse,
+    PunchHole,
+    CopyFileRange,
+    Reflink,
+}
+
+impl BenchFn {
+    ///
+    /// All the benchmark functions, in the order they are run in static mode
+    ///
+    pub fn all() -> Vec<BenchFn> {
+        vec![
+            BenchFn::Mkdir,
+            BenchFn::Mknod,
+            BenchFn::Read,
+            BenchFn::ColdRead,
+            BenchFn::Write,
+            BenchFn::WriteSync,
+            BenchFn::MmapRead,
+            BenchFn::MmapRandRead,
+            BenchFn::MmapWrite,
+            BenchFn::PageFault,
+            BenchFn::CreateWrite,
+            BenchFn::CreateFallocate,
+            BenchFn::CreateSparse,
+            BenchFn::PunchHole,
+            BenchFn::CopyFileRange,
+            BenchFn::Reflink,
+        ]
+    }
+
+    ///
+    /// The benchmark functions run in static mode if none is selected: all except reflink, which
+    /// few filesystems support
+    ///
+    pub fn defaults() -> Vec<BenchFn> {
+        BenchFn::all()
+            .into_iter()
+            .filter(|bench_fn| *bench_fn != BenchFn::Reflink)
+            .collect()
+    }
+
+    ///
+    /// Whether the benchmark function creates new files rather than using a fileset
+    ///
+    pub fn creates_files(&self) -> bool {
+        matches!(
+            self,
+            BenchFn::Mkdir
+                | BenchFn::Mknod
+                | BenchFn::CreateWrite
+                | BenchFn::CreateFallocate
+                | BenchFn::CreateSparse
+        )
+    }
+
+    ///
+    /// Whether the benchmark function accesses the files through a memory mapping
+    ///
+    pub fn is_mmap(&self) -> bool {
+        matches!(
+            self,
+            BenchFn::MmapRead | BenchFn::MmapRandRead | BenchFn::MmapWrite | BenchFn::PageFault
+        )
+    }
+
+    ///
+    /// The bytes an operation of the benchmark function reads or writes, if it reads or writes
+    ///
+    pub fn io_size(&self, io_size: usize) -> Option<usize> {
+        match self {
+            BenchFn::Mkdir
+            | BenchFn::Mknod
+            | BenchFn::CreateWrite
+            | BenchFn::CreateFallocate
+            | BenchFn::CreateSparse
+            | BenchFn::PunchHole
+            | BenchFn::CopyFileRange
+            | BenchFn::Reflink => None,
+            // a page fault brings in one page
+            BenchFn::PageFault => Some(page_size()),
+            _ => Some(io_size),
+        }
+    }
+
+    ///
+    /// The title of the benchmark function's plots
+    ///
+    pub fn title(&self) -> &'static str {
+        match self {
+            BenchFn::Mkdir => "Mkdir",
+            BenchFn::Mknod => "Mknod",
+            BenchFn::Read => "Read",
+            BenchFn::ColdRead => "Cold read",
+            BenchFn::Write => "Write",
+            BenchFn::WriteSync => "Write (full sync)",
+            BenchFn::MmapRead => "Mmap read",
+            BenchFn::MmapRandRead => "Mmap random read",
+            BenchFn::MmapWrite => "Mmap write (msync)",
+            BenchFn::PageFault => "Page fault",
+            BenchFn::CreateWrite => "Create (written)",
+            BenchFn::CreateFallocate => "Create (fallocate)",
+            BenchFn::CreateSparse => "Create (sparse)",
+            BenchFn::PunchHole => "Punch hole",
+            BenchFn::CopyFileRange => "Copy (copy_file_range)",
+            BenchFn::Reflink => "Copy (reflink)",
+        }
+    }
 }
 
 impl FromStr for BenchFn {
@@ -39,8 +165,20 @@ impl FromStr for BenchFn {
             "cold_read" => Ok(BenchFn::ColdRead),
             "write" => Ok(BenchFn::Write),
             "write_sync" => Ok(BenchFn::WriteSync),
+            "mmap_read" => Ok(BenchFn::MmapRead),
+            "mmap_rand_read" => Ok(BenchFn::MmapRandRead),
+            "mmap_write" => Ok(BenchFn::MmapWrite),
+            "page_fault" => Ok(BenchFn::PageFault),
+            "create_write" => Ok(BenchFn::CreateWrite),
+            "create_fallocate" => Ok(BenchFn::CreateFallocate),
+            "create_sparse" => Ok(BenchFn::CreateSparse),
+            "punch_hole" => Ok(BenchFn::PunchHole),
+            "copy_file_range" => Ok(BenchFn::CopyFileRange),
+            "reflink" => Ok(BenchFn::Reflink),
             _ => Err(
-                "valid benckmark functions are: mkdir, mknod, read, cold_read, write, write_sync"
+                "valid benckmark functions are: mkdir, mknod, read, cold_read, write, write_sync, \
+                mmap_read, mmap_rand_read, mmap_write, page_fault, create_write, create_fallocate, \
+                create_sparse, punch_hole, copy_file_range, reflink"
                     .to_string(),
             ),
         }
@@ -56,39 +194,104 @@ impl Display for BenchFn {
             BenchFn::ColdRead => write!(f, "cold_read"),
             BenchFn::Write => write!(f, "write"),
             BenchFn::WriteSync => write!(f, "write_sync"),
+            BenchFn::MmapRead => write!(f, "mmap_read"),
+            BenchFn::MmapRandRead => write!(f, "mmap_rand_read"),
+            BenchFn::MmapWrite => write!(f, "mmap_write"),
+            BenchFn::PageFault => write!(f, "page_fault"),
+            BenchFn::CreateWrite => write!(f, "create_write"),
+            BenchFn::CreateFallocate => write!(f, "create_fallocate"),
+            BenchFn::CreateSparse => write!(f, "create_sparse"),
+            BenchFn::PunchHole => write!(f, "punch_hole"),
+            BenchFn::CopyFileRange => write!(f, "copy_file_range"),
+            BenchFn::Reflink => write!(f, "reflink"),
+        }
+    }
+}
+
+///
+/// How the files of the fileset are created in the setup
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileLayout {
+    Written,    // filled with content
+    Fallocated, // preallocated by fallocate, without content
+    Sparse,     // extended to the file size by set_len, without storage
+}
+
+impl FromStr for FileLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "written" => Ok(FileLayout::Written),
+            "fallocate" => Ok(FileLayout::Fallocated),
+            "sparse" => Ok(FileLayout::Sparse),
+            _ => Err("valid file layouts are: written, fallocate, sparse".to_string()),
+        }
+    }
+}
+
+impl Display for FileLayout {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileLayout::Written => write!(f, "written"),
+            FileLayout::Fallocated => write!(f, "fallocate"),
+            FileLayout::Sparse => write!(f, "sparse"),
         }
     }
 }
 
 pub fn micro_setup(
+    fs: &dyn FileSystem,
     file_size: usize,
     fileset_size: usize,
     path: &PathBuf,
+    creates_files: bool,
     invalidate_cache: bool,
+    verifier: Option<&Verifier>,
+    file_layout: FileLayout,
 ) -> Result<(), Error> {
-    Fs::cleanup(path)?;
+    Fs::cleanup(fs, path)?;
     // creating the root directory to generate the benchmark files inside it
-    Fs::make_dir(&path)?;
+    fs.mkdir(path)?;
 
     let style = ProgressStyle::default_bar().template("[{elapsed_precise}] {msg}");
-    let bar = ProgressBar::new_spinner();
+    let bar = output::spinner();
     bar.set_style(style);
     bar.set_message(format!("setting up {}", Fs::path_to_str(path)?));
     let progress = Progress::start(bar.clone());
 
-    if path.ends_with("mkdir") || path.ends_with("mknod") {
-        // we don't need to setup anything for mkdir and mknod
+    if creates_files {
+        // we don't need to setup anything for the functions creating their own files
+    } else if file_layout != FileLayout::Written {
+        // create files of size file_size without content, preallocated or sparse
+        for file in 0..fileset_size {
+            let mut file_name = path.clone();
+            file_name.push(file.to_string());
+            let mut file = fs.create(&file_name)?;
+            match file_layout {
+                FileLayout::Fallocated => file.allocate(0, file_size as u64)?,
+                _ => file.set_len(file_size as u64)?,
+            }
+        }
     } else {
         // create files of size io_size filled with random content
         for file in 0..fileset_size {
             let mut file_name = path.clone();
             file_name.push(file.to_string());
 
-            // each file is filled with random content
-            let mut rand_buffer = vec![0u8; file_size];
-            let mut rng = rand::thread_rng();
-            rng.fill_bytes(&mut rand_buffer);
-            Fs::make_file(&file_name)?.write_all(&mut rand_buffer)?;
+            // each file is filled with random content, or with the content derived from the seed
+            // if the reads are going to be verified
+            let rand_buffer = match verifier {
+                Some(verifier) => verifier.content(&file.to_string(), 0, file_size)?,
+                None => {
+                    let mut rand_buffer = vec![0u8; file_size];
+                    let mut rng = rand::thread_rng();
+                    rng.fill_bytes(&mut rand_buffer);
+                    rand_buffer
+                }
+            };
+            fs.create(&file_name)?.write_all_at(&rand_buffer, 0)?;
         }
     }
 
@@ -101,17 +304,171 @@ pub fn micro_setup(
     Ok(())
 }
 
+// the maximum number of files of the fileset kept mapped by the mmap benchmark functions
+const MAX_MAPPED_FILES: usize = 4096;
+
+///
+/// The mappings of the files of the fileset that the mmap benchmark functions access. Each file is
+/// opened through the backend and mapped once, on its first operation, and kept mapped, so that
+/// the operations access the pages of the mapping rather than open and map the file. The mapping
+/// of a file also keeps the offset its next sequential read starts at.
+///
+#[derive(Default)]
+pub struct Mappings {
+    files: HashMap<PathBuf, (Mmap, usize)>,
+}
+
+impl Mappings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the mapping of the first len bytes of the file and its read offset, mapped if needed
+    fn get(
+        &mut self,
+        fs: &dyn FileSystem,
+        path: &Path,
+        len: usize,
+    ) -> io::Result<&mut (Mmap, usize)> {
+        if !self.files.contains_key(path) {
+            // a mapping is dropped for the new one if too many files are mapped
+            if self.files.len() >= MAX_MAPPED_FILES {
+                if let Some(mapped) = self.files.keys().next().cloned() {
+                    self.files.remove(&mapped);
+                }
+            }
+            let mmap = fs.open(path)?.map(len)?;
+            self.files.insert(path.to_path_buf(), (mmap, 0));
+        }
+
+        Ok(self.files.get_mut(path).unwrap())
+    }
+}
+
+///
+/// Run an mmap operation of the benchmark function on the file, mapping its first file_size bytes,
+/// and return the timestamps of the finished operations and the offset of the content read. The
+/// files are mapped once in the mappings, and the opening and mapping are excluded from the op
+/// times. An mmap read copies the next io_size bytes of the mapping to the read buffer, reading
+/// through the file sequentially, and an mmap random read touches as many random pages of the
+/// mapping. An mmap write copies the content to the mapping and msyncs it. The page faults are
+/// timed one page at a time, touching the pages of a new mapping of the file in order.
+///
+#[allow(clippy::too_many_arguments)]
+pub fn mmap_op(
+    op: &BenchFn,
+    fs: &dyn FileSystem,
+    mappings: &mut Mappings,
+    file_name: &Path,
+    file_size: usize,
+    io_size: usize,
+    content: &[u8],
+    read_buffer: &mut [u8],
+    excluded_time: &mut Duration,
+) -> io::Result<(Vec<Timestamp>, usize)> {
+    let start = Timestamp::now();
+    if let BenchFn::PageFault = op {
+        // the pages of a mapping kept from an earlier fault would not fault again
+        let mmap = fs.open(file_name)?.map(file_size)?;
+        *excluded_time += start.elapsed();
+
+        let timestamps = (0..file_size)
+            .step_by(page_size())
+            .map(|offset| {
+                mmap.touch(offset);
+                op_timestamp(excluded_time)
+            })
+            .collect();
+        return Ok((timestamps, 0));
+    }
+
+    let (mmap, next_offset) = mappings.get(fs, file_name, file_size)?;
+    *excluded_time += start.elapsed();
+
+    let mut offset = 0;
+    match op {
+        BenchFn::MmapRead => {
+            // the reads start over at the beginning of the file once they reach its end
+            if *next_offset + io_size > file_size {
+                *next_offset = 0;
+            }
+            offset = *next_offset;
+            read_buffer[..io_size].copy_from_slice(&mmap.as_slice()[offset..offset + io_size]);
+            *next_offset += io_size;
+        }
+        BenchFn::MmapRandRead => {
+            let pages = (file_size / page_size()).max(1);
+            for _ in 0..(io_size / page_size()).max(1) {
+                mmap.touch(thread_rng().gen_range(0..pages) * page_size());
+            }
+        }
+        BenchFn::MmapWrite => {
+            mmap.as_mut_slice()[..io_size].copy_from_slice(content);
+            mmap.sync(0, io_size)?;
+        }
+        _ => {}
+    }
+
+    Ok((vec![op_timestamp(excluded_time)], offset))
+}
+
+///
+/// Run a creation, hole punching or copy operation of the benchmark function. The creations make
+/// the file idx in the root directory, of file_size bytes written in io_size chunks of the
+/// content, preallocated by fallocate, or extended by set_len without storage. A hole of io_size
+/// bytes is punched at a random aligned offset of a random file of the fileset, and a random file
+/// of the fileset is copied by copy_file_range or reflinked to its copy, replacing the last one.
+///
+pub fn file_op(
+    fs: &dyn FileSystem,
+    op: &BenchFn,
+    root_path: &Path,
+    idx: u64,
+    fileset_size: usize,
+    file_size: usize,
+    content: &[u8],
+) -> io::Result<()> {
+    let file = thread_rng().gen_range(0..fileset_size);
+    let file_name = root_path.join(file.to_string());
+    let copy_name = root_path.join(format!("{}.copy", file));
+    match op {
+        BenchFn::CreateWrite => {
+            let mut file = fs.create(&root_path.join(idx.to_string()))?;
+            for offset in (0..file_size).step_by(content.len()) {
+                let len = content.len().min(file_size - offset);
+                file.write_all_at(&content[..len], offset as u64)?;
+            }
+        }
+        BenchFn::CreateFallocate => fs
+            .create(&root_path.join(idx.to_string()))?
+            .allocate(0, file_size as u64)?,
+        BenchFn::CreateSparse => fs
+            .create(&root_path.join(idx.to_string()))?
+            .set_len(file_size as u64)?,
+        BenchFn::PunchHole => {
+            let io_size = content.len();
+            let offset = thread_rng().gen_range(0..(file_size / io_size).max(1)) * io_size;
+            fs.open(&file_name)?
+                .punch_hole(offset as u64, io_size as u64)?;
+        }
+        BenchFn::CopyFileRange => fs.copy_range(&file_name, &copy_name)?,
+        BenchFn::Reflink => fs.reflink(&file_name, &copy_name)?,
+        _ => {}
+    }
+
+    Ok(())
+}
+
 // get a random leaf from the input path
-pub fn random_leaf(path: &PathBuf) -> Result<PathBuf, Error> {
-    let entries = path.read_dir()?.collect::<Vec<_>>();
+pub fn random_leaf(fs: &dyn FileSystem, path: &PathBuf) -> Result<PathBuf, Error> {
+    let entries = fs.readdir(path)?;
     if entries.len() == 0 {
         return Ok(path.clone());
     }
 
     // select one of the directories
     let random = thread_rng().gen_range(0..entries.len());
-    //TODO fix the unwrap
-    random_leaf(&entries[random].as_ref().unwrap().path())
+    random_leaf(fs, &entries[random])
 }
 
 pub fn print_output(
@@ -120,12 +477,24 @@ pub fn print_output(
     io_size: usize,
     analysed_data: &AnalysedData,
     throughput: bool,
+    mismatches: Option<usize>,
+    failures: u64,
 ) {
-    println!("{:18} {}", "iterations:", iterations);
-    println!("{:18} {}", "run time:", time_format(run_time));
-    println!(
+    outputln!("{:18} {}", "iterations:", iterations);
+    outputln!("{:18} {}", "failed ops:", failures);
+    if let Some(mismatches) = mismatches {
+        outputln!("{:18} {}", "data mismatches:", mismatches);
+    }
+    outputln!("{:18} {}", "run time:", time_format(run_time));
+    outputln!(
+        "{:18} {}",
+        "timer overhead:",
+        time_format(clock::overhead().as_secs_f64())
+    );
+    outputln!("{:18} {}", "CI method:", analysed_data.method);
+    outputln!(
         "{:18} [{}, {}]",
-        "op time (95% CI):",
+        format!("op time ({}):", analysed_data.ci_label()),
         time_format(1f64 / analysed_data.mean_ub),
         time_format(1f64 / analysed_data.mean_lb),
     );
@@ -137,22 +506,122 @@ pub fn print_output(
         let byte_s_ub = Byte::from_bytes((analysed_data.mean_ub * io_size as f64) as u128);
         let byte_s_ub = byte_s_ub.get_appropriate_unit(true);
 
-        println!(
+        outputln!(
             "{:18} [{}, {}] ([{}/s, {}/s])",
-            "ops/s (95% CI):", analysed_data.mean_lb, analysed_data.mean_ub, byte_s_lb, byte_s_ub
+            format!("ops/s ({}):", analysed_data.ci_label()),
+            analysed_data.mean_lb,
+            analysed_data.mean_ub,
+            byte_s_lb,
+            byte_s_ub
         );
     } else {
-        println!(
+        outputln!(
             "{:18} [{}, {}]",
-            "ops/s (95% CI):", analysed_data.mean_lb, analysed_data.mean_ub
+            format!("ops/s ({}):", analysed_data.ci_label()),
+            analysed_data.mean_lb,
+            analysed_data.mean_ub
         );
     }
+    print_robust_statistics(&analysed_data.robust, analysed_data.outliers_excluded);
+
+    outputln!();
+}
+
+///
+/// The header of the behaviour timelines: the ops/s, bytes/s, moving average of the ops/s, and
+/// the latency percentiles of each time window
+///
+pub fn behaviour_header() -> Vec<String> {
+    [
+        "time",
+        "ops",
+        "bytes/s",
+        "ops_ma",
+        "latency_p50(us)",
+        "latency_p90(us)",
+        "latency_p99(us)",
+    ]
+    .iter()
+    .map(|column| column.to_string())
+    .collect()
+}
+
+///
+/// The behaviour timeline records of the time windows. The bytes/s are only given for the
+/// operations that read or write io_size bytes.
+///
+pub fn behaviour_records(time_windows: &[TimeWindow], io_size: Option<usize>) -> Vec<Record> {
+    let ops_per_seconds = time_windows
+        .iter()
+        .map(|time_window| time_window.ops_per_second)
+        .collect::<Vec<_>>();
+    let moving_averages = moving_average(&ops_per_seconds, MOVING_AVERAGE_WINDOWS);
 
-    println!();
+    time_windows
+        .iter()
+        .zip(moving_averages)
+        .map(|(time_window, moving_average)| {
+            let bytes_per_second = match io_size {
+                Some(io_size) => (time_window.ops_per_second * io_size as f64).to_string(),
+                None => String::new(),
+            };
+            // in microseconds, rounded to nanoseconds
+            let latency =
+                |p: f64| ((percentile(&time_window.latencies, p) * 1e9).round() / 1e3).to_string();
+
+            [
+                time_window.time.to_string(),
+                time_window.ops_per_second.to_string(),
+                bytes_per_second,
+                moving_average.to_string(),
+                latency(0.5),
+                latency(0.9),
+                latency(0.99),
+            ]
+            .to_vec()
+            .into()
+        })
+        .collect()
+}
+
+///
+/// The timestamp of an operation that has just finished, moved back by the time excluded from the
+/// op times so far. The overhead of reading the clock is added to the excluded time.
+///
+pub fn op_timestamp(excluded_time: &mut Duration) -> Timestamp {
+    *excluded_time += clock::overhead();
+    let now = Timestamp::now();
+    now.checked_sub(*excluded_time).unwrap_or(now)
+}
+
+///
+/// Print the median, IQR, trimmed mean, range and outliers of the ops/s sample points
+///
+pub fn print_robust_statistics(robust: &RobustStatistics, outliers_excluded: bool) {
+    outputln!("{:18} {:.2}", "median ops/s:", robust.median);
+    outputln!("{:18} {:.2}", "IQR ops/s:", robust.iqr);
+    outputln!(
+        "{:18} {:.2}",
+        format!("trimmed ({}%):", TRIM_PROPORTION * 100f64),
+        robust.trimmed_mean
+    );
+    outputln!(
+        "{:18} [{:.2}, {:.2}]",
+        "min/max ops/s:",
+        robust.min,
+        robust.max
+    );
+    outputln!(
+        "{:18} {} ({:.2}%){}",
+        "outliers:",
+        robust.outliers,
+        robust.outlier_fraction * 100f64,
+        if outliers_excluded { ", excluded" } else { "" }
+    );
 }
 
 pub fn clear_cache() -> Result<(), Error> {
-    let spinner = ProgressBar::new_spinner();
+    let spinner = output::spinner();
     spinner.set_style(ProgressStyle::default_spinner().template("{spinner} clearing the cache"));
     let progress = Progress::start(spinner);
 
@@ -1,18 +1,46 @@
+use crate::clock::{self, Timestamp};
+#[cfg(target_os = "linux")]
+use crate::engine::uring::{reserve_open_files, Uring};
+use crate::engine::{IoEngine, UringConfig};
 use crate::error::Error;
+use crate::failures::Failures;
+use crate::format::time_format;
 use crate::fs::Fs;
-use crate::micro::{micro_setup, print_output, random_leaf};
+use crate::integrity::{log_mismatches, Mismatch, Verifier};
+use crate::interrupt;
+use crate::micro::{
+    behaviour_header, behaviour_records, file_op, micro_setup, mmap_op, op_timestamp, print_output,
+    print_robust_statistics, random_leaf, Mappings,
+};
+use crate::output::{self, outputln};
 use crate::plotter::{Indexes, Plotter};
 use crate::progress::Progress;
-use crate::stats::Statistics;
-use crate::BenchFn::Mknod;
-use crate::{Bench, BenchFn, BenchResult, Config, Record};
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::results::RunResults;
+use crate::stats::{ci_label, CiMethod, RobustStatistics, Statistics};
+use crate::write_sync::Syncer;
+use crate::{Bench, BenchFn, BenchResult, Config, Record, ResultMode};
+use indicatif::ProgressStyle;
 use log::error;
 use rand::{thread_rng, Rng, RngCore};
-use std::io::{Read, Write};
+#[cfg(target_os = "linux")]
+use std::fs::OpenOptions;
 use std::path::PathBuf;
-use std::sync::mpsc::channel;
-use std::time::{Duration, SystemTime};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// the maximum number of operation latencies kept for the latency charts of a run
+const MAX_LATENCY_SAMPLES: usize = 100_000;
+
+// the results of the worker of a run: the timestamps of the finished ops, their number, the read
+// mismatches and the failures, and the latency of each op if the ops overlap
+type WorkerResults = (
+    Vec<Timestamp>,
+    u64,
+    Vec<Mismatch>,
+    Failures,
+    Option<Vec<Duration>>,
+);
 
 pub struct OfflineBench {
     config: Config,
@@ -20,385 +48,568 @@ pub struct OfflineBench {
 
 impl Bench for OfflineBench {
     fn new(config: Config) -> Result<Self, Error> {
+        output::set_quiet(config.quiet);
         Ok(Self { config })
     }
 
     fn setup(&self, path: &PathBuf, invalidate_cache: bool) -> Result<(), Error> {
-        micro_setup(
-            self.config.file_size,
-            self.config.fileset_size,
-            path,
-            invalidate_cache,
-        )
+        self.setup_root(path, false, invalidate_cache)
     }
 
-    fn run(&self, _bench_fn: Option<BenchFn>) -> Result<(), Error> {
-        sudo::escalate_if_needed()?;
+    fn run(&self, _bench_fn: Option<BenchFn>) -> Result<RunResults, Error> {
+        // clearing the cache for cold reads requires root
+        if self.config.bench_fns.contains(&BenchFn::ColdRead) {
+            sudo::escalate_if_needed()?;
+        }
 
         let rt = Duration::from_secs(self.config.run_time as u64); // running time
-        self.behaviour_bench(rt)?;
+        let run_results = self.behaviour_bench(rt)?;
 
-        println!(
+        outputln!(
             "results logged to: {}",
             Fs::path_to_str(&self.config.log_path)?
         );
 
-        Ok(())
+        Ok(run_results)
     }
 }
 
 impl OfflineBench {
-    fn behaviour_bench(&self, run_time: Duration) -> Result<(), Error> {
+    // set up the root path of a benchmark function: the fileset, or only the empty root directory
+    // for the functions creating their own files
+    fn setup_root(
+        &self,
+        path: &PathBuf,
+        creates_files: bool,
+        invalidate_cache: bool,
+    ) -> Result<(), Error> {
+        micro_setup(
+            self.config.backend.as_ref(),
+            self.config.file_size,
+            self.config.fileset_size,
+            path,
+            creates_files,
+            invalidate_cache,
+            self.config.verifier().as_ref(),
+            self.config.file_layout,
+        )
+    }
+
+    fn behaviour_bench(&self, run_time: Duration) -> Result<RunResults, Error> {
         let progress_style = ProgressStyle::default_bar().template("[{elapsed_precise}] {msg}");
 
-        let mut plotter_mkdir_behaviour = Plotter::new();
-        let mut plotter_mknod_behaviour = Plotter::new();
-        let mut plotter_read_behaviour = Plotter::new();
-        let mut plotter_cold_read_behaviour = Plotter::new();
-        let mut plotter_write_behaviour = Plotter::new();
-        let mut plotter_write_sync_behaviour = Plotter::new();
-        let behaviour_header = ["time".to_string(), "ops".to_string()].to_vec();
+        // the selected benchmark functions and their plot titles
+        let bench_fns = BenchFn::all()
+            .into_iter()
+            .filter(|bench_fn| self.config.bench_fns.contains(bench_fn))
+            .map(|bench_fn| {
+                let title = bench_fn.title();
+                (bench_fn, title)
+            })
+            .collect::<Vec<_>>();
+        // a behaviour plotter for each benchmark function, including all the filesystems
+        let mut behaviour_plotters = bench_fns
+            .iter()
+            .map(|_| Plotter::with_style(self.config.plot_style.clone()))
+            .collect::<Vec<_>>();
+        // a latency CDF plotter for each benchmark function, and a box plotter of all of them
+        let mut latency_plotters = bench_fns
+            .iter()
+            .map(|_| Plotter::with_style(self.config.plot_style.clone()))
+            .collect::<Vec<_>>();
+        let mut box_plotter = Plotter::with_style(self.config.plot_style.clone());
+        // the ops/s samples of each benchmark function per filesystem, to compare the filesystems,
+        // and whether they are the ops/s of independent runs
+        let mut samples = bench_fns.iter().map(|_| vec![]).collect::<Vec<_>>();
+        let behaviour_header = behaviour_header();
+        let ops_s_header = ops_per_second_header("operation");
+        let ops_s_samples_header = ["iterations".to_string(), "ops/s".to_string()].to_vec();
+        let repetitions_header = [
+            "operation".to_string(),
+            "runs".to_string(),
+            "ops/s".to_string(),
+            "std".to_string(),
+            "cv".to_string(),
+            "ops/s_lb".to_string(),
+            "ops/s_ub".to_string(),
+            "confidence_level".to_string(),
+        ]
+        .to_vec();
 
+        let mut run_results = RunResults::new();
         for (idx, mount_path) in self.config.mount_paths.iter().enumerate() {
-            let (mkdir_ops_s, mkdir_behaviour, mkdir_times) = self.micro_op(
-                BenchFn::Mkdir,
-                run_time,
-                mount_path,
-                &self.config.fs_names[idx],
-                progress_style.clone(),
-            )?;
-            let (mknod_ops_s, mknod_behaviour, mknod_times) = self.micro_op(
-                BenchFn::Mknod,
-                run_time,
-                mount_path,
-                &self.config.fs_names[idx],
-                progress_style.clone(),
-            )?;
-            let (read_ops_s, read_behaviour, read_times) = self.micro_op(
-                BenchFn::Read,
-                run_time,
-                mount_path,
-                &self.config.fs_names[idx],
-                progress_style.clone(),
-            )?;
-            let (cold_read_ops_s, cold_read_behaviour, cold_read_times) = self.micro_op(
-                BenchFn::ColdRead,
-                run_time,
-                mount_path,
-                &self.config.fs_names[idx],
-                progress_style.clone(),
-            )?;
-            let (write_ops_s, write_behaviour, write_times) = self.micro_op(
-                BenchFn::Write,
-                run_time,
-                mount_path,
-                &self.config.fs_names[idx],
-                progress_style.clone(),
-            )?;
-            let (write_sync_ops_s, write_sync_behaviour, write_sync_times) = self.micro_op(
-                BenchFn::WriteSync,
-                run_time,
-                mount_path,
-                &self.config.fs_names[idx],
-                progress_style.clone(),
-            )?;
+            let fs_name = &self.config.fs_names[idx];
+            let mut ops_s_results = BenchResult::new(ops_s_header.clone());
+            let mut repetitions_results = BenchResult::new(repetitions_header.clone());
 
-            let ops_s_header = [
-                "operation".to_string(),
-                "runtime(s)".to_string(),
-                "ops/s".to_string(),
-                "ops/s_lb".to_string(),
-                "ops/s_ub".to_string(),
-            ]
-            .to_vec();
+            for ((((bench_fn, title), behaviour_plotter), latency_plotter), fn_samples) in bench_fns
+                .iter()
+                .zip(behaviour_plotters.iter_mut())
+                .zip(latency_plotters.iter_mut())
+                .zip(samples.iter_mut())
+            {
+                if interrupt::interrupted() {
+                    break;
+                }
+
+                let repetitions = self.config.repetitions;
+                let mut runs = vec![];
+                let mut window_ops_s = vec![];
+                for run in 1..=repetitions {
+                    if interrupt::interrupted() {
+                        break;
+                    }
 
-            // log and plot ops/s
-            let mut ops_s_results = BenchResult::new(ops_s_header);
-            ops_s_results.add_record(mkdir_ops_s)?;
-            ops_s_results.add_record(mknod_ops_s)?;
-            ops_s_results.add_record(read_ops_s)?;
-            ops_s_results.add_record(cold_read_ops_s)?;
-            ops_s_results.add_record(write_ops_s)?;
-            ops_s_results.add_record(write_sync_ops_s)?;
+                    // the results are logged as soon as each run is finished, so that they are
+                    // kept if the benchmark is interrupted later
+                    let results = match self.micro_op(
+                        bench_fn.clone(),
+                        run_time,
+                        mount_path,
+                        fs_name,
+                        progress_style.clone(),
+                    )? {
+                        Some(results) => results,
+                        None => continue,
+                    };
+                    let (ops_s, behaviour, times) =
+                        (results.ops_per_second, results.behaviour, results.samples);
+
+                    // the results of each run are kept separately when repeating the runs
+                    let (run_suffix, run_title) = if repetitions > 1 {
+                        (format!("_run{}", run), format!(", run {}", run))
+                    } else {
+                        (String::new(), String::new())
+                    };
+
+                    // log behaviour results, and plot the behaviour of the first run
+                    let mut behaviour_results = BenchResult::new(behaviour_header.clone());
+                    behaviour_results.add_records(behaviour.clone())?;
+                    let mut file_name = self.config.log_path.clone();
+                    file_name.push(format!(
+                        "{}_{}{}.csv",
+                        fs_name,
+                        bench_fn.to_string(),
+                        run_suffix
+                    ));
+                    run_results.log(&behaviour_results, &file_name, ResultMode::Behaviour)?;
+                    if runs.is_empty() {
+                        window_ops_s = behaviour
+                            .iter()
+                            .map(|record| record.fields[1].parse::<f64>())
+                            .collect::<Result<Vec<_>, _>>()?;
+                        behaviour_plotter.add_coordinates(
+                            behaviour,
+                            Some(fs_name.clone()),
+                            Indexes::new(0, false, 1, None, None),
+                        )?;
+
+                        // plot the latency distribution and its change over time
+                        let (latency_times, latencies) = results.latencies;
+                        if !latencies.is_empty() {
+                            let mut plotter = Plotter::with_style(self.config.plot_style.clone());
+                            plotter.add_distribution(latencies.clone(), Some(latency_times), None);
+                            let mut file_name = self.config.log_path.clone();
+                            file_name.push(format!(
+                                "{}_{}_latency_heatmap.svg",
+                                fs_name,
+                                bench_fn.to_string()
+                            ));
+                            plotter.heatmap(
+                                Some("Time (s)"),
+                                Some("Latency (us)"),
+                                Some(&format!("{} latency ({})", title, fs_name)),
+                                &file_name,
+                            )?;
+
+                            latency_plotter.add_distribution(
+                                latencies.clone(),
+                                None,
+                                Some(fs_name.clone()),
+                            );
+                            box_plotter.add_distribution(
+                                latencies,
+                                None,
+                                Some(format!("{} ({})", bench_fn.to_string(), fs_name)),
+                            );
+                        }
+                    }
+
+                    // log and plot sample iteration average ops/s
+                    let mut times_results = BenchResult::new(ops_s_samples_header.clone());
+                    times_results.add_records(times.clone())?;
+                    let mut file_name = self.config.log_path.clone();
+                    file_name.push(format!(
+                        "{}_{}_ops_s_period{}.csv",
+                        fs_name,
+                        bench_fn.to_string(),
+                        run_suffix
+                    ));
+                    run_results.log(&times_results, &file_name, ResultMode::SampleOpsPerSecond)?;
+
+                    let mut plotter = Plotter::with_style(self.config.plot_style.clone());
+                    plotter.add_coordinates(times, None, Indexes::new(0, false, 1, None, None))?;
+                    file_name.set_extension("svg");
+                    plotter.point_series(
+                        Some("Sampling iterations"),
+                        Some("Average Ops/s"),
+                        Some(&format!("{} ({}{})", title, fs_name, run_title)),
+                        &file_name,
+                    )?;
+
+                    runs.push(ops_s);
+                }
+
+                if runs.len() >= 3 {
+                    // the runs are independent of each other, unlike the time windows of a run
+                    let run_ops_s = runs
+                        .iter()
+                        .map(|run| run.fields[2].parse::<f64>())
+                        .collect::<Result<Vec<_>, _>>()?;
+                    fn_samples.push((fs_name.clone(), run_ops_s, true));
+
+                    let summary = self.compare_runs(bench_fn, fs_name, &runs, &mut run_results)?;
+                    ops_s_results.add_record(summary.ops_per_second_record())?;
+                    repetitions_results.add_record(summary.into())?;
+                } else if !runs.is_empty() {
+                    // a single run, or not enough runs to compare if the benchmark is interrupted,
+                    // whose time windows are only compared as an estimate
+                    fn_samples.push((fs_name.clone(), window_ops_s, false));
+                    ops_s_results.add_record(runs.remove(0))?;
+                }
+            }
+
+            if !repetitions_results.records.is_empty() {
+                let mut file_name = self.config.log_path.clone();
+                file_name.push(format!("{}_repetitions.csv", fs_name));
+                run_results.log(&repetitions_results, &file_name, ResultMode::Repetitions)?;
+            }
+
+            if !ops_s_results.records.is_empty() {
+                // log and plot ops/s
+                let mut file_name = self.config.log_path.clone();
+                file_name.push(format!("{}_ops_per_second.csv", fs_name));
+                run_results.log(&ops_s_results, &file_name, ResultMode::OpsPerSecond)?;
+
+                let mut plotter = Plotter::with_style(self.config.plot_style.clone());
+                plotter.add_coordinates(
+                    ops_s_results.records,
+                    None,
+                    Indexes::new(0, true, 2, Some(3), Some(4)),
+                )?;
+                file_name.set_extension("svg");
+                plotter.bar_chart(
+                    Some("Operation"),
+                    Some("Ops/s"),
+                    Some(&format!("Ops/s ({})", fs_name)),
+                    &file_name,
+                )?;
+            }
+
+            if interrupt::interrupted() {
+                break;
+            }
+        }
+
+        if self.config.mount_paths.len() > 1 {
+            self.compare_filesystems(&bench_fns, &samples, &mut run_results)?;
+        }
+
+        // plot the behaviour results
+        for (((bench_fn, title), behaviour_plotter), latency_plotter) in bench_fns
+            .iter()
+            .zip(behaviour_plotters.iter())
+            .zip(latency_plotters.iter())
+        {
+            if !latency_plotter.is_empty() {
+                let mut file_name = self.config.log_path.clone();
+                file_name.push(format!("{}_latency_cdf.svg", bench_fn.to_string()));
+                latency_plotter.cdf_chart(
+                    Some("Latency (us)"),
+                    Some(&format!("{} latency", title)),
+                    &file_name,
+                )?;
+            }
+
+            if behaviour_plotter.is_empty() {
+                continue;
+            }
 
             let mut file_name = self.config.log_path.clone();
-            file_name.push(format!("{}_ops_per_second.csv", self.config.fs_names[idx]));
-            ops_s_results.log(&file_name)?;
-
-            let mut plotter = Plotter::new();
-            plotter.add_coordinates(
-                ops_s_results.records,
-                None,
-                Indexes::new(0, true, 2, Some(3), Some(4)),
-            )?;
-            file_name.set_extension("svg");
-            plotter.bar_chart(
-                Some("Operation"),
+            file_name.push(format!("{}.svg", bench_fn.to_string()));
+            behaviour_plotter.line_chart(
+                Some("Time (s)"),
                 Some("Ops/s"),
-                Some(&format!("Ops/s ({})", self.config.fs_names[idx])),
+                Some(title),
+                false,
+                false,
                 &file_name,
             )?;
+        }
 
-            // log behaviour results
-            let mut mkdir_behaviour_results = BenchResult::new(behaviour_header.clone());
-            mkdir_behaviour_results.add_records(mkdir_behaviour.clone())?;
+        if !box_plotter.is_empty() {
             let mut file_name = self.config.log_path.clone();
-            file_name.push(format!("{}_mkdir.csv", self.config.fs_names[idx]));
-            mkdir_behaviour_results.log(&file_name)?;
-            plotter_mkdir_behaviour.add_coordinates(
-                mkdir_behaviour,
-                Some(self.config.fs_names[idx].clone()),
-                Indexes::new(0, false, 1, None, None),
-            )?;
+            file_name.push("latency_box.svg");
+            box_plotter.box_plot(Some("Latency (us)"), Some("Latency"), &file_name)?;
+        }
 
-            let mut mknod_behaviour_results = BenchResult::new(behaviour_header.clone());
-            mknod_behaviour_results.add_records(mknod_behaviour.clone())?;
-            let mut file_name = self.config.log_path.clone();
-            file_name.push(format!("{}_mknod.csv", self.config.fs_names[idx]));
-            mknod_behaviour_results.log(&file_name)?;
-            plotter_mknod_behaviour.add_coordinates(
-                mknod_behaviour,
-                Some(self.config.fs_names[idx].clone()),
-                Indexes::new(0, false, 1, None, None),
-            )?;
+        Ok(run_results)
+    }
 
-            let mut read_behaviour_results = BenchResult::new(behaviour_header.clone());
-            read_behaviour_results.add_records(read_behaviour.clone())?;
-            let mut file_name = self.config.log_path.clone();
-            file_name.push(format!("{}_read.csv", self.config.fs_names[idx]));
-            read_behaviour_results.log(&file_name)?;
-            plotter_read_behaviour.add_coordinates(
-                read_behaviour,
-                Some(self.config.fs_names[idx].clone()),
-                Indexes::new(0, false, 1, None, None),
-            )?;
+    // wait until the ops/s has reached the steady state with the target precision, or the maximum
+    // run time, and return the run time. The precision is checked every second after the warmup
+    // time, on the ops/s after the initial transient. The wait ends early if the worker stops by
+    // itself, e.g. on too many failures, so that its error is returned when it is joined.
+    fn adaptive_run<T>(
+        &self,
+        max_time: Duration,
+        precision: f64,
+        sender: &Sender<Request>,
+        receiver: &Receiver<Vec<Timestamp>>,
+        worker: &JoinHandle<T>,
+    ) -> Result<Duration, Error> {
+        let start = Timestamp::now();
+        interrupt::sleep_until(
+            Duration::from_secs(self.config.warmup_time).min(max_time),
+            || worker.is_finished(),
+        );
 
-            let mut cold_read_behaviour_results = BenchResult::new(behaviour_header.clone());
-            cold_read_behaviour_results.add_records(cold_read_behaviour.clone())?;
-            let mut file_name = self.config.log_path.clone();
-            file_name.push(format!("{}_cold_read.csv", self.config.fs_names[idx]));
-            cold_read_behaviour_results.log(&file_name)?;
-            plotter_cold_read_behaviour.add_coordinates(
-                cold_read_behaviour,
-                Some(self.config.fs_names[idx].clone()),
-                Indexes::new(0, false, 1, None, None),
-            )?;
+        let mut times = vec![];
+        loop {
+            let elapsed = start.elapsed();
+            if elapsed >= max_time || interrupt::interrupted() || worker.is_finished() {
+                return Ok(elapsed);
+            }
 
-            let mut write_behaviour_results = BenchResult::new(behaviour_header.clone());
-            write_behaviour_results.add_records(write_behaviour.clone())?;
-            let mut file_name = self.config.log_path.clone();
-            file_name.push(format!("{}_write.csv", self.config.fs_names[idx]));
-            write_behaviour_results.log(&file_name)?;
-            plotter_write_behaviour.add_coordinates(
-                write_behaviour,
-                Some(self.config.fs_names[idx].clone()),
-                Indexes::new(0, false, 1, None, None),
-            )?;
+            // the worker may stop by itself between the check and the request
+            if sender.send(Request::Collect).is_err() {
+                return Ok(start.elapsed());
+            }
+            match receiver.recv() {
+                Ok(mut collected) => times.append(&mut collected),
+                Err(_) => return Ok(start.elapsed()),
+            }
+            if !times.is_empty() {
+                let ops_per_seconds =
+                    Statistics::ops_in_window(&times, elapsed, self.config.window())?
+                        .iter()
+                        .map(|(_t, ops_s)| *ops_s)
+                        .collect::<Vec<_>>();
+                if let Ok(statistics) = Statistics::new(&ops_per_seconds) {
+                    let transient = statistics.mser5_truncation();
+                    if let Ok(steady) = Statistics::new(&ops_per_seconds[transient..]) {
+                        if steady.relative_precision(self.config.confidence_level)? <= precision {
+                            return Ok(start.elapsed());
+                        }
+                    }
+                }
+            }
 
-            let mut write_sync_behaviour_results = BenchResult::new(behaviour_header.clone());
-            write_sync_behaviour_results.add_records(write_sync_behaviour.clone())?;
-            let mut file_name = self.config.log_path.clone();
-            file_name.push(format!("{}_write_sync.csv", self.config.fs_names[idx]));
-            write_sync_behaviour_results.log(&file_name)?;
-            plotter_write_sync_behaviour.add_coordinates(
-                write_sync_behaviour,
-                Some(self.config.fs_names[idx].clone()),
-                Indexes::new(0, false, 1, None, None),
-            )?;
+            interrupt::sleep_until(Duration::from_secs(1).min(max_time - elapsed), || {
+                worker.is_finished()
+            });
+        }
+    }
 
-            // log and plot sample iteration average ops/s
-            let ops_s_samples_header = ["iterations".to_string(), "ops/s".to_string()].to_vec();
-            let mut mkdir_times_results = BenchResult::new(ops_s_samples_header.clone());
-            mkdir_times_results.add_records(mkdir_times.clone())?;
-            let mut file_name = self.config.log_path.clone();
-            file_name.push(format!(
-                "{}_mkdir_ops_s_period.csv",
-                self.config.fs_names[idx]
-            ));
-            mkdir_times_results.log(&file_name)?;
-
-            let mut plotter = Plotter::new();
-            plotter.add_coordinates(mkdir_times, None, Indexes::new(0, false, 1, None, None))?;
-            file_name.set_extension("svg");
-            plotter.point_series(
-                Some("Sampling iterations"),
-                Some("Average Ops/s"),
-                Some(&format!("Mkdir ({})", self.config.fs_names[idx])),
-                &file_name,
-            )?;
+    // compare the ops/s of each pair of filesystems for each benchmark function by the Mann-Whitney
+    // U test, Welch's t-test and the bootstrap confidence interval of the difference of means, and
+    // log which differences are significant. The tests assume independent samples, so the ops/s of
+    // the time windows of single runs, which are autocorrelated, only get an estimate without a
+    // verdict.
+    fn compare_filesystems(
+        &self,
+        bench_fns: &[(BenchFn, &str)],
+        samples: &[Vec<(String, Vec<f64>, bool)>],
+        run_results: &mut RunResults,
+    ) -> Result<(), Error> {
+        let significance_level = self.config.significance_level;
+        let header = [
+            "operation".to_string(),
+            "fs_a".to_string(),
+            "fs_b".to_string(),
+            "ops/s_a".to_string(),
+            "ops/s_b".to_string(),
+            "mann_whitney_u".to_string(),
+            "mann_whitney_p".to_string(),
+            "welch_t".to_string(),
+            "welch_p".to_string(),
+            "difference_lb".to_string(),
+            "difference_ub".to_string(),
+            "verdict".to_string(),
+        ]
+        .to_vec();
+        let mut results = BenchResult::new(header);
 
-            let mut mknod_times_results = BenchResult::new(ops_s_samples_header.clone());
-            mknod_times_results.add_records(mknod_times.clone())?;
-            let mut file_name = self.config.log_path.clone();
-            file_name.push(format!(
-                "{}_mknod_ops_s_period.csv",
-                self.config.fs_names[idx]
-            ));
-            mknod_times_results.log(&file_name)?;
-
-            let mut plotter = Plotter::new();
-            plotter.add_coordinates(mknod_times, None, Indexes::new(0, false, 1, None, None))?;
-            file_name.set_extension("svg");
-            plotter.point_series(
-                Some("Sampling iterations"),
-                Some("Average Ops/s"),
-                Some(&format!("Mknod ({})", self.config.fs_names[idx])),
-                &file_name,
-            )?;
+        outputln!(
+            "differences between the filesystems (significance level: {})",
+            significance_level
+        );
+        for ((bench_fn, _), fn_samples) in bench_fns.iter().zip(samples.iter()) {
+            for (idx, (fs_a, sample_a, runs_a)) in fn_samples.iter().enumerate() {
+                for (fs_b, sample_b, runs_b) in fn_samples.iter().skip(idx + 1) {
+                    // the tests need a few samples from each filesystem
+                    let (a, b) = match (Statistics::new(sample_a), Statistics::new(sample_b)) {
+                        (Ok(a), Ok(b)) => (a, b),
+                        _ => continue,
+                    };
 
-            let mut read_times_results = BenchResult::new(ops_s_samples_header.clone());
-            read_times_results.add_records(read_times.clone())?;
-            let mut file_name = self.config.log_path.clone();
-            file_name.push(format!(
-                "{}_read_ops_s_period.csv",
-                self.config.fs_names[idx]
-            ));
-            read_times_results.log(&file_name)?;
-
-            let mut plotter = Plotter::new();
-            plotter.add_coordinates(read_times, None, Indexes::new(0, false, 1, None, None))?;
-            file_name.set_extension("svg");
-            plotter.point_series(
-                Some("Sampling iterations"),
-                Some("Average Ops/s"),
-                Some(&format!("Read ({})", self.config.fs_names[idx])),
-                &file_name,
-            )?;
+                    if !runs_a || !runs_b {
+                        outputln!(
+                            "{:12} {} vs {}: single-run estimate (ops/s: {:.2} vs {:.2}, at least 3 runs are needed for a verdict)",
+                            bench_fn.to_string(),
+                            fs_a,
+                            fs_b,
+                            a.mean(),
+                            b.mean()
+                        );
+                        results.add_record(
+                            [
+                                vec![
+                                    bench_fn.to_string(),
+                                    fs_a.clone(),
+                                    fs_b.clone(),
+                                    a.mean().to_string(),
+                                    b.mean().to_string(),
+                                ],
+                                vec![String::new(); 6],
+                                vec!["single-run estimate".to_string()],
+                            ]
+                            .concat()
+                            .into(),
+                        )?;
+                        continue;
+                    }
 
-            let mut cold_read_times_results = BenchResult::new(ops_s_samples_header.clone());
-            cold_read_times_results.add_records(cold_read_times.clone())?;
-            let mut file_name = self.config.log_path.clone();
-            file_name.push(format!(
-                "{}_cold_read_ops_s_period.csv",
-                self.config.fs_names[idx]
-            ));
-            cold_read_times_results.log(&file_name)?;
-
-            let mut plotter = Plotter::new();
-            plotter.add_coordinates(
-                cold_read_times,
-                None,
-                Indexes::new(0, false, 1, None, None),
-            )?;
-            file_name.set_extension("svg");
-            plotter.point_series(
-                Some("Sampling iterations"),
-                Some("Average Ops/s"),
-                Some(&format!("Cold read ({})", self.config.fs_names[idx])),
-                &file_name,
-            )?;
+                    let mann_whitney = a.mann_whitney_u(&b);
+                    let welch = a.welch_t_test(&b);
+                    let (difference_lb, difference_ub) = a.difference_confidence_interval(
+                        &b,
+                        1f64 - significance_level,
+                        self.config.bootstrap_iterations,
+                    )?;
 
-            let mut write_times_results = BenchResult::new(ops_s_samples_header.clone());
-            write_times_results.add_records(write_times.clone())?;
-            let mut file_name = self.config.log_path.clone();
-            file_name.push(format!(
-                "{}_write_ops_s_period.csv",
-                self.config.fs_names[idx]
-            ));
-            write_times_results.log(&file_name)?;
-
-            let mut plotter = Plotter::new();
-            plotter.add_coordinates(write_times, None, Indexes::new(0, false, 1, None, None))?;
-            file_name.set_extension("svg");
-            plotter.point_series(
-                Some("Sampling iterations"),
-                Some("Average Ops/s"),
-                Some(&format!("Write ({})", self.config.fs_names[idx])),
-                &file_name,
-            )?;
+                    // the difference is significant if all the tests agree on it
+                    let significant = [
+                        mann_whitney.p_value < significance_level,
+                        welch.p_value < significance_level,
+                        difference_lb > 0f64 || difference_ub < 0f64,
+                    ];
+                    let verdict = if significant.iter().all(|s| *s) {
+                        "significant"
+                    } else if significant.iter().any(|s| *s) {
+                        "inconclusive"
+                    } else {
+                        "not significant"
+                    };
 
-            let mut write_sync_times_results = BenchResult::new(ops_s_samples_header.clone());
-            write_sync_times_results.add_records(write_sync_times.clone())?;
-            let mut file_name = self.config.log_path.clone();
-            file_name.push(format!(
-                "{}_write_sync_ops_s_period.csv",
-                self.config.fs_names[idx]
-            ));
-            write_sync_times_results.log(&file_name)?;
-
-            let mut plotter = Plotter::new();
-            plotter.add_coordinates(
-                write_sync_times,
-                None,
-                Indexes::new(0, false, 1, None, None),
-            )?;
-            file_name.set_extension("svg");
-            plotter.point_series(
-                Some("Sampling iterations"),
-                Some("Average Ops/s"),
-                Some(&format!("Write_sync ({})", self.config.fs_names[idx])),
-                &file_name,
-            )?;
+                    outputln!(
+                        "{:12} {} vs {}: {} (Mann-Whitney p: {:.4}, Welch p: {:.4}, difference: [{:.2}, {:.2}])",
+                        bench_fn.to_string(),
+                        fs_a,
+                        fs_b,
+                        verdict,
+                        mann_whitney.p_value,
+                        welch.p_value,
+                        difference_lb,
+                        difference_ub
+                    );
+
+                    results.add_record(
+                        vec![
+                            bench_fn.to_string(),
+                            fs_a.clone(),
+                            fs_b.clone(),
+                            a.mean().to_string(),
+                            b.mean().to_string(),
+                            mann_whitney.statistic.to_string(),
+                            mann_whitney.p_value.to_string(),
+                            welch.statistic.to_string(),
+                            welch.p_value.to_string(),
+                            difference_lb.to_string(),
+                            difference_ub.to_string(),
+                            verdict.to_string(),
+                        ]
+                        .into(),
+                    )?;
+                }
+            }
         }
+        outputln!();
 
-        // plot the behaviour results
         let mut file_name = self.config.log_path.clone();
-        file_name.push("mkdir.svg");
-        plotter_mkdir_behaviour.line_chart(
-            Some("Time (s)"),
-            Some("Ops/s"),
-            Some("Mkdir"),
-            false,
-            false,
-            &file_name,
-        )?;
+        file_name.push("significance.csv");
+        run_results.log(&results, &file_name, ResultMode::Significance)
+    }
 
-        let mut file_name = self.config.log_path.clone();
-        file_name.push("mknod.svg");
-        plotter_mknod_behaviour.line_chart(
-            Some("Time (s)"),
-            Some("Ops/s"),
-            Some("Mknod"),
-            false,
-            false,
-            &file_name,
-        )?;
+    // log the results of the runs of a benchmark function and summarize them by the statistics
+    // across the runs
+    fn compare_runs(
+        &self,
+        op: &BenchFn,
+        fs_name: &str,
+        runs: &[Record],
+        run_results: &mut RunResults,
+    ) -> Result<RunsSummary, Error> {
+        let runs_header = ops_per_second_header("run");
 
-        let mut file_name = self.config.log_path.clone();
-        file_name.push("read.svg");
-        plotter_read_behaviour.line_chart(
-            Some("Time (s)"),
-            Some("Ops/s"),
-            Some("Read"),
-            false,
-            false,
-            &file_name,
-        )?;
+        let mut run_time = 0f64;
+        let mut ops_s = vec![];
+        let mut ops = 0u64;
+        let mut failures = 0u64;
+        let mut runs_results = BenchResult::new(runs_header);
+        for (idx, run) in runs.iter().enumerate() {
+            run_time += run.fields[1].parse::<f64>()?;
+            ops_s.push(run.fields[2].parse::<f64>()?);
+            ops += run.fields[5].parse::<u64>()?;
+            failures += run.fields[6].parse::<u64>()?;
 
-        let mut file_name = self.config.log_path.clone();
-        file_name.push("cold_read.svg");
-        plotter_cold_read_behaviour.line_chart(
-            Some("Time (s)"),
-            Some("Ops/s"),
-            Some("Cold read"),
-            false,
-            false,
-            &file_name,
-        )?;
+            let mut fields = run.fields.clone();
+            fields[0] = (idx + 1).to_string();
+            runs_results.add_record(Record { fields })?;
+        }
 
         let mut file_name = self.config.log_path.clone();
-        file_name.push("write.svg");
-        plotter_write_behaviour.line_chart(
-            Some("Time (s)"),
-            Some("Ops/s"),
-            Some("Write"),
-            false,
-            false,
-            &file_name,
-        )?;
+        file_name.push(format!("{}_{}_runs.csv", fs_name, op.to_string()));
+        run_results.log(&runs_results, &file_name, ResultMode::OpsPerSecond)?;
 
-        let mut file_name = self.config.log_path.clone();
-        file_name.push("write_sync.svg");
-        plotter_write_sync_behaviour.line_chart(
-            Some("Time (s)"),
-            Some("Ops/s"),
-            Some("Write (full sync)"),
-            false,
-            false,
-            &file_name,
-        )?;
+        let statistics = Statistics::new(&ops_s)?;
+        let (mean_lb, mean_ub) = statistics.t_confidence_interval(self.config.confidence_level)?;
+        // the ops/s are floored like those of a single run, so that the summary and the ops/s
+        // record agree
+        let summary = RunsSummary {
+            op: op.to_string(),
+            runs: runs.len(),
+            run_time,
+            mean: statistics.mean().floor(),
+            std: statistics.sample_std(),
+            cv: statistics.sample_std() / statistics.mean(),
+            mean_lb: mean_lb.floor(),
+            mean_ub: mean_ub.floor(),
+            confidence_level: self.config.confidence_level,
+            ops,
+            failures,
+            robust: statistics.robust(),
+        };
 
-        Ok(())
+        outputln!(
+            "{} ({}) across {} runs",
+            op.to_string(),
+            fs_name,
+            summary.runs
+        );
+        outputln!("{:18} {:.2}", "mean ops/s:", summary.mean);
+        outputln!("{:18} {:.2}", "std:", summary.std);
+        outputln!("{:18} {:.2}%", "cv:", summary.cv * 100f64);
+        outputln!(
+            "{:18} [{:.2}, {:.2}]",
+            format!("ops/s ({}):", ci_label(summary.confidence_level)),
+            summary.mean_lb,
+            summary.mean_ub
+        );
+        print_robust_statistics(&summary.robust, false);
+        outputln!();
+
+        Ok(summary)
     }
 
     fn micro_op(
@@ -408,58 +619,113 @@ impl OfflineBench {
         mount_path: &PathBuf,
         fs_name: &str,
         style: ProgressStyle,
-    ) -> Result<(Record, Vec<Record>, Vec<Record>), Error> {
+    ) -> Result<Option<OpResults>, Error> {
         let mut root_path = mount_path.clone();
         root_path.push(op.to_string());
 
         let invalidate_cache = if op == BenchFn::ColdRead { true } else { false };
-        self.setup(&root_path, invalidate_cache)?;
+        self.setup_root(&root_path, op.creates_files(), invalidate_cache)?;
 
         let io_size = self.config.io_size;
+        let file_size = self.config.file_size;
         let fileset_size = self.config.fileset_size;
         let operation = op.clone();
+        let verifier = self.config.verifier();
+        let verify = verifier.is_some();
+        let fs = self.config.backend.clone();
+        // the reads and writes are run by io_uring if it is the io engine
+        let uring = match (self.config.io_engine, op.io_size(io_size)) {
+            (IoEngine::IoUring, Some(_)) if !op.is_mmap() => Some(self.config.uring),
+            _ => None,
+        };
 
         // create a big vector filled with random content
         let mut rand_content = vec![0u8; 8192 * io_size];
         let mut rng = rand::thread_rng();
         rng.fill_bytes(&mut rand_content);
 
-        let bar = ProgressBar::new_spinner();
+        let bar = output::spinner();
         bar.set_style(style);
         bar.set_message(format!("{} ({})", op.to_string(), fs_name));
         let progress = Progress::start(bar.clone());
 
         let (sender, receiver) = channel();
-        let handle = std::thread::spawn(move || -> Result<(Vec<SystemTime>, u64), Error> {
+        // the worker sends the times collected so far on request, to check for the steady state
+        let (collected_sender, collected_receiver) = channel();
+        let max_failures = self.config.max_failures;
+        let write_sync = self.config.write_sync;
+        let dir_sync = self.config.dir_sync;
+        // calibrate the timer overhead before the ops are timed
+        clock::overhead();
+        let handle = std::thread::spawn(move || -> Result<WorkerResults, Error> {
+            if let Some(uring) = uring {
+                return uring_op(
+                    &operation,
+                    uring,
+                    &root_path,
+                    fileset_size,
+                    io_size,
+                    &rand_content,
+                    verifier.as_ref(),
+                    max_failures,
+                    &receiver,
+                    &collected_sender,
+                );
+            }
+
             let mut behaviour = vec![];
             let mut idx = 0;
+            let mut mismatches = vec![];
+            let mut failures = Failures::new();
+            let mut collected = 0; // the number of times sent on request
+            let op_name = operation.to_string();
+            // the time spent on other work than the ops, which is excluded from the op times
+            let mut excluded_time = Duration::ZERO;
+            let mut syncer = Syncer::new(write_sync);
+            let mut mappings = Mappings::new();
 
             loop {
                 match receiver.try_recv() {
-                    Ok(true) => {
-                        return Ok((behaviour, idx));
+                    Ok(Request::Stop) => {
+                        // the writes since the last sync are synced before the run ends
+                        if let Err(e) = syncer.sync() {
+                            error!("error: {:?}", e);
+                            failures.add_io(&op_name, &e);
+                        }
+                        return Ok((behaviour, idx, mismatches, failures, None));
+                    }
+                    Ok(Request::Collect) => {
+                        collected_sender.send(behaviour[collected..].to_vec())?;
+                        collected = behaviour.len();
                     }
                     _ => match operation {
                         BenchFn::Mkdir => {
                             // find a random leaf from the existing directory hierarchy and
                             // generate some (random number between 0 to 100) directories inside it
-                            let start = SystemTime::now();
-                            let random_dir = random_leaf(&root_path)?;
+                            let start = Timestamp::now();
+                            let random_dir = random_leaf(fs.as_ref(), &root_path)?;
                             let dirs = thread_rng().gen_range(0..100);
-                            let end = start.elapsed()?;
+                            // exclude the time for choosing a leaf randomly from the op times
+                            excluded_time += start.elapsed();
 
                             for dir in 0..dirs {
                                 let mut dir_name = random_dir.clone();
                                 dir_name.push(dir.to_string());
-                                match Fs::make_dir(&dir_name) {
+                                match fs.mkdir(&dir_name).and_then(|_| {
+                                    if dir_sync {
+                                        fs.sync_dir(&random_dir)
+                                    } else {
+                                        Ok(())
+                                    }
+                                }) {
                                     Ok(()) => {
-                                        let now = SystemTime::now();
-                                        // subtract the time for choosing a leaf randomly from the op time
-                                        behaviour.push(now.checked_sub(end).unwrap_or(now));
+                                        behaviour.push(op_timestamp(&mut excluded_time));
                                         idx = idx + 1;
                                     }
                                     Err(e) => {
                                         error!("error: {:?}", e);
+                                        failures.add_io(&op_name, &e);
+                                        failures.check(max_failures)?;
                                     }
                                 }
                             }
@@ -467,70 +733,181 @@ impl OfflineBench {
                         BenchFn::Mknod => {
                             let mut file_name = root_path.clone();
                             file_name.push(idx.to_string());
-                            match Fs::make_file(&file_name) {
+                            match fs.create(&file_name).and_then(|_| {
+                                if dir_sync {
+                                    fs.sync_dir(&root_path)
+                                } else {
+                                    Ok(())
+                                }
+                            }) {
                                 Ok(_) => {
-                                    behaviour.push(SystemTime::now());
+                                    behaviour.push(op_timestamp(&mut excluded_time));
                                     idx = idx + 1;
                                 }
                                 Err(e) => {
                                     error!("error: {:?}", e);
+                                    failures.add_io(&op_name, &e);
+                                    failures.check(max_failures)?;
                                 }
                             }
                         }
                         BenchFn::Read | BenchFn::ColdRead => {
                             let file = thread_rng().gen_range(0..fileset_size);
+                            // the file name is also the key its content is generated with
+                            let key = file.to_string();
                             let mut file_name = root_path.clone();
-                            file_name.push(file.to_string());
-                            let mut file = Fs::open_file(&file_name)?;
+                            file_name.push(&key);
                             let mut read_buffer = vec![0u8; io_size];
-                            match file.read_exact(&mut read_buffer) {
+                            match fs
+                                .open(&file_name)
+                                .and_then(|mut file| file.read_exact_at(&mut read_buffer, 0))
+                            {
                                 Ok(_) => {
-                                    behaviour.push(SystemTime::now());
+                                    behaviour.push(op_timestamp(&mut excluded_time));
                                     idx += 1;
+
+                                    if let Some(verifier) = verifier.as_ref() {
+                                        let start = Timestamp::now();
+                                        if let Some(mismatch) =
+                                            verifier.verify(&key, 0, &read_buffer)?
+                                        {
+                                            mismatches.push(mismatch);
+                                        }
+                                        // exclude the verification from the op times
+                                        excluded_time += start.elapsed();
+                                    }
                                 }
                                 Err(e) => {
-                                    println!("error: {:?}", e);
+                                    error!("error: {:?}", e);
+                                    failures.add_io(&op_name, &e);
+                                    failures.check(max_failures)?;
                                 }
                             }
                         }
                         BenchFn::Write => {
-                            let rand_content_index = thread_rng().gen_range(0..8192 - io_size - 1);
-                            let mut content = rand_content
+                            let rand_content_index =
+                                thread_rng().gen_range(0..=rand_content.len() - io_size);
+                            let content = rand_content
                                 [rand_content_index..(rand_content_index + io_size)]
                                 .to_vec();
 
                             let file = thread_rng().gen_range(0..fileset_size);
                             let mut file_name = root_path.clone();
                             file_name.push(file.to_string());
-                            let mut file = Fs::open_file(&file_name)?;
-                            match file.write_all(&mut content) {
+                            match fs
+                                .open(&file_name)
+                                .and_then(|mut file| file.write_all_at(&content, 0))
+                            {
                                 Ok(_) => {
-                                    behaviour.push(SystemTime::now());
+                                    behaviour.push(op_timestamp(&mut excluded_time));
                                     idx += 1;
                                 }
                                 Err(e) => {
-                                    println!("error: {:?}", e);
+                                    error!("error: {:?}", e);
+                                    failures.add_io(&op_name, &e);
+                                    failures.check(max_failures)?;
                                 }
                             }
                         }
                         BenchFn::WriteSync => {
-                            let rand_content_index = thread_rng().gen_range(0..8192 - io_size - 1);
-                            let mut content = rand_content
+                            let rand_content_index =
+                                thread_rng().gen_range(0..=rand_content.len() - io_size);
+                            let content = rand_content
                                 [rand_content_index..(rand_content_index + io_size)]
                                 .to_vec();
 
                             let file = thread_rng().gen_range(0..fileset_size);
                             let mut file_name = root_path.clone();
                             file_name.push(file.to_string());
-                            let mut file = Fs::open_file(&file_name)?;
-                            match file.write_all(&mut content) {
+                            match syncer.write(fs.as_ref(), &file_name, &content, 0) {
                                 Ok(_) => {
-                                    file.sync_data()?;
-                                    behaviour.push(SystemTime::now());
+                                    behaviour.push(op_timestamp(&mut excluded_time));
                                     idx += 1;
                                 }
                                 Err(e) => {
-                                    println!("error: {:?}", e);
+                                    error!("error: {:?}", e);
+                                    failures.add_io(&op_name, &e);
+                                    failures.check(max_failures)?;
+                                }
+                            }
+                        }
+                        BenchFn::MmapRead
+                        | BenchFn::MmapRandRead
+                        | BenchFn::MmapWrite
+                        | BenchFn::PageFault => {
+                            let rand_content_index =
+                                thread_rng().gen_range(0..=rand_content.len() - io_size);
+                            let content =
+                                &rand_content[rand_content_index..(rand_content_index + io_size)];
+
+                            let file = thread_rng().gen_range(0..fileset_size);
+                            // the file name is also the key its content is generated with
+                            let key = file.to_string();
+                            let mut file_name = root_path.clone();
+                            file_name.push(&key);
+                            let mut read_buffer = vec![0u8; io_size];
+                            match mmap_op(
+                                &operation,
+                                fs.as_ref(),
+                                &mut mappings,
+                                &file_name,
+                                file_size,
+                                io_size,
+                                content,
+                                &mut read_buffer,
+                                &mut excluded_time,
+                            ) {
+                                Ok((mut timestamps, offset)) => {
+                                    idx += timestamps.len() as u64;
+                                    behaviour.append(&mut timestamps);
+
+                                    if let (Some(verifier), BenchFn::MmapRead) =
+                                        (verifier.as_ref(), &operation)
+                                    {
+                                        let start = Timestamp::now();
+                                        if let Some(mismatch) =
+                                            verifier.verify(&key, offset as u64, &read_buffer)?
+                                        {
+                                            mismatches.push(mismatch);
+                                        }
+                                        // exclude the verification from the op times
+                                        excluded_time += start.elapsed();
+                                    }
+                                }
+                                Err(e) => {
+                                    error!("error: {:?}", e);
+                                    failures.add_io(&op_name, &e);
+                                    failures.check(max_failures)?;
+                                }
+                            }
+                        }
+                        BenchFn::CreateWrite
+                        | BenchFn::CreateFallocate
+                        | BenchFn::CreateSparse
+                        | BenchFn::PunchHole
+                        | BenchFn::CopyFileRange
+                        | BenchFn::Reflink => {
+                            let rand_content_index =
+                                thread_rng().gen_range(0..=rand_content.len() - io_size);
+                            let content =
+                                &rand_content[rand_content_index..(rand_content_index + io_size)];
+                            match file_op(
+                                fs.as_ref(),
+                                &operation,
+                                &root_path,
+                                idx,
+                                fileset_size,
+                                file_size,
+                                content,
+                            ) {
+                                Ok(_) => {
+                                    behaviour.push(op_timestamp(&mut excluded_time));
+                                    idx += 1;
+                                }
+                                Err(e) => {
+                                    error!("error: {:?}", e);
+                                    failures.add_io(&op_name, &e);
+                                    failures.check(max_failures)?;
                                 }
                             }
                         }
@@ -539,8 +916,17 @@ impl OfflineBench {
             }
         });
 
-        std::thread::sleep(run_time);
-        let (behaviour, idx) = match sender.send(true) {
+        // the run time is shorter than asked if the benchmark is interrupted, or if the ops/s has
+        // reached the steady state with the target precision
+        let run_time = match self.config.precision {
+            Some(precision) => {
+                self.adaptive_run(run_time, precision, &sender, &collected_receiver, &handle)?
+            }
+            // the worker stops by itself if too many operations fail
+            None => interrupt::sleep_until(run_time, || handle.is_finished()),
+        };
+        let (behaviour, idx, mismatches, failures, op_latencies) = match sender.send(Request::Stop)
+        {
             Ok(_) => {
                 bar.set_message(format!(
                     "{} ({}): waiting for collected data...",
@@ -549,7 +935,8 @@ impl OfflineBench {
                 ));
                 handle.join().unwrap()?
             }
-            Err(e) => return Err(Error::SyncError(e.to_string())),
+            // the worker has stopped by itself, and returns the error it stopped on
+            Err(_) => handle.join().unwrap()?,
         };
 
         bar.set_message(format!(
@@ -557,25 +944,140 @@ impl OfflineBench {
             op.to_string(),
             fs_name
         ));
-        let ops_in_window = Statistics::ops_in_window(&behaviour, run_time)?;
-        let ops_per_seconds = ops_in_window
+        if interrupt::interrupted() && !self.config.keep_fileset {
+            Fs::cleanup(
+                self.config.backend.as_ref(),
+                &mount_path.join(op.to_string()),
+            )?;
+        }
+
+        let window = self.config.window();
+        let time_windows = if behaviour.is_empty() {
+            vec![]
+        } else {
+            Statistics::time_windows(&behaviour, run_time, window)?
+        };
+        let ops_per_seconds = time_windows
             .iter()
-            .map(|(_t, ops_s)| *ops_s as f64)
+            .map(|time_window| time_window.ops_per_second)
             .collect::<Vec<_>>();
-        let analysed_data = Statistics::new(&ops_per_seconds)?.analyse()?;
+        // the initial transient is excluded from the analysis in the adaptive mode
+        let transient = match (self.config.precision, Statistics::new(&ops_per_seconds)) {
+            (Some(_), Ok(statistics)) => statistics.mser5_truncation(),
+            _ => 0,
+        };
+        let analysed_data = match Statistics::new(&ops_per_seconds[transient..]).and_then(|s| {
+            s.analyse(
+                self.config.confidence_level,
+                self.config.bootstrap_iterations,
+                self.config.ci_method,
+                self.config.exclude_outliers,
+            )
+        }) {
+            Ok(analysed_data) => analysed_data,
+            // an interrupted operation may not have run long enough to be analysed
+            Err(_) if interrupt::interrupted() => {
+                progress.finish_with_message(&format!(
+                    "{} ({}) interrupted, not enough samples to analyse",
+                    op.to_string(),
+                    fs_name
+                ))?;
+                return Ok(None);
+            }
+            // the filesystem may not support the operation at all, which is not fatal to the
+            // other operations
+            Err(_) if idx == 0 && failures.count() > 0 => {
+                progress.finish_with_message(&format!(
+                    "{} ({}) failed, no operation succeeded",
+                    op.to_string(),
+                    fs_name
+                ))?;
+                failures.print();
+                let mut file_name = self.config.log_path.clone();
+                file_name.push(format!("{}_{}_failures.csv", fs_name, op.to_string()));
+                failures.log(&file_name)?;
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        };
 
-        progress.finish_with_message(&format!("{} ({}) finished", op.to_string(), fs_name))?;
-        if op == BenchFn::Mkdir || op == Mknod {
-            print_output(idx, run_time.as_secs_f64(), io_size, &analysed_data, false);
-        } else {
-            print_output(idx, run_time.as_secs_f64(), io_size, &analysed_data, true);
+        progress.finish_with_message(&format!(
+            "{} ({}) {}",
+            op.to_string(),
+            fs_name,
+            if interrupt::interrupted() {
+                "interrupted"
+            } else {
+                "finished"
+            }
+        ))?;
+        if self.config.precision.is_some() {
+            let transient_time = if transient > 0 {
+                time_windows[transient - 1].time
+            } else {
+                0f64
+            };
+            outputln!("{:18} {}", "transient:", time_format(transient_time));
+        }
+        outputln!(
+            "{:18} {}",
+            "time window:",
+            time_format(window.as_secs_f64())
+        );
+        let mismatches_num = if verify { Some(mismatches.len()) } else { None };
+        match op.io_size(io_size) {
+            Some(op_io_size) => print_output(
+                idx,
+                run_time.as_secs_f64(),
+                op_io_size,
+                &analysed_data,
+                true,
+                mismatches_num,
+                failures.count(),
+            ),
+            None => print_output(
+                idx,
+                run_time.as_secs_f64(),
+                io_size,
+                &analysed_data,
+                false,
+                mismatches_num,
+                failures.count(),
+            ),
+        }
+
+        if verify {
+            let mut file_name = self.config.log_path.clone();
+            file_name.push(format!("{}_{}_mismatches.csv", fs_name, op.to_string()));
+            log_mismatches(mismatches, &file_name)?;
         }
 
-        let mut behaviour_records = vec![];
-        for (time, ops_s) in ops_in_window.iter() {
-            behaviour_records.push([time.to_string(), ops_s.to_string()].to_vec().into());
+        if failures.count() > 0 {
+            failures.print();
+            let mut file_name = self.config.log_path.clone();
+            file_name.push(format!("{}_{}_failures.csv", fs_name, op.to_string()));
+            failures.log(&file_name)?;
         }
 
+        let behaviour_records = behaviour_records(&time_windows, op.io_size(io_size));
+        // the time and latency in microseconds of the operations, strided to a bounded number. The
+        // latency of an op is the gap since the previous one, unless the ops overlap and the
+        // worker measured their latencies
+        let stride = (behaviour.len() / MAX_LATENCY_SAMPLES).max(1);
+        let latencies = (stride..behaviour.len())
+            .step_by(stride)
+            .map(|idx| {
+                let latency = match &op_latencies {
+                    Some(op_latencies) => op_latencies[idx],
+                    None => behaviour[idx].duration_since(behaviour[idx - 1]),
+                };
+                (
+                    behaviour[idx].duration_since(behaviour[0]).as_secs_f64(),
+                    latency.as_secs_f64() * 1e6,
+                )
+            })
+            .unzip();
+
         let ops_per_second_record = Record {
             fields: [
                 op.to_string(),
@@ -583,8 +1085,14 @@ impl OfflineBench {
                 analysed_data.mean.to_string(),
                 analysed_data.mean_lb.to_string(),
                 analysed_data.mean_ub.to_string(),
+                idx.to_string(),
+                failures.count().to_string(),
+                analysed_data.confidence_level.to_string(),
+                analysed_data.method.to_string(),
             ]
-            .to_vec(),
+            .into_iter()
+            .chain(robust_fields(&analysed_data.robust))
+            .collect(),
         };
 
         let mut ops_s_samples_records = vec![];
@@ -592,10 +1100,328 @@ impl OfflineBench {
             ops_s_samples_records.push([idx.to_string(), ops_s.to_string()].to_vec().into());
         }
 
-        Ok((
-            ops_per_second_record,
-            behaviour_records,
-            ops_s_samples_records,
-        ))
+        Ok(Some(OpResults {
+            ops_per_second: ops_per_second_record,
+            behaviour: behaviour_records,
+            samples: ops_s_samples_records,
+            latencies,
+        }))
+    }
+}
+
+// run the reads or writes of the benchmark function by io_uring until asked to stop, keeping up
+// to the queue depth of them in flight on the files of the fileset
+#[cfg(target_os = "linux")]
+fn uring_op(
+    op: &BenchFn,
+    config: UringConfig,
+    root_path: &PathBuf,
+    fileset_size: usize,
+    io_size: usize,
+    rand_content: &[u8],
+    verifier: Option<&Verifier>,
+    max_failures: Option<u64>,
+    receiver: &Receiver<Request>,
+    collected_sender: &Sender<Vec<Timestamp>>,
+) -> Result<WorkerResults, Error> {
+    // the files are opened before the ops are timed, so only the reads and writes are timed
+    reserve_open_files(fileset_size)?;
+    let files = (0..fileset_size)
+        .map(|file| {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(root_path.join(file.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut uring = Uring::new(config, files, io_size)?;
+    // the time each op in flight was pushed at, by its slot, as the ops overlap and the gaps
+    // between their completions are not their latencies
+    let mut pushed = vec![Timestamp::now(); config.queue_depth as usize];
+
+    let mut behaviour = vec![];
+    let mut latencies = vec![]; // the latency of each op in the behaviour
+    let mut idx = 0;
+    let mut mismatches = vec![];
+    let mut failures = Failures::new();
+    let mut collected = 0; // the number of times sent on request
+    let op_name = op.to_string();
+    // the time spent on other work than the ops, which is excluded from the op times
+    let mut excluded_time = Duration::ZERO;
+
+    loop {
+        match receiver.try_recv() {
+            Ok(Request::Stop) => {
+                // the ops still in flight are waited for, but not counted
+                uring.drain()?;
+                return Ok((behaviour, idx, mismatches, failures, Some(latencies)));
+            }
+            Ok(Request::Collect) => {
+                collected_sender.send(behaviour[collected..].to_vec())?;
+                collected = behaviour.len();
+            }
+            _ => {
+                // fill the queue, and then wait for at least one op to finish
+                while !uring.is_full() {
+                    let file = thread_rng().gen_range(0..fileset_size);
+                    let slot = match op {
+                        BenchFn::Write | BenchFn::WriteSync => {
+                            let rand_content_index =
+                                thread_rng().gen_range(0..=rand_content.len() - io_size);
+                            let content =
+                                &rand_content[rand_content_index..(rand_content_index + io_size)];
+                            uring.push_write(file, 0, content, *op == BenchFn::WriteSync)?
+                        }
+                        _ => uring.push_read(file, 0, io_size)?,
+                    };
+                    // on the same time line as the op timestamps, without the excluded time
+                    let now = Timestamp::now();
+                    pushed[slot] = now.checked_sub(excluded_time).unwrap_or(now);
+                }
+
+                for completion in uring.complete(1)? {
+                    match completion.result {
+                        Ok(_) => {
+                            let timestamp = op_timestamp(&mut excluded_time);
+                            behaviour.push(timestamp);
+                            latencies.push(timestamp.duration_since(pushed[completion.slot]));
+                            idx += 1;
+
+                            if let (Some(verifier), BenchFn::Read | BenchFn::ColdRead) =
+                                (verifier, op)
+                            {
+                                let start = Timestamp::now();
+                                // the file index is also the key its content is generated with
+                                if let Some(mismatch) = verifier.verify(
+                                    &completion.file.to_string(),
+                                    0,
+                                    uring.buffer(completion.slot),
+                                )? {
+                                    mismatches.push(mismatch);
+                                }
+                                // exclude the verification from the op times
+                                excluded_time += start.elapsed();
+                            }
+                        }
+                        Err(e) => {
+                            error!("error: {:?}", e);
+                            failures.add_io(&op_name, &e);
+                            failures.check(max_failures)?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// the io_uring engine is rejected by the configuration on other platforms
+#[cfg(not(target_os = "linux"))]
+fn uring_op(
+    _op: &BenchFn,
+    _config: UringConfig,
+    _root_path: &PathBuf,
+    _fileset_size: usize,
+    _io_size: usize,
+    _rand_content: &[u8],
+    _verifier: Option<&Verifier>,
+    _max_failures: Option<u64>,
+    _receiver: &Receiver<Request>,
+    _collected_sender: &Sender<Vec<Timestamp>>,
+) -> Result<WorkerResults, Error> {
+    Err(Error::InvalidConfig(
+        "The io_uring io engine is only supported on Linux".to_string(),
+    ))
+}
+
+// the results of a benchmark function run
+struct OpResults {
+    ops_per_second: Record,
+    behaviour: Vec<Record>,
+    samples: Vec<Record>,            // the sample iteration average ops/s
+    latencies: (Vec<f64>, Vec<f64>), // the times and latencies of the operations
+}
+
+// the statistics of a benchmark function across the repeated runs
+struct RunsSummary {
+    op: String,
+    runs: usize,
+    run_time: f64, // the total runtime of the runs
+    mean: f64,
+    std: f64,
+    cv: f64,
+    mean_lb: f64,
+    mean_ub: f64,
+    confidence_level: f64,
+    ops: u64,
+    failures: u64,
+    robust: RobustStatistics, // the robust statistics of the run means
+}
+
+impl RunsSummary {
+    // the ops/s record over all the runs, with the confidence interval across the runs
+    fn ops_per_second_record(&self) -> Record {
+        Record {
+            fields: [
+                self.op.clone(),
+                self.run_time.to_string(),
+                self.mean.to_string(),
+                self.mean_lb.to_string(),
+                self.mean_ub.to_string(),
+                self.ops.to_string(),
+                self.failures.to_string(),
+                self.confidence_level.to_string(),
+                CiMethod::T.to_string(),
+            ]
+            .into_iter()
+            .chain(robust_fields(&self.robust))
+            .collect(),
+        }
+    }
+}
+
+impl From<RunsSummary> for Record {
+    fn from(summary: RunsSummary) -> Self {
+        Self {
+            fields: [
+                summary.op,
+                summary.runs.to_string(),
+                summary.mean.to_string(),
+                summary.std.to_string(),
+                summary.cv.to_string(),
+                summary.mean_lb.to_string(),
+                summary.mean_ub.to_string(),
+                summary.confidence_level.to_string(),
+            ]
+            .to_vec(),
+        }
+    }
+}
+
+// the requests to the thread running a benchmark function
+enum Request {
+    // stop and return the results
+    Stop,
+    // send the times collected since the last request
+    Collect,
+}
+
+// the ops/s header, starting with the column naming each row
+fn ops_per_second_header(first: &str) -> Vec<String> {
+    [
+        first,
+        "runtime(s)",
+        "ops/s",
+        "ops/s_lb",
+        "ops/s_ub",
+        "ops",
+        "failures",
+        "confidence_level",
+        "ci_method",
+        "median",
+        "iqr",
+        "trimmed_mean",
+        "min",
+        "max",
+        "outliers",
+        "outlier_fraction",
+    ]
+    .iter()
+    .map(|column| column.to_string())
+    .collect()
+}
+
+// the robust statistics columns of an ops/s record
+fn robust_fields(robust: &RobustStatistics) -> Vec<String> {
+    [
+        robust.median.to_string(),
+        robust.iqr.to_string(),
+        robust.trimmed_mean.to_string(),
+        robust.min.to_string(),
+        robust.max.to_string(),
+        robust.outliers.to_string(),
+        robust.outlier_fraction.to_string(),
+    ]
+    .to_vec()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fs::{FileHandle, FileStat, FileSystem, StdFs, TempDir};
+    use std::io;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    // a backend that fails every create, as a full filesystem does
+    struct FullFs;
+
+    impl FileSystem for FullFs {
+        fn mkdir(&self, path: &Path) -> io::Result<()> {
+            StdFs.mkdir(path)
+        }
+
+        fn mkdir_all(&self, path: &Path) -> io::Result<()> {
+            StdFs.mkdir_all(path)
+        }
+
+        fn create(&self, _path: &Path) -> io::Result<Box<dyn FileHandle>> {
+            Err(io::Error::from_raw_os_error(libc::ENOSPC))
+        }
+
+        fn open(&self, path: &Path) -> io::Result<Box<dyn FileHandle>> {
+            StdFs.open(path)
+        }
+
+        fn open_dir(&self, path: &Path) -> io::Result<()> {
+            StdFs.open_dir(path)
+        }
+
+        fn sync_dir(&self, path: &Path) -> io::Result<()> {
+            StdFs.sync_dir(path)
+        }
+
+        fn stat(&self, path: &Path) -> io::Result<FileStat> {
+            StdFs.stat(path)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            StdFs.rename(from, to)
+        }
+
+        fn remove_file(&self, path: &Path) -> io::Result<()> {
+            StdFs.remove_file(path)
+        }
+
+        fn remove_dir(&self, path: &Path) -> io::Result<()> {
+            StdFs.remove_dir(path)
+        }
+
+        fn readdir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+            StdFs.readdir(path)
+        }
+    }
+
+    #[test]
+    fn max_failures_test() -> Result<(), Error> {
+        let dir = TempDir::new("offline")?;
+        StdFs.mkdir_all(&dir.0.join("results"))?;
+        let config = Config::builder(dir.0.join("results"))
+            .filesystem("full", dir.0.join("mount"))
+            .backend(Arc::new(FullFs))
+            .bench_fns(vec![BenchFn::Mknod])
+            .run_time(2f64)
+            .max_failures(10)
+            .quiet(true)
+            .build()?;
+
+        // the benchmark is aborted as soon as the maximum is reached, with the threshold error,
+        // rather than at the end of the run time
+        let start = Timestamp::now();
+        let result = OfflineBench::new(config)?.run(None);
+        assert!(matches!(result, Err(Error::FailureThreshold(_))));
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        Ok(())
     }
 }
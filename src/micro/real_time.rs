@@ -1,12 +1,23 @@
+use crate::clock::{self, Timestamp};
 use crate::error::Error;
-use crate::fs::Fs;
-use crate::micro::{micro_setup, print_output, random_leaf, BenchFn};
+use crate::failures::Failures;
+use crate::format::time_format;
+use crate::fs::FileSystem;
+use crate::integrity::{log_mismatches, Mismatch, Verifier};
+use crate::interrupt;
+use crate::micro::{
+    behaviour_header, behaviour_records, file_op, micro_setup, mmap_op, op_timestamp, print_output,
+    random_leaf, BenchFn, Mappings,
+};
+use crate::output::{self, outputln};
 use crate::plotter::{Indexes, Plotter};
 use crate::progress::Progress;
+use crate::results::RunResults;
 use crate::stats::Statistics;
-use crate::{Bench, BenchResult, Config};
+use crate::write_sync::{Syncer, WriteSyncPolicy};
+use crate::{Bench, BenchResult, Config, ResultMode};
 use async_channel::{unbounded, Receiver, Sender};
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::ProgressStyle;
 use log::error;
 use piston_window::event_id::{AFTER_RENDER, CLOSE};
 use piston_window::{EventLoop, GenericEvent, PistonWindow, WindowSettings};
@@ -14,11 +25,10 @@ use plotters::prelude::{ChartBuilder, IntoDrawingArea, LineSeries, Palette, Pale
 use plotters_piston::draw_piston_window;
 use rand::{thread_rng, Rng, RngCore};
 use std::collections::VecDeque;
-use std::io::{Read, Write};
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 use std::thread::JoinHandle;
-use std::time::{Duration, SystemTime};
+use std::time::Duration;
 
 pub struct RealTimeBench {
     config: Config,
@@ -28,6 +38,7 @@ pub struct RealTimeBench {
 
 impl Bench for RealTimeBench {
     fn new(config: Config) -> Result<Self, Error> {
+        output::set_quiet(config.quiet);
         let (sender, receiver) = unbounded();
         Ok(Self {
             config,
@@ -37,15 +48,10 @@ impl Bench for RealTimeBench {
     }
 
     fn setup(&self, path: &PathBuf, invalidate_cache: bool) -> Result<(), Error> {
-        micro_setup(
-            self.config.file_size,
-            self.config.fileset_size,
-            path,
-            invalidate_cache,
-        )
+        self.setup_root(path, false, invalidate_cache)
     }
 
-    fn run(&self, bench_fn: Option<BenchFn>) -> Result<(), Error> {
+    fn run(&self, bench_fn: Option<BenchFn>) -> Result<RunResults, Error> {
         let bench_fn = bench_fn.ok_or(Error::InvalidConfig(
             "A valid bench function not provided".to_string(),
         ))?;
@@ -60,7 +66,7 @@ impl Bench for RealTimeBench {
         } else {
             false
         };
-        self.setup(&root_path, invalidate_cache)?;
+        self.setup_root(&root_path, bench_fn.creates_files(), invalidate_cache)?;
 
         let progress_style = ProgressStyle::default_bar().template("[{elapsed_precise}] {msg}");
 
@@ -69,21 +75,35 @@ impl Bench for RealTimeBench {
         let shared_ops = ops.clone();
         let shared_bench_fn = bench_fn.clone();
         let io_size = self.config.io_size;
+        let file_size = self.config.file_size;
         let file_set_size = self.config.fileset_size;
-        let handle = std::thread::spawn(move || -> Result<(Vec<SystemTime>, u64), Error> {
-            RealTimeBench::realtime_op(
-                shared_bench_fn,
-                io_size,
-                file_set_size,
-                &root_path,
-                receiver,
-                shared_ops,
-            )
-        });
-
-        self.plot(ops, handle, progress_style, bench_fn.to_string())?;
-
-        Ok(())
+        let verifier = self.config.verifier();
+        let max_failures = self.config.max_failures;
+        let write_sync = self.config.write_sync;
+        let dir_sync = self.config.dir_sync;
+        let fs = self.config.backend.clone();
+        // calibrate the timer overhead before the ops are timed
+        clock::overhead();
+        let handle = std::thread::spawn(
+            move || -> Result<(Vec<Timestamp>, u64, Vec<Mismatch>, Failures), Error> {
+                RealTimeBench::realtime_op(
+                    fs,
+                    shared_bench_fn,
+                    io_size,
+                    file_size,
+                    file_set_size,
+                    &root_path,
+                    receiver,
+                    shared_ops,
+                    verifier,
+                    max_failures,
+                    write_sync,
+                    dir_sync,
+                )
+            },
+        );
+
+        self.plot(ops, handle, progress_style, bench_fn.to_string())
     }
 }
 
@@ -93,13 +113,34 @@ enum Signal {
 }
 
 impl RealTimeBench {
+    // set up the root path of a benchmark function: the fileset, or only the empty root directory
+    // for the functions creating their own files
+    fn setup_root(
+        &self,
+        path: &PathBuf,
+        creates_files: bool,
+        invalidate_cache: bool,
+    ) -> Result<(), Error> {
+        micro_setup(
+            self.config.backend.as_ref(),
+            self.config.file_size,
+            self.config.fileset_size,
+            path,
+            creates_files,
+            invalidate_cache,
+            self.config.verifier().as_ref(),
+            self.config.file_layout,
+        )
+    }
+
     fn plot(
         &self,
         ops: Arc<RwLock<f64>>,
-        handle: JoinHandle<Result<(Vec<SystemTime>, u64), Error>>,
+        handle: JoinHandle<Result<(Vec<Timestamp>, u64, Vec<Mismatch>, Failures), Error>>,
         style: ProgressStyle,
         bench_fn: String,
-    ) -> Result<(), Error> {
+    ) -> Result<RunResults, Error> {
+        let mut run_results = RunResults::new();
         let fps = 20; // frame per second
         let length = 20; // plot length in second
         let n_data_points: usize = (fps * length) as usize;
@@ -115,7 +156,7 @@ impl RealTimeBench {
         let mut data = VecDeque::from(vec![0f64; n_data_points + 1]);
         let mut ticks = 0;
 
-        let bar = ProgressBar::new_spinner();
+        let bar = output::spinner();
         bar.set_style(style);
         bar.set_message(bench_fn.clone());
         let progress = Progress::start(bar.clone());
@@ -186,59 +227,106 @@ impl RealTimeBench {
                     .try_send(Signal::Start)
                     .map_err(|err| Error::SyncError(err.to_string()))?;
             }
-            // if we have reached the max runtime or the plot window is closed, stop benchmarking
-            if ticks >= max_ticks || event.event_id() == CLOSE {
-                // plotting is finished
-                let (behaviour, ops) = match self.sender.try_send(Signal::Stop) {
-                    Ok(_) => {
-                        bar.set_message("waiting for collected data...");
-                        handle.join().unwrap()?
-                    }
-                    Err(e) => return Err(Error::SyncError(e.to_string())),
-                };
+            // if we have reached the max runtime, the plot window is closed, the benchmark is
+            // interrupted or the benchmark function has stopped on an error, stop benchmarking
+            let finished = handle.is_finished();
+            if ticks >= max_ticks
+                || event.event_id() == CLOSE
+                || interrupt::interrupted()
+                || finished
+            {
+                // plotting is finished, and a benchmark function that has stopped by itself is
+                // joined for its error without a stop signal
+                if !finished {
+                    self.sender
+                        .try_send(Signal::Stop)
+                        .map_err(|err| Error::SyncError(err.to_string()))?;
+                }
+                bar.set_message("waiting for collected data...");
+                let (behaviour, ops, mismatches, failures) = handle.join().unwrap()?;
 
                 let run_time = Duration::from_millis((ticks * tick_length) as u64);
 
                 bar.set_message("analysing data...");
-                let ops_in_window = Statistics::ops_in_window(&behaviour, run_time)?;
-                let ops_per_seconds = ops_in_window
+                let window_length = self.config.window();
+                let time_windows = Statistics::time_windows(&behaviour, run_time, window_length)?;
+                let ops_per_seconds = time_windows
                     .iter()
-                    .map(|(_t, ops_s)| *ops_s as f64)
+                    .map(|time_window| time_window.ops_per_second)
                     .collect::<Vec<_>>();
-                let analysed_data = Statistics::new(&ops_per_seconds)?.analyse()?;
+                let analysed_data = Statistics::new(&ops_per_seconds)?.analyse(
+                    self.config.confidence_level,
+                    self.config.bootstrap_iterations,
+                    self.config.ci_method,
+                    self.config.exclude_outliers,
+                )?;
 
-                let mut behaviour_records = vec![];
-                for (time, ops_s) in ops_in_window.iter() {
-                    behaviour_records.push([time.to_string(), ops_s.to_string()].to_vec().into());
-                }
+                // only the reads and writes have bytes/s
+                let io_size = bench_fn
+                    .parse::<BenchFn>()
+                    .map_err(Error::InvalidConfig)?
+                    .io_size(self.config.io_size);
+                let behaviour_records = behaviour_records(&time_windows, io_size);
 
                 progress.finish_with_message(&format!("{} finished", bench_fn))?;
-                if bench_fn == "mkdir" || bench_fn == "mknod" {
-                    print_output(
+                let mismatches_num = if self.config.verify {
+                    Some(mismatches.len())
+                } else {
+                    None
+                };
+                match io_size {
+                    Some(io_size) => print_output(
                         ops,
                         run_time.as_secs_f64(),
-                        self.config.io_size,
+                        io_size,
                         &analysed_data,
-                        false,
-                    );
-                } else {
-                    print_output(
+                        true,
+                        mismatches_num,
+                        failures.count(),
+                    ),
+                    None => print_output(
                         ops,
                         run_time.as_secs_f64(),
                         self.config.io_size,
                         &analysed_data,
-                        true,
-                    );
+                        false,
+                        mismatches_num,
+                        failures.count(),
+                    ),
+                }
+                outputln!(
+                    "{:18} {}",
+                    "time window:",
+                    time_format(window_length.as_secs_f64())
+                );
+
+                if self.config.verify {
+                    let mut file_name = self.config.log_path.clone();
+                    file_name.push(format!(
+                        "{}_{}_mismatches.csv",
+                        self.config.fs_names[0], bench_fn
+                    ));
+                    log_mismatches(mismatches, &file_name)?;
+                }
+
+                if failures.count() > 0 {
+                    failures.print();
+                    let mut file_name = self.config.log_path.clone();
+                    file_name.push(format!(
+                        "{}_{}_failures.csv",
+                        self.config.fs_names[0], bench_fn
+                    ));
+                    failures.log(&file_name)?;
                 }
 
                 // log behaviour result
-                let behaviour_header = ["time".to_string(), "ops".to_string()].to_vec();
+                let behaviour_header = behaviour_header();
                 let mut mkdir_behaviour_results = BenchResult::new(behaviour_header.clone());
                 mkdir_behaviour_results.add_records(behaviour_records.clone())?;
                 let mut file_name = self.config.log_path.clone();
                 file_name.push(format!("{}_{}.csv", self.config.fs_names[0], bench_fn));
-                mkdir_behaviour_results.log(&file_name)?;
-                let mut plotter = Plotter::new();
+                run_results.log(&mkdir_behaviour_results, &file_name, ResultMode::Behaviour)?;
+                let mut plotter = Plotter::with_style(self.config.plot_style.clone());
                 plotter.add_coordinates(
                     behaviour_records,
                     None,
@@ -261,29 +349,47 @@ impl RealTimeBench {
             }
         }
 
-        Ok(())
+        Ok(run_results)
     }
 
     fn realtime_op(
+        fs: Arc<dyn FileSystem>,
         op: BenchFn,
         io_size: usize,
+        file_size: usize,
         fileset_size: usize,
         path: &PathBuf,
         receiver: Receiver<Signal>,
         ops: Arc<RwLock<f64>>,
-    ) -> Result<(Vec<SystemTime>, u64), Error> {
+        verifier: Option<Verifier>,
+        max_failures: Option<u64>,
+        write_sync: WriteSyncPolicy,
+        dir_sync: bool,
+    ) -> Result<(Vec<Timestamp>, u64, Vec<Mismatch>, Failures), Error> {
         let mut behaviour = vec![];
         let mut idx = 0;
+        let mut mismatches = vec![];
+        let mut failures = Failures::new();
+        let op_name = op.to_string();
+        // the time spent on other work than the ops, which is excluded from the op times
+        let mut excluded_time = Duration::ZERO;
 
         // create a big vector filled with random content
         let mut rand_content = vec![0u8; 8192 * io_size];
         let mut rng = rand::thread_rng();
         rng.fill_bytes(&mut rand_content);
+        let mut syncer = Syncer::new(write_sync);
+        let mut mappings = Mappings::new();
         let mut start = false;
         loop {
             match receiver.try_recv() {
                 Ok(Signal::Stop) => {
-                    return Ok((behaviour, idx));
+                    // the writes since the last sync are synced before the run ends
+                    if let Err(e) = syncer.sync() {
+                        error!("error: {:?}", e);
+                        failures.add_io(&op_name, &e);
+                    }
+                    return Ok((behaviour, idx, mismatches, failures));
                 }
                 Ok(Signal::Start) => {
                     start = true;
@@ -296,24 +402,31 @@ impl RealTimeBench {
                     BenchFn::Mkdir => {
                         // find a random leaf from the existing directory hierarchy and
                         // generate some (random number between 0 to 100) directories inside it
-                        let start = SystemTime::now();
-                        let random_dir = random_leaf(&path)?;
+                        let start = Timestamp::now();
+                        let random_dir = random_leaf(fs.as_ref(), &path)?;
                         let dirs = thread_rng().gen_range(0..100);
-                        let end = start.elapsed()?;
+                        // exclude the time for choosing a leaf randomly from the op times
+                        excluded_time += start.elapsed();
 
                         for dir in 0..dirs {
                             let mut dir_name = random_dir.clone();
                             dir_name.push(dir.to_string());
-                            match Fs::make_dir(&dir_name) {
+                            match fs.mkdir(&dir_name).and_then(|_| {
+                                if dir_sync {
+                                    fs.sync_dir(&random_dir)
+                                } else {
+                                    Ok(())
+                                }
+                            }) {
                                 Ok(()) => {
-                                    let now = SystemTime::now();
-                                    // subtract the time for choosing a leaf randomly from the op time
-                                    behaviour.push(now.checked_sub(end).unwrap_or(now));
+                                    behaviour.push(op_timestamp(&mut excluded_time));
                                     idx = idx + 1;
                                     *ops.write()? += 1.0;
                                 }
                                 Err(e) => {
                                     error!("error: {:?}", e);
+                                    failures.add_io(&op_name, &e);
+                                    failures.check(max_failures)?;
                                 }
                             }
                         }
@@ -321,76 +434,181 @@ impl RealTimeBench {
                     BenchFn::Mknod => {
                         let mut file_name = path.clone();
                         file_name.push(idx.to_string());
-                        match Fs::make_file(&file_name) {
+                        match fs.create(&file_name).and_then(|_| {
+                            if dir_sync {
+                                fs.sync_dir(path)
+                            } else {
+                                Ok(())
+                            }
+                        }) {
                             Ok(_) => {
-                                behaviour.push(SystemTime::now());
+                                behaviour.push(op_timestamp(&mut excluded_time));
                                 idx = idx + 1;
                                 *ops.write()? += 1.0;
                             }
                             Err(e) => {
                                 error!("error: {:?}", e);
+                                failures.add_io(&op_name, &e);
+                                failures.check(max_failures)?;
                             }
                         }
                     }
                     BenchFn::Read | BenchFn::ColdRead => {
                         let file = thread_rng().gen_range(0..fileset_size);
+                        // the file name is also the key its content is generated with
+                        let key = file.to_string();
                         let mut file_name = path.clone();
-                        file_name.push(file.to_string());
+                        file_name.push(&key);
                         let mut read_buffer = vec![0u8; io_size];
-                        let mut file = Fs::open_file(&file_name)?;
-                        match file.read_exact(&mut read_buffer) {
+                        match fs
+                            .open(&file_name)
+                            .and_then(|mut file| file.read_exact_at(&mut read_buffer, 0))
+                        {
                             Ok(_) => {
-                                behaviour.push(SystemTime::now());
+                                behaviour.push(op_timestamp(&mut excluded_time));
                                 idx += 1;
                                 *ops.write()? += 1.0;
+
+                                if let Some(verifier) = verifier.as_ref() {
+                                    if let Some(mismatch) =
+                                        verifier.verify(&key, 0, &read_buffer)?
+                                    {
+                                        mismatches.push(mismatch);
+                                    }
+                                }
                             }
                             Err(e) => {
-                                println!("error: {:?}", e);
+                                error!("error: {:?}", e);
+                                failures.add_io(&op_name, &e);
+                                failures.check(max_failures)?;
                             }
                         }
                     }
                     BenchFn::Write => {
                         let rand_content_index =
                             thread_rng().gen_range(0..(8192 * io_size) - io_size - 1);
-                        let mut content = rand_content
+                        let content = rand_content
                             [rand_content_index..(rand_content_index + io_size)]
                             .to_vec();
 
                         let file = thread_rng().gen_range(1..fileset_size);
                         let mut file_name = path.clone();
                         file_name.push(file.to_string());
-                        let mut file = Fs::open_file(&file_name)?;
-                        match file.write_all(&mut content) {
+                        match fs
+                            .open(&file_name)
+                            .and_then(|mut file| file.write_all_at(&content, 0))
+                        {
                             Ok(_) => {
-                                behaviour.push(SystemTime::now());
+                                behaviour.push(op_timestamp(&mut excluded_time));
                                 idx += 1;
                                 *ops.write()? += 1.0;
                             }
                             Err(e) => {
-                                println!("error: {:?}", e);
+                                error!("error: {:?}", e);
+                                failures.add_io(&op_name, &e);
+                                failures.check(max_failures)?;
                             }
                         }
                     }
                     BenchFn::WriteSync => {
                         let rand_content_index =
                             thread_rng().gen_range(0..(8192 * io_size) - io_size - 1);
-                        let mut content = rand_content
+                        let content = rand_content
                             [rand_content_index..(rand_content_index + io_size)]
                             .to_vec();
 
                         let file = thread_rng().gen_range(1..fileset_size);
                         let mut file_name = path.clone();
                         file_name.push(file.to_string());
-                        let mut file = Fs::open_file(&file_name)?;
-                        match file.write_all(&mut content) {
+                        match syncer.write(fs.as_ref(), &file_name, &content, 0) {
                             Ok(_) => {
-                                file.sync_data()?;
-                                behaviour.push(SystemTime::now());
+                                behaviour.push(op_timestamp(&mut excluded_time));
                                 idx += 1;
                                 *ops.write()? += 1.0;
                             }
                             Err(e) => {
-                                println!("error: {:?}", e);
+                                error!("error: {:?}", e);
+                                failures.add_io(&op_name, &e);
+                                failures.check(max_failures)?;
+                            }
+                        }
+                    }
+                    BenchFn::MmapRead
+                    | BenchFn::MmapRandRead
+                    | BenchFn::MmapWrite
+                    | BenchFn::PageFault => {
+                        let rand_content_index =
+                            thread_rng().gen_range(0..(8192 * io_size) - io_size - 1);
+                        let content =
+                            &rand_content[rand_content_index..(rand_content_index + io_size)];
+
+                        let file = thread_rng().gen_range(0..fileset_size);
+                        // the file name is also the key its content is generated with
+                        let key = file.to_string();
+                        let mut file_name = path.clone();
+                        file_name.push(&key);
+                        let mut read_buffer = vec![0u8; io_size];
+                        match mmap_op(
+                            &op,
+                            fs.as_ref(),
+                            &mut mappings,
+                            &file_name,
+                            file_size,
+                            io_size,
+                            content,
+                            &mut read_buffer,
+                            &mut excluded_time,
+                        ) {
+                            Ok((mut timestamps, offset)) => {
+                                idx += timestamps.len() as u64;
+                                *ops.write()? += timestamps.len() as f64;
+                                behaviour.append(&mut timestamps);
+
+                                if let (Some(verifier), BenchFn::MmapRead) =
+                                    (verifier.as_ref(), &op)
+                                {
+                                    if let Some(mismatch) =
+                                        verifier.verify(&key, offset as u64, &read_buffer)?
+                                    {
+                                        mismatches.push(mismatch);
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                error!("error: {:?}", e);
+                                failures.add_io(&op_name, &e);
+                                failures.check(max_failures)?;
+                            }
+                        }
+                    }
+                    BenchFn::CreateWrite
+                    | BenchFn::CreateFallocate
+                    | BenchFn::CreateSparse
+                    | BenchFn::PunchHole
+                    | BenchFn::CopyFileRange
+                    | BenchFn::Reflink => {
+                        let rand_content_index =
+                            thread_rng().gen_range(0..(8192 * io_size) - io_size - 1);
+                        let content =
+                            &rand_content[rand_content_index..(rand_content_index + io_size)];
+                        match file_op(
+                            fs.as_ref(),
+                            &op,
+                            path,
+                            idx,
+                            fileset_size,
+                            file_size,
+                            content,
+                        ) {
+                            Ok(_) => {
+                                behaviour.push(op_timestamp(&mut excluded_time));
+                                *ops.write()? += 1.0;
+                                idx += 1;
+                            }
+                            Err(e) => {
+                                error!("error: {:?}", e);
+                                failures.add_io(&op_name, &e);
+                                failures.check(max_failures)?;
                             }
                         }
                     }
@@ -1,15 +1,28 @@
+use crate::clock::{self, Timestamp};
+#[cfg(target_os = "linux")]
+use crate::engine::uring::Uring;
+use crate::engine::IoEngine;
+use crate::failures::Failures;
 use crate::format::time_format;
 use crate::fs::Fs;
+use crate::interrupt;
 use crate::micro::clear_cache;
+use crate::mmap::Mmap;
+use crate::output::{self, outputln};
 use crate::plotter::{Indexes, Plotter};
 use crate::progress::Progress;
-use crate::{Bench, BenchFn, BenchResult, Config, Error, Record};
+use crate::results::RunResults;
+use crate::{Bench, BenchFn, BenchResult, Config, Error, Record, ResultMode};
 use byte_unit::{Byte, ByteUnit};
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::ProgressStyle;
+use log::error;
 use rand::RngCore;
-use std::io::{Read, Write};
+use std::io;
 use std::path::PathBuf;
-use std::time::SystemTime;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Barrier};
+use threadpool::ThreadPool;
 
 pub struct Throughput {
     config: Config,
@@ -17,16 +30,19 @@ pub struct Throughput {
 
 impl Bench for Throughput {
     fn new(config: Config) -> Result<Self, Error> {
+        output::set_quiet(config.quiet);
         Ok(Self { config })
     }
 
     fn setup(&self, path: &PathBuf, invalidate_cache: bool) -> Result<(), Error> {
-        if !path.exists() {
+        let fs = self.config.backend.as_ref();
+        if !fs.exists(path) {
             let style = ProgressStyle::default_bar().template("[{elapsed_precise}] {msg}");
-            let bar = ProgressBar::new_spinner();
+            let bar = output::spinner();
             bar.set_style(style);
             bar.set_message(format!("setting up {}", Fs::path_to_str(path)?));
             let progress = Progress::start(bar.clone());
+            fs.mkdir_all(path)?;
 
             let mut file_size = 1024 * 1024 * 64; // 64 MiB
             let mut idx = 0;
@@ -38,7 +54,7 @@ impl Bench for Throughput {
 
                 let mut file_name = path.clone();
                 file_name.push(idx.to_string());
-                Fs::make_file(&file_name)?.write_all(&mut rand_buffer)?;
+                fs.create(&file_name)?.write_all_at(&rand_buffer, 0)?;
 
                 file_size += 1024 * 1024 * 64;
                 idx += 1;
@@ -54,7 +70,7 @@ impl Bench for Throughput {
         Ok(())
     }
 
-    fn run(&self, _bench_fn: Option<BenchFn>) -> Result<(), Error> {
+    fn run(&self, _bench_fn: Option<BenchFn>) -> Result<RunResults, Error> {
         sudo::escalate_if_needed()?;
 
         let progress_style = ProgressStyle::default_bar().template("[{elapsed_precise}] {msg}");
@@ -65,84 +81,90 @@ impl Bench for Throughput {
         ]
         .to_vec();
 
-        let mut read_plotter = Plotter::new();
-        let mut write_plotter = Plotter::new();
+        // the selected benchmark functions that run in throughput mode
+        let ops = [
+            BenchFn::Read,
+            BenchFn::Write,
+            BenchFn::MmapRead,
+            BenchFn::MmapWrite,
+        ]
+        .into_iter()
+        .filter(|op| self.config.bench_fns.contains(op))
+        .collect::<Vec<_>>();
+        if ops.is_empty() {
+            return Err(Error::InvalidConfig(
+                "The throughput mode runs the read, write, mmap_read and mmap_write benchmark \
+                functions, but none of them is selected"
+                    .to_string(),
+            ));
+        }
+
+        let mut run_results = RunResults::new();
+        let mut plotters = ops
+            .iter()
+            .map(|_| Plotter::with_style(self.config.plot_style.clone()))
+            .collect::<Vec<_>>();
 
         for (idx, mount_path) in self.config.mount_paths.iter().enumerate() {
             let mut root_path = mount_path.clone();
             root_path.push("throughput");
             self.setup(&root_path, true)?;
 
-            let read_throughput = self.throughput(
-                BenchFn::Read,
-                &root_path,
-                &self.config.fs_names[idx],
-                progress_style.clone(),
-            )?;
-
-            let write_throughput = self.throughput(
-                BenchFn::Write,
-                &root_path,
-                &self.config.fs_names[idx],
-                progress_style.clone(),
-            )?;
-
-            let mut read_throughput_results = BenchResult::new(throughput_header.clone());
-            read_throughput_results.add_records(read_throughput.clone())?;
-            let mut file_name = self.config.log_path.clone();
-            file_name.push(format!("{}_read_throughput.csv", self.config.fs_names[idx]));
-            read_throughput_results.log(&file_name)?;
-
-            read_plotter.add_coordinates(
-                read_throughput,
-                Some(self.config.fs_names[idx].clone()),
-                Indexes::new(0, false, 1, None, None),
-            )?;
+            for (op, plotter) in ops.iter().zip(plotters.iter_mut()) {
+                let throughput = self.throughput(
+                    op.clone(),
+                    &root_path,
+                    &self.config.fs_names[idx],
+                    progress_style.clone(),
+                )?;
+
+                let mut throughput_results = BenchResult::new(throughput_header.clone());
+                throughput_results.add_records(throughput.clone())?;
+                let mut file_name = self.config.log_path.clone();
+                file_name.push(format!(
+                    "{}_{}_throughput.csv",
+                    self.config.fs_names[idx], op
+                ));
+                run_results.log(&throughput_results, &file_name, ResultMode::Throughput)?;
+
+                if !throughput.is_empty() {
+                    plotter.add_coordinates(
+                        throughput,
+                        Some(self.config.fs_names[idx].clone()),
+                        Indexes::new(0, false, 1, None, None),
+                    )?;
+                }
+            }
 
-            let mut write_throughput_results = BenchResult::new(throughput_header.clone());
-            write_throughput_results.add_records(write_throughput.clone())?;
-            let mut file_name = self.config.log_path.clone();
-            file_name.push(format!(
-                "{}_write_throughput.csv",
-                self.config.fs_names[idx]
-            ));
-            write_throughput_results.log(&file_name)?;
+            if interrupt::interrupted() {
+                if !self.config.keep_fileset {
+                    Fs::cleanup(self.config.backend.as_ref(), &root_path)?;
+                }
+                break;
+            }
+        }
 
-            write_plotter.add_coordinates(
-                write_throughput,
-                Some(self.config.fs_names[idx].clone()),
-                Indexes::new(0, false, 1, None, None),
-            )?;
+        for (op, plotter) in ops.iter().zip(plotters) {
+            if !plotter.is_empty() {
+                let mut file_name = self.config.log_path.clone();
+                file_name.push(format!("{}_throughput.svg", op));
+                plotter.line_chart(
+                    Some("File size (MiB)"),
+                    Some("Throughput (MiB/s)"),
+                    Some(&format!("{} Throughput", op.title())),
+                    true,
+                    true,
+                    &file_name,
+                )?;
+            }
         }
 
-        let mut file_name = self.config.log_path.clone();
-        file_name.push("read_throughput.svg");
-        read_plotter.line_chart(
-            Some("File size (MiB)"),
-            Some("Throughput (MiB/s)"),
-            Some("Read Throughput"),
-            true,
-            true,
-            &file_name,
-        )?;
-
-        let mut file_name = self.config.log_path.clone();
-        file_name.push("write_throughput.svg");
-        write_plotter.line_chart(
-            Some("File size (MiB)"),
-            Some("Throughput (MiB/s)"),
-            Some("Write Throughput"),
-            true,
-            true,
-            &file_name,
-        )?;
-
-        println!(
+        outputln!(
             "results logged to: {}",
             Fs::path_to_str(&self.config.log_path)?
         );
 
-        Ok(())
+        Ok(run_results)
     }
 }
 
@@ -154,33 +176,109 @@ impl Throughput {
         fs_name: &str,
         style: ProgressStyle,
     ) -> Result<Vec<Record>, Error> {
-        let bar = ProgressBar::new_spinner();
+        let bar = output::spinner();
         bar.set_style(style);
         bar.set_message(format!("{} throughput ({})", op.to_string(), fs_name));
         let progress = Progress::start(bar);
 
         let mut size = 1024 * 1024 * 64; // 64 MiB
         let mut throughputs = vec![];
+        let mut failures = Failures::new();
 
-        let start = SystemTime::now();
+        let start = Timestamp::now();
         // read 64 MiB, 128 MiB, 192 MiB, 256 MiB, 320 MiB,..., 1024 MiB
         let mut idx = 0;
-        while size <= 1024 * 1024 * 1024 {
+        // stop at the current size if interrupted, keeping the throughputs measured so far
+        while size <= 1024 * 1024 * 1024 && !interrupt::interrupted() {
             let mut file_name = path.clone();
             file_name.push(idx.to_string());
-            let mut file = Fs::open_file(&file_name)?;
+            // the file is read or written in io_size chunks, by io_uring or a pool of threads, unless
+            // it is accessed through a memory mapping
+            if (self.config.io_engine == IoEngine::IoUring || self.config.io_threads.is_some())
+                && !op.is_mmap()
+            {
+                let content = match op {
+                    BenchFn::Write => {
+                        let mut rand_content = vec![0u8; size];
+                        let mut rng = rand::thread_rng();
+                        rng.fill_bytes(&mut rand_content);
+                        Some(rand_content)
+                    }
+                    _ => None,
+                };
+                let throughput = match self.config.io_threads {
+                    Some(threads) => self.threaded_throughput(&file_name, size, content, threads),
+                    None => self.uring_throughput(&file_name, size, content.as_deref())?,
+                };
+                match throughput {
+                    Ok(throughput) => throughputs.push((size, throughput)),
+                    Err(e) => {
+                        error!("error: {:?}", e);
+                        failures.add_io(&op.to_string(), &e);
+                        failures.check(self.config.max_failures)?;
+                    }
+                }
+
+                idx += 1;
+                size += 1024 * 1024 * 64;
+                continue;
+            }
+
+            if op.is_mmap() {
+                // the read content is copied out of the mapping, and the written content into it
+                let mut buffer = vec![0u8; size];
+                if op == BenchFn::MmapWrite {
+                    let mut rng = rand::thread_rng();
+                    rng.fill_bytes(&mut buffer);
+                }
+
+                // the file is opened and mapped before the copy is timed
+                let throughput = self
+                    .config
+                    .backend
+                    .open(&file_name)
+                    .and_then(|mut file| file.map(size))
+                    .and_then(|mut mmap| {
+                        let start1 = Timestamp::now();
+                        mmap_throughput(&op, &mut mmap, &mut buffer)?;
+                        let end1 = start1
+                            .elapsed()
+                            .saturating_sub(clock::overhead())
+                            .as_secs_f64();
+                        Ok(size as f64 / end1) // B/s
+                    });
+                match throughput {
+                    Ok(throughput) => throughputs.push((size, throughput)),
+                    Err(e) => {
+                        error!("error: {:?}", e);
+                        failures.add_io(&op.to_string(), &e);
+                        failures.check(self.config.max_failures)?;
+                    }
+                }
+
+                idx += 1;
+                size += 1024 * 1024 * 64;
+                continue;
+            }
+
+            let mut file = self.config.backend.open(&file_name)?;
             match op {
                 BenchFn::Read => {
                     let mut read_buffer = vec![0u8; size];
-                    let start1 = SystemTime::now();
-                    match file.read_exact(&mut read_buffer) {
+                    let start1 = Timestamp::now();
+                    match file.read_exact_at(&mut read_buffer, 0) {
                         Ok(_) => {
-                            let end1 = start1.elapsed()?.as_secs_f64();
+                            let end1 = start1
+                                .elapsed()
+                                .saturating_sub(clock::overhead())
+                                .as_secs_f64();
                             let throughput = size as f64 / end1; // B/s
                             throughputs.push((size, throughput));
                         }
                         Err(e) => {
-                            println!("error: {:?}", e);
+                            error!("error: {:?}", e);
+                            failures.add_io(&op.to_string(), &e);
+                            failures.check(self.config.max_failures)?;
                         }
                     }
                 }
@@ -189,15 +287,20 @@ impl Throughput {
                     let mut rng = rand::thread_rng();
                     rng.fill_bytes(&mut rand_content);
 
-                    let start1 = SystemTime::now();
-                    match file.write_all(&mut rand_content) {
+                    let start1 = Timestamp::now();
+                    match file.write_all_at(&rand_content, 0) {
                         Ok(_) => {
-                            let end1 = start1.elapsed()?.as_secs_f64();
+                            let end1 = start1
+                                .elapsed()
+                                .saturating_sub(clock::overhead())
+                                .as_secs_f64();
                             let throughput = size as f64 / end1; // B/s
                             throughputs.push((size, throughput));
                         }
                         Err(e) => {
-                            println!("error: {:?}", e);
+                            error!("error: {:?}", e);
+                            failures.add_io(&op.to_string(), &e);
+                            failures.check(self.config.max_failures)?;
                         }
                     }
                 }
@@ -208,10 +311,27 @@ impl Throughput {
             size += 1024 * 1024 * 64;
         }
 
-        let end = start.elapsed()?.as_secs_f64();
+        let end = start.elapsed().as_secs_f64();
         progress.finish()?;
 
-        println!("{:11} {}", "run time:", time_format(end));
+        outputln!("{:11} {}", "run time:", time_format(end));
+        outputln!(
+            "{:11} {}",
+            "timer overhead:",
+            time_format(clock::overhead().as_secs_f64())
+        );
+        if failures.count() > 0 {
+            outputln!("{:11} {}", "failed ops:", failures.count());
+            failures.print();
+
+            let mut file_name = self.config.log_path.clone();
+            file_name.push(format!(
+                "{}_{}_throughput_failures.csv",
+                fs_name,
+                op.to_string()
+            ));
+            failures.log(&file_name)?;
+        }
 
         let mut throughput_records = vec![];
         for (size, throughput) in throughputs {
@@ -220,7 +340,7 @@ impl Throughput {
 
             let throughput = Byte::from_bytes(throughput as u128);
             let adjusted_throughput = throughput.get_appropriate_unit(true);
-            println!(
+            outputln!(
                 "[{:10} {}/s]",
                 adjusted_size.format(0),
                 adjusted_throughput.format(3),
@@ -243,7 +363,138 @@ impl Throughput {
             );
         }
 
-        println!();
+        outputln!();
         Ok(throughput_records)
     }
+
+    // read the file, or write the content to it, in io_size chunks issued by a pool of threads,
+    // each with its own open file, and return the throughput in B/s, or the first failed chunk's
+    // error
+    fn threaded_throughput(
+        &self,
+        file_name: &PathBuf,
+        size: usize,
+        content: Option<Vec<u8>>,
+        threads: usize,
+    ) -> io::Result<f64> {
+        let io_size = self.config.io_size;
+        let content = content.map(Arc::new);
+        let next_offset = Arc::new(AtomicUsize::new(0));
+        // the threads open their files before the barrier, so only the reads and writes are timed
+        let barrier = Arc::new(Barrier::new(threads + 1));
+        let (sender, receiver) = channel();
+        let pool = ThreadPool::new(threads);
+        for _ in 0..threads {
+            let fs = self.config.backend.clone();
+            let file_name = file_name.clone();
+            let content = content.clone();
+            let next_offset = next_offset.clone();
+            let barrier = barrier.clone();
+            let sender = sender.clone();
+            pool.execute(move || {
+                let file = fs.open(&file_name);
+                barrier.wait();
+                let result = file.and_then(|mut file| {
+                    let mut read_buffer = vec![0u8; io_size];
+                    // take the next chunk until the whole file is read or written
+                    loop {
+                        let offset = next_offset.fetch_add(io_size, Ordering::Relaxed);
+                        if offset >= size {
+                            return Ok(());
+                        }
+                        let len = io_size.min(size - offset);
+                        match content.as_ref() {
+                            Some(content) => {
+                                file.write_all_at(&content[offset..offset + len], offset as u64)?
+                            }
+                            None => file.read_exact_at(&mut read_buffer[..len], offset as u64)?,
+                        }
+                    }
+                });
+                sender.send(result).unwrap();
+            });
+        }
+
+        barrier.wait();
+        let start = Timestamp::now();
+        let results = receiver.iter().take(threads).collect::<Vec<_>>();
+        let end = start
+            .elapsed()
+            .saturating_sub(clock::overhead())
+            .as_secs_f64();
+        for result in results {
+            result?;
+        }
+
+        Ok(size as f64 / end) // B/s
+    }
+
+    // read the file, or write the content to it, in io_size operations run by io_uring, and
+    // return the throughput in B/s, or the first failed operation's error
+    #[cfg(target_os = "linux")]
+    fn uring_throughput(
+        &self,
+        file_name: &PathBuf,
+        size: usize,
+        content: Option<&[u8]>,
+    ) -> Result<io::Result<f64>, Error> {
+        let file = self.config.backend.open(file_name)?.try_clone_file()?;
+        let io_size = self.config.io_size;
+        let mut uring = Uring::new(self.config.uring, vec![file], io_size)?;
+
+        let mut offset = 0;
+        let start = Timestamp::now();
+        while offset < size || uring.in_flight() > 0 {
+            while offset < size && !uring.is_full() {
+                let len = io_size.min(size - offset);
+                match content {
+                    Some(content) => {
+                        uring.push_write(0, offset as u64, &content[offset..offset + len], false)?
+                    }
+                    None => uring.push_read(0, offset as u64, len)?,
+                };
+                offset += len;
+            }
+
+            for completion in uring.complete(1)? {
+                if let Err(e) = completion.result {
+                    uring.drain()?;
+                    return Ok(Err(e));
+                }
+            }
+        }
+        let end = start
+            .elapsed()
+            .saturating_sub(clock::overhead())
+            .as_secs_f64();
+
+        Ok(Ok(size as f64 / end)) // B/s
+    }
+
+    // the io_uring engine is rejected by the configuration on other platforms
+    #[cfg(not(target_os = "linux"))]
+    fn uring_throughput(
+        &self,
+        _file_name: &PathBuf,
+        _size: usize,
+        _content: Option<&[u8]>,
+    ) -> Result<io::Result<f64>, Error> {
+        Err(Error::InvalidConfig(
+            "The io_uring io engine is only supported on Linux".to_string(),
+        ))
+    }
+}
+
+// copy the content of the mapping to the buffer for an mmap read, or the buffer to it and msync it
+// for an mmap write
+fn mmap_throughput(op: &BenchFn, mmap: &mut Mmap, buffer: &mut [u8]) -> io::Result<()> {
+    match op {
+        BenchFn::MmapWrite => {
+            mmap.as_mut_slice().copy_from_slice(buffer);
+            mmap.sync(0, buffer.len())?;
+        }
+        _ => buffer.copy_from_slice(mmap.as_slice()),
+    }
+
+    Ok(())
 }
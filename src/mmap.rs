@@ -0,0 +1,146 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::ptr;
+
+///
+/// A shared read-write memory mapping of a file, unmapped when dropped. The pages of the mapping
+/// are only faulted in when they are first touched.
+///
+pub struct Mmap {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// the mapping is owned by one thread at a time, like the file it maps
+unsafe impl Send for Mmap {}
+
+impl Mmap {
+    ///
+    /// Map the first len bytes of the file, which should be at least len bytes long
+    ///
+    pub fn map(file: &File, len: usize) -> io::Result<Self> {
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot map zero bytes",
+            ));
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+
+    ///
+    /// Read the byte at the offset, faulting its page in if it is not mapped yet. The read is
+    /// volatile, so it is not optimised away.
+    ///
+    pub fn touch(&self, offset: usize) -> u8 {
+        assert!(offset < self.len, "touching past the end of the mapping");
+        unsafe { ptr::read_volatile(self.ptr.add(offset)) }
+    }
+
+    ///
+    /// Write the modified pages of the range back to the file and wait for them, like msync
+    /// with MS_SYNC
+    ///
+    pub fn sync(&self, offset: usize, len: usize) -> io::Result<()> {
+        // msync takes a page aligned address
+        let start = offset - offset % page_size();
+        let result = unsafe {
+            libc::msync(
+                self.ptr.add(start) as *mut libc::c_void,
+                len + offset - start,
+                libc::MS_SYNC,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len) };
+    }
+}
+
+///
+/// The size of the memory pages, that the page faults are counted in
+///
+pub fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::os::unix::fs::FileExt;
+
+    #[test]
+    fn mmap_test() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("fs-bench-mmap-{}", std::process::id()));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        let len = 3 * page_size();
+        file.write_all_at(&vec![7u8; len], 0)?;
+
+        let mut mmap = Mmap::map(&file, len)?;
+        assert_eq!(mmap.len(), len);
+        assert_eq!(mmap.touch(page_size() + 1), 7);
+
+        // the writes to the mapping reach the file
+        mmap.as_mut_slice()[page_size() + 10..page_size() + 20].copy_from_slice(&[1u8; 10]);
+        mmap.sync(page_size() + 10, 10)?;
+        let mut content = vec![0u8; 10];
+        file.read_exact_at(&mut content, page_size() as u64 + 10)?;
+        assert_eq!(content, vec![1u8; 10]);
+        assert_eq!(mmap.as_slice()[page_size() + 9], 7);
+
+        assert!(Mmap::map(&file, 0).is_err());
+        drop(mmap);
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+}
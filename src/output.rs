@@ -0,0 +1,41 @@
+use indicatif::{ProgressBar, ProgressDrawTarget};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// set when the progress bars and printing of the benchmarks are suppressed
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+// print a line to stdout, unless the benchmarks are quiet
+macro_rules! outputln {
+    ($($arg:tt)*) => {
+        if !$crate::output::quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use outputln;
+
+///
+/// Suppress the progress bars and printing of the benchmarks, e.g. when they are run from a test
+/// harness, or enable them again
+///
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::SeqCst);
+}
+
+///
+/// Whether the progress bars and printing of the benchmarks are suppressed
+///
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::SeqCst)
+}
+
+// a spinner, hidden if the benchmarks are quiet
+pub(crate) fn spinner() -> ProgressBar {
+    let spinner = ProgressBar::new_spinner();
+    if quiet() {
+        spinner.set_draw_target(ProgressDrawTarget::hidden());
+    }
+
+    spinner
+}
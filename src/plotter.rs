@@ -1,11 +1,262 @@
 use crate::error::Error;
 use crate::Record;
 use plotters::prelude::*;
+use std::fmt::{Display, Formatter};
 use std::ops::Range;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+// the number of points a CDF is drawn with, and the time and value bins of a heatmap
+const CDF_POINTS: usize = 1000;
+const HEATMAP_TIME_BINS: usize = 60;
+const HEATMAP_VALUE_BINS: usize = 40;
+
+// draw a chart on the drawing area of the backend of the output format of the file, with the
+// drawing area named by $root in the body
+macro_rules! draw_on {
+    ($style:expr, $file_name:expr, |$root:ident| $body:block) => {{
+        let (path, format) = $style.output(Path::new($file_name));
+        let size = ($style.width, $style.height);
+        match format {
+            PlotFormat::Svg => {
+                let $root = SVGBackend::new(&path, size).into_drawing_area();
+                $body
+            }
+            PlotFormat::Png => {
+                let $root = BitMapBackend::new(&path, size).into_drawing_area();
+                $body
+            }
+            PlotFormat::Pdf => {
+                // plotters has no PDF backend, so the chart is drawn to SVG and then converted
+                let svg_path = path.with_extension("pdf.svg");
+                {
+                    let $root = SVGBackend::new(&svg_path, size).into_drawing_area();
+                    $body
+                }
+                svg_to_pdf(&svg_path, &path)?;
+            }
+        }
+    }};
+}
+
+// build the y axis range as linear or logarithmic, with the range named by $range in the body
+macro_rules! with_y_range {
+    ($log_scale:expr, $start:expr, $end:expr, |$range:ident| $body:block) => {
+        if $log_scale {
+            let $range = ($start..$end).log_scale();
+            $body
+        } else {
+            let $range = $start..$end;
+            $body
+        }
+    };
+}
+
+///
+/// The file formats of the plots
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlotFormat {
+    Svg,
+    Png,
+    /// converted from SVG with rsvg-convert, which must be installed
+    Pdf,
+}
+
+impl PlotFormat {
+    pub fn extension(&self) -> &str {
+        match self {
+            PlotFormat::Svg => "svg",
+            PlotFormat::Png => "png",
+            PlotFormat::Pdf => "pdf",
+        }
+    }
+
+    ///
+    /// Whether the plots can be written in this format on this machine
+    ///
+    pub fn is_supported(&self) -> bool {
+        match self {
+            PlotFormat::Pdf => Command::new("rsvg-convert")
+                .arg("--version")
+                .output()
+                .map(|output| output.status.success())
+                .unwrap_or(false),
+            _ => true,
+        }
+    }
+}
+
+impl FromStr for PlotFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "svg" => Ok(PlotFormat::Svg),
+            "png" => Ok(PlotFormat::Png),
+            "pdf" => Ok(PlotFormat::Pdf),
+            _ => Err("valid plot formats are: svg, png, pdf".to_string()),
+        }
+    }
+}
+
+impl Display for PlotFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+///
+/// The colour schemes of the plotted series
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorScheme {
+    Default,
+    /// the Okabe-Ito colours, that can be told apart with the common colour blindnesses
+    Colorblind,
+    /// shades of grey, for printing
+    Grayscale,
+}
+
+impl ColorScheme {
+    ///
+    /// The colour of the series with the index
+    ///
+    pub fn color(&self, idx: usize) -> RGBColor {
+        match self {
+            ColorScheme::Default => {
+                let (r, g, b) = Palette99::pick(idx).rgb();
+                RGBColor(r, g, b)
+            }
+            ColorScheme::Colorblind => {
+                let colors = [
+                    RGBColor(230, 159, 0),
+                    RGBColor(86, 180, 233),
+                    RGBColor(0, 158, 115),
+                    RGBColor(0, 114, 178),
+                    RGBColor(213, 94, 0),
+                    RGBColor(204, 121, 167),
+                    RGBColor(240, 228, 66),
+                    RGBColor(0, 0, 0),
+                ];
+                colors[idx % colors.len()]
+            }
+            ColorScheme::Grayscale => {
+                let shades = [0, 100, 160, 60, 130, 190];
+                let shade = shades[idx % shades.len()];
+                RGBColor(shade, shade, shade)
+            }
+        }
+    }
+}
+
+impl FromStr for ColorScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(ColorScheme::Default),
+            "colorblind" => Ok(ColorScheme::Colorblind),
+            "grayscale" => Ok(ColorScheme::Grayscale),
+            _ => Err("valid colour schemes are: default, colorblind, grayscale".to_string()),
+        }
+    }
+}
+
+impl Display for ColorScheme {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorScheme::Default => write!(f, "default"),
+            ColorScheme::Colorblind => write!(f, "colorblind"),
+            ColorScheme::Grayscale => write!(f, "grayscale"),
+        }
+    }
+}
+
+///
+/// The output format, size, fonts, scale and colours of the plots
+///
+#[derive(Debug, Clone)]
+pub struct PlotStyle {
+    pub format: Option<PlotFormat>, // the file format, chosen by the file extension if not set
+    pub width: u32,
+    pub height: u32,
+    pub font_size: f64, // the font size of the axis descriptions, which the other fonts scale with
+    pub log_scale: bool, // whether the y axis of the line, bar and point charts is logarithmic
+    pub color_scheme: ColorScheme,
+}
+
+impl PlotStyle {
+    pub fn new() -> Self {
+        Self {
+            format: None,
+            width: 800,
+            height: 500,
+            font_size: 20.0,
+            log_scale: false,
+            color_scheme: ColorScheme::Default,
+        }
+    }
+
+    fn caption_font(&self) -> (&str, f64) {
+        ("sans-serif", self.font_size * 1.5)
+    }
+
+    fn desc_font(&self) -> (&str, f64) {
+        ("sans-serif", self.font_size)
+    }
+
+    fn label_font(&self) -> (&str, f64) {
+        ("sans-serif", self.font_size * 0.6)
+    }
+
+    // the path and format of a plot file, with the extension of the format if it is set
+    fn output(&self, file_name: &Path) -> (PathBuf, PlotFormat) {
+        if let Some(format) = self.format {
+            return (file_name.with_extension(format.extension()), format);
+        }
+
+        let format = file_name
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.parse::<PlotFormat>().ok())
+            .unwrap_or(PlotFormat::Svg);
+        (file_name.to_path_buf(), format)
+    }
+
+    // the y axis range of values between the min and max, where the min positive value is the
+    // start of a logarithmic axis
+    fn y_range(&self, min: f64, max: f64, min_positive: f64) -> Result<(f64, f64), Error> {
+        if self.log_scale {
+            if !min_positive.is_finite() {
+                return Err(Error::format("plot", "no positive values for a log scale"));
+            }
+            Ok((min_positive / 1.25, max.max(min_positive) * 1.25))
+        } else {
+            // y starts bellow the first y-axis value, and ends after the last y-axis value
+            Ok((min - (min / 5.0), max + (max / 5.0)))
+        }
+    }
+}
+
+impl Default for PlotStyle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 pub struct Plotter {
     coordinates: Vec<Coordinates>,
+    distributions: Vec<Distribution>,
+    style: PlotStyle,
+}
+
+// the sample values of a distribution, e.g. the latencies of an operation
+struct Distribution {
+    values: Vec<f64>,
+    times: Option<Vec<f64>>, // the time of each value, for the heatmaps
+    label: Option<String>,
 }
 
 struct Coordinates {
@@ -84,11 +335,41 @@ impl Indexes {
 
 impl Plotter {
     pub fn new() -> Self {
+        Self::with_style(PlotStyle::new())
+    }
+
+    pub fn with_style(style: PlotStyle) -> Self {
         Self {
             coordinates: vec![],
+            distributions: vec![],
+            style,
         }
     }
 
+    ///
+    /// Whether any coordinates or distributions have been added to be plotted
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.coordinates.is_empty() && self.distributions.is_empty()
+    }
+
+    ///
+    /// Add the sample values of a distribution to be plotted by the CDF, box plot and heatmap
+    /// charts. The times of the values are only needed for the heatmaps.
+    ///
+    pub fn add_distribution(
+        &mut self,
+        values: Vec<f64>,
+        times: Option<Vec<f64>>,
+        label: Option<String>,
+    ) {
+        self.distributions.push(Distribution {
+            values,
+            times,
+            label,
+        });
+    }
+
     pub fn add_coordinates(
         &mut self,
         records: Vec<Record>,
@@ -143,6 +424,7 @@ impl Plotter {
         // find the min and max values among the coordinates
         let mut y_min = f64::INFINITY;
         let mut y_max = f64::NEG_INFINITY;
+        let mut y_min_positive = f64::INFINITY; // the start of a log scale
         let mut x_max = f64::NEG_INFINITY;
         let mut max_x_axis = vec![]; // the x_axis values that contains the max x value
         for coordinate in self.coordinates.iter() {
@@ -167,164 +449,186 @@ impl Plotter {
             if min < y_min {
                 y_min = min;
             }
+            y_min_positive = y_axis
+                .iter()
+                .filter(|y| **y > 0f64)
+                .fold(y_min_positive, |a, b| a.min(*b));
             if max > y_max {
                 y_max = max;
             }
         }
 
-        let y_start = y_min - (y_min / 5.0); // y starts bellow the first y-axis value
-        let y_end = y_max + (y_max / 5.0); // and ends after the last y-axis value
-
-        let root_area = SVGBackend::new(file_name, (800, 500)).into_drawing_area();
-        root_area.fill(&WHITE)?;
-
-        if fixed_ticks {
-            let ticks = max_x_axis.iter().map(|x| *x as i64).collect::<Vec<_>>();
-
-            let mut ctx = ChartBuilder::on(&root_area)
-                .set_label_area_size(LabelAreaPosition::Left, 100.0)
-                .set_label_area_size(LabelAreaPosition::Bottom, 50.0)
-                .margin(30.0)
-                .caption(caption.unwrap_or(""), ("sans-serif", 30.0))
-                .build_cartesian_2d(
-                    (ticks[0]..ticks[ticks.len() - 1]).with_key_points(ticks),
-                    y_start..y_end,
-                )?;
+        let (y_start, y_end) = self.style.y_range(y_min, y_max, y_min_positive)?;
+        // the values that can not be shown on a log scale are drawn at its start
+        let y_floor = if self.style.log_scale {
+            y_start
+        } else {
+            f64::NEG_INFINITY
+        };
 
-            ctx.configure_mesh()
-                .axis_desc_style(("sans-serif", 20.0))
-                .x_desc(x_label.unwrap_or(""))
-                .x_label_formatter(&|x| {
-                    if *x >= 1000 {
-                        format!("{:e}", x)
-                    } else {
-                        x.to_string()
+        draw_on!(self.style, file_name, |root_area| {
+            root_area.fill(&WHITE)?;
+
+            with_y_range!(self.style.log_scale, y_start, y_end, |y_range| {
+                if fixed_ticks {
+                    let ticks = max_x_axis.iter().map(|x| *x as i64).collect::<Vec<_>>();
+
+                    let mut ctx = ChartBuilder::on(&root_area)
+                        .set_label_area_size(LabelAreaPosition::Left, 100.0)
+                        .set_label_area_size(LabelAreaPosition::Bottom, 50.0)
+                        .margin(30.0)
+                        .caption(caption.unwrap_or(""), self.style.caption_font())
+                        .build_cartesian_2d(
+                            (ticks[0]..ticks[ticks.len() - 1]).with_key_points(ticks),
+                            y_range,
+                        )?;
+
+                    ctx.configure_mesh()
+                        .label_style(self.style.label_font())
+                        .axis_desc_style(self.style.desc_font())
+                        .x_desc(x_label.unwrap_or(""))
+                        .x_label_formatter(&|x| {
+                            if *x >= 1000 {
+                                format!("{:e}", x)
+                            } else {
+                                x.to_string()
+                            }
+                        })
+                        .y_desc(y_label.unwrap_or(""))
+                        .y_label_formatter(&|y| {
+                            if *y >= 1000.0 {
+                                format!("{:e}", y)
+                            } else {
+                                y.to_string()
+                            }
+                        })
+                        .draw()?;
+
+                    // plot the coordinates
+                    let mut has_legend = false;
+                    let mut colors = (0..).map(|idx| self.style.color_scheme.color(idx));
+
+                    for coordinate in self.coordinates.iter() {
+                        let x_axis = coordinate
+                            .x_axis
+                            .iter()
+                            .map(|x| x.get_float())
+                            .collect::<Result<Vec<f64>, Error>>()?;
+                        let x_axis = x_axis.iter().map(|x| *x as i64).collect::<Vec<_>>();
+                        let y_axis = coordinate.y_axis.clone();
+
+                        let color = colors.next().unwrap();
+                        let series = ctx.draw_series(LineSeries::new(
+                            x_axis
+                                .iter()
+                                .zip(y_axis.iter())
+                                .map(|(x, y_axis)| (*x, y_axis.y.max(y_floor))), // The data iter
+                            &color,
+                        ))?;
+                        if let Some(label) = coordinate.label.clone() {
+                            series.label(label).legend(move |(x, y)| {
+                                PathElement::new(vec![(x, y), (x + 20, y)], color)
+                            });
+                            has_legend = true;
+                        }
+
+                        if points {
+                            ctx.draw_series(x_axis.iter().zip(coordinate.y_axis.iter()).map(
+                                |(x, y_axis)| {
+                                    Circle::new(
+                                        (*x, y_axis.y.max(y_floor)),
+                                        3,
+                                        ShapeStyle::from(&BLACK).filled(),
+                                    )
+                                },
+                            ))?;
+                        }
                     }
-                })
-                .y_desc(y_label.unwrap_or(""))
-                .y_label_formatter(&|y| {
-                    if *y >= 1000.0 {
-                        format!("{:e}", y)
-                    } else {
-                        y.to_string()
-                    }
-                })
-                .draw()?;
 
-            // plot the coordinates
-            let mut has_legend = false;
-            let mut colors = (0..).map(Palette99::pick);
-
-            for coordinate in self.coordinates.iter() {
-                let x_axis = coordinate
-                    .x_axis
-                    .iter()
-                    .map(|x| x.get_float())
-                    .collect::<Result<Vec<f64>, Error>>()?;
-                let x_axis = x_axis.iter().map(|x| *x as i64).collect::<Vec<_>>();
-                let y_axis = coordinate.y_axis.clone();
-
-                let color = colors.next().unwrap();
-                let series = ctx.draw_series(LineSeries::new(
-                    x_axis
-                        .iter()
-                        .zip(y_axis.iter())
-                        .map(|(x, y_axis)| (*x, y_axis.y)), // The data iter
-                    &color,
-                ))?;
-                if let Some(label) = coordinate.label.clone() {
-                    series
-                        .label(label)
-                        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &color));
-                    has_legend = true;
-                }
-
-                if points {
-                    ctx.draw_series(x_axis.iter().zip(coordinate.y_axis.iter()).map(
-                        |(x, y_axis)| {
-                            Circle::new((*x, y_axis.y), 3, ShapeStyle::from(&BLACK).filled())
-                        },
-                    ))?;
-                }
-            }
-
-            if has_legend {
-                // draw the legend
-                ctx.configure_series_labels().border_style(&BLACK).draw()?;
-            }
-        } else {
-            let mut ctx = ChartBuilder::on(&root_area)
-                .set_label_area_size(LabelAreaPosition::Left, 100.0)
-                .set_label_area_size(LabelAreaPosition::Bottom, 50.0)
-                .margin(30.0)
-                .caption(caption.unwrap_or(""), ("sans-serif", 30.0))
-                .build_cartesian_2d(0.0..x_max, y_start..y_end)?;
-
-            ctx.configure_mesh()
-                .axis_desc_style(("sans-serif", 20.0))
-                .x_desc(x_label.unwrap_or(""))
-                .x_label_formatter(&|x| {
-                    if *x >= 1000.0 {
-                        format!("{:e}", x)
-                    } else {
-                        x.to_string()
+                    if has_legend {
+                        // draw the legend
+                        ctx.configure_series_labels().border_style(&BLACK).draw()?;
                     }
-                })
-                .y_desc(y_label.unwrap_or(""))
-                .y_label_formatter(&|y| {
-                    if *y >= 1000.0 {
-                        format!("{:e}", y)
-                    } else {
-                        y.to_string()
+                } else {
+                    let mut ctx = ChartBuilder::on(&root_area)
+                        .set_label_area_size(LabelAreaPosition::Left, 100.0)
+                        .set_label_area_size(LabelAreaPosition::Bottom, 50.0)
+                        .margin(30.0)
+                        .caption(caption.unwrap_or(""), self.style.caption_font())
+                        .build_cartesian_2d(0.0..x_max, y_range)?;
+
+                    ctx.configure_mesh()
+                        .label_style(self.style.label_font())
+                        .axis_desc_style(self.style.desc_font())
+                        .x_desc(x_label.unwrap_or(""))
+                        .x_label_formatter(&|x| {
+                            if *x >= 1000.0 {
+                                format!("{:e}", x)
+                            } else {
+                                x.to_string()
+                            }
+                        })
+                        .y_desc(y_label.unwrap_or(""))
+                        .y_label_formatter(&|y| {
+                            if *y >= 1000.0 {
+                                format!("{:e}", y)
+                            } else {
+                                y.to_string()
+                            }
+                        })
+                        .draw()?;
+
+                    // plot the coordinates
+                    let mut has_legend = false;
+                    let mut colors = (0..).map(|idx| self.style.color_scheme.color(idx));
+
+                    for coordinate in self.coordinates.iter() {
+                        let x_axis = coordinate
+                            .x_axis
+                            .iter()
+                            .map(|x| x.get_float())
+                            .collect::<Result<Vec<f64>, Error>>()?;
+
+                        let y_axis = coordinate.y_axis.clone();
+
+                        let color = colors.next().unwrap();
+                        let series = ctx.draw_series(LineSeries::new(
+                            x_axis
+                                .iter()
+                                .zip(y_axis.iter())
+                                .map(|(x, y_axis)| (*x, y_axis.y.max(y_floor))),
+                            &color,
+                        ))?;
+                        if let Some(label) = coordinate.label.clone() {
+                            series.label(label).legend(move |(x, y)| {
+                                PathElement::new(vec![(x, y), (x + 20, y)], color)
+                            });
+                            has_legend = true;
+                        }
+
+                        if points {
+                            ctx.draw_series(x_axis.iter().zip(coordinate.y_axis.iter()).map(
+                                |(x, y_axis)| {
+                                    Circle::new(
+                                        (*x, y_axis.y.max(y_floor)),
+                                        3,
+                                        ShapeStyle::from(&BLACK).filled(),
+                                    )
+                                },
+                            ))?;
+                        }
                     }
-                })
-                .draw()?;
-
-            // plot the coordinates
-            let mut has_legend = false;
-            let mut colors = (0..).map(Palette99::pick);
-
-            for coordinate in self.coordinates.iter() {
-                let x_axis = coordinate
-                    .x_axis
-                    .iter()
-                    .map(|x| x.get_float())
-                    .collect::<Result<Vec<f64>, Error>>()?;
-
-                let y_axis = coordinate.y_axis.clone();
-
-                let color = colors.next().unwrap();
-                let series = ctx.draw_series(LineSeries::new(
-                    x_axis
-                        .iter()
-                        .zip(y_axis.iter())
-                        .map(|(x, y_axis)| (*x, y_axis.y)),
-                    &color,
-                ))?;
-                if let Some(label) = coordinate.label.clone() {
-                    series
-                        .label(label)
-                        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &color));
-                    has_legend = true;
-                }
 
-                if points {
-                    ctx.draw_series(x_axis.iter().zip(coordinate.y_axis.iter()).map(
-                        |(x, y_axis)| {
-                            Circle::new((*x, y_axis.y), 3, ShapeStyle::from(&BLACK).filled())
-                        },
-                    ))?;
+                    if has_legend {
+                        // draw the legend
+                        ctx.configure_series_labels().border_style(&BLACK).draw()?;
+                    }
                 }
-            }
-
-            if has_legend {
-                // draw the legend
-                ctx.configure_series_labels().border_style(&BLACK).draw()?;
-            }
-        };
+            });
 
-        // to avoid the IO failure being ignored silently, we manually call the present function
-        root_area.present()?;
+            // to avoid the IO failure being ignored silently, we manually call the present function
+            root_area.present()?;
+        });
 
         Ok(())
     }
@@ -336,9 +640,6 @@ impl Plotter {
         caption: Option<&str>,
         file_name: &P,
     ) -> Result<(), Error> {
-        let root_area = SVGBackend::new(file_name, (800, 500)).into_drawing_area();
-        root_area.fill(&WHITE)?;
-
         // for the bar chart, we need the string values of the x axis
         let x_axis = self.coordinates[0]
             .x_axis
@@ -357,80 +658,112 @@ impl Plotter {
             .iter()
             .map(|y_axis| y_axis.y)
             .fold(f64::NEG_INFINITY, |a, b| a.max(b));
-        let y_start = y_min - (y_min / 5.0); // y starts bellow the first y-axis value
-        let y_end = y_max + (y_max / 5.0); // and ends after the last y-axis value
-
-        let mut ctx = ChartBuilder::on(&root_area)
-            .set_label_area_size(LabelAreaPosition::Left, 100.0)
-            .set_label_area_size(LabelAreaPosition::Bottom, 50.0)
-            .caption(caption.unwrap_or(""), ("sans-serif", 30.0))
-            .margin(5.0)
-            .build_cartesian_2d(custom_x_axes.clone(), y_start..y_end)?;
-
-        ctx.configure_mesh()
-            .axis_desc_style(("sans-serif", 20.0))
-            .x_desc(x_label.unwrap_or(""))
-            .y_desc(y_label.unwrap_or(""))
-            .y_label_formatter(&|y| {
-                if *y >= 1000.0 {
-                    format!("{:e}", y)
-                } else {
-                    y.to_string()
-                }
-            })
-            .draw()?;
+        let y_min_positive = self.coordinates[0]
+            .y_axis
+            .iter()
+            .map(|y_axis| y_axis.y)
+            .filter(|y| *y > 0f64)
+            .fold(f64::INFINITY, |a, b| a.min(b));
+        let (y_start, y_end) = self.style.y_range(y_min, y_max, y_min_positive)?;
+        // the bars start from zero, or from the start of a log scale
+        let y_base = if self.style.log_scale { y_start } else { 0f64 };
+        let color = self.style.color_scheme.color(0);
+
+        draw_on!(self.style, file_name, |root_area| {
+            root_area.fill(&WHITE)?;
+
+            with_y_range!(self.style.log_scale, y_start, y_end, |y_range| {
+                let mut ctx = ChartBuilder::on(&root_area)
+                    .set_label_area_size(LabelAreaPosition::Left, 100.0)
+                    .set_label_area_size(LabelAreaPosition::Bottom, 50.0)
+                    .caption(caption.unwrap_or(""), self.style.caption_font())
+                    .margin(5.0)
+                    .build_cartesian_2d(custom_x_axes.clone(), y_range)?;
+
+                ctx.configure_mesh()
+                    .label_style(self.style.label_font())
+                    .axis_desc_style(self.style.desc_font())
+                    .x_desc(x_label.unwrap_or(""))
+                    .y_desc(y_label.unwrap_or(""))
+                    .y_label_formatter(&|y| {
+                        if *y >= 1000.0 {
+                            format!("{:e}", y)
+                        } else {
+                            y.to_string()
+                        }
+                    })
+                    .draw()?;
+
+                // draw the bars
+                ctx.draw_series(
+                    custom_x_axes
+                        .ticks
+                        .iter()
+                        .zip(self.coordinates[0].y_axis.iter())
+                        .map(|(x, y_axis)| {
+                            let x_before = format!("{}_before", x);
+                            let x_after = format!("{}_after", x);
+                            Rectangle::new(
+                                [(x_before, y_base), (x_after, y_axis.y.max(y_base))],
+                                color.filled(),
+                            )
+                        }),
+                )?;
 
-        // draw the bars
-        ctx.draw_series(
-            custom_x_axes
-                .ticks
-                .iter()
-                .zip(self.coordinates[0].y_axis.iter())
-                .map(|(x, y_axis)| {
-                    let x_before = format!("{}_before", x);
-                    let x_after = format!("{}_after", x);
-                    Rectangle::new([(x_before, 0.0), (x_after, y_axis.y)], RED.filled())
-                }),
-        )?;
-
-        // draw the error bars
-        ctx.draw_series(
-            custom_x_axes
-                .ticks
-                .iter()
-                .zip(self.coordinates[0].y_axis.iter())
-                .map(|(x, y_axis)| {
-                    if let (Some(lb), Some(ub)) = (y_axis.lb, y_axis.ub) {
-                        ErrorBar::new_vertical(x.clone(), lb, y_axis.y, ub, BLACK.filled(), 10)
-                    } else {
-                        ErrorBar::new_vertical(x.clone(), 0f64, 0f64, 0f64, RED.filled(), 0)
-                    }
-                }),
-        )?;
+                // draw the error bars
+                ctx.draw_series(
+                    custom_x_axes
+                        .ticks
+                        .iter()
+                        .zip(self.coordinates[0].y_axis.iter())
+                        .map(|(x, y_axis)| {
+                            if let (Some(lb), Some(ub)) = (y_axis.lb, y_axis.ub) {
+                                ErrorBar::new_vertical(
+                                    x.clone(),
+                                    lb.max(y_base),
+                                    y_axis.y.max(y_base),
+                                    ub.max(y_base),
+                                    BLACK.filled(),
+                                    10,
+                                )
+                            } else {
+                                ErrorBar::new_vertical(
+                                    x.clone(),
+                                    y_base,
+                                    y_base,
+                                    y_base,
+                                    color.filled(),
+                                    0,
+                                )
+                            }
+                        }),
+                )?;
 
-        // draw the bar labels
-        let series = ctx.draw_series(
-            custom_x_axes
-                .ticks
-                .iter()
-                .zip(self.coordinates[0].y_axis.iter())
-                .map(|(x, y_axis)| {
-                    EmptyElement::at((x.clone(), y_axis.y))
-                        + Text::new(y_axis.y.to_string(), (5, -10), ("sans-serif", 12))
-                }),
-        )?;
-
-        // draw the legend
-        if let Some(label) = self.coordinates[0].label.clone() {
-            series
-                .label(label)
-                .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &RED));
-
-            ctx.configure_series_labels().border_style(&BLACK).draw()?;
-        }
+                // draw the bar labels
+                let series = ctx.draw_series(
+                    custom_x_axes
+                        .ticks
+                        .iter()
+                        .zip(self.coordinates[0].y_axis.iter())
+                        .map(|(x, y_axis)| {
+                            EmptyElement::at((x.clone(), y_axis.y.max(y_base)))
+                                + Text::new(y_axis.y.to_string(), (5, -10), self.style.label_font())
+                        }),
+                )?;
 
-        // to avoid the IO failure being ignored silently, we manually call the present function
-        root_area.present()?;
+                // draw the legend
+                if let Some(label) = self.coordinates[0].label.clone() {
+                    series
+                        .label(label)
+                        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+
+                    ctx.configure_series_labels().border_style(&BLACK).draw()?;
+                }
+
+                // to avoid the IO failure being ignored silently, we manually call the present function
+                root_area.present()?;
+            });
+        });
 
         Ok(())
     }
@@ -442,9 +775,6 @@ impl Plotter {
         caption: Option<&str>,
         file_name: &P,
     ) -> Result<(), Error> {
-        let root_area = SVGBackend::new(file_name, (800, 500)).into_drawing_area();
-        root_area.fill(&WHITE)?;
-
         let x_axis = self.coordinates[0]
             .x_axis
             .iter()
@@ -464,46 +794,336 @@ impl Plotter {
             .iter()
             .map(|y_axis| y_axis.y)
             .fold(f64::NEG_INFINITY, |a, b| a.max(b));
-        let y_start = y_min - (y_min / 5.0); // y starts bellow the first y-axis value
-        let y_end = y_max + (y_max / 5.0); // and ends after the last y-axis value
-
-        let mut ctx = ChartBuilder::on(&root_area)
-            .set_label_area_size(LabelAreaPosition::Left, 100.0)
-            .set_label_area_size(LabelAreaPosition::Bottom, 50.0)
-            .caption(caption.unwrap_or(""), ("sans-serif", 30.0))
-            .margin(5.0)
-            .build_cartesian_2d(x_start..x_end, y_start..y_end)?;
-
-        ctx.configure_mesh()
-            .axis_desc_style(("sans-serif", 20.0))
-            .x_desc(x_label.unwrap_or(""))
-            .x_label_formatter(&|x| {
-                if *x >= 1000.0 {
-                    format!("{:e}", x)
-                } else {
-                    x.to_string()
-                }
-            })
-            .y_desc(y_label.unwrap_or(""))
-            .y_label_formatter(&|y| {
-                if *y >= 1000.0 {
-                    format!("{:e}", y)
-                } else {
-                    y.to_string()
+        let y_min_positive = self.coordinates[0]
+            .y_axis
+            .iter()
+            .map(|y_axis| y_axis.y)
+            .filter(|y| *y > 0f64)
+            .fold(f64::INFINITY, |a, b| a.min(b));
+        let (y_start, y_end) = self.style.y_range(y_min, y_max, y_min_positive)?;
+        // the values that can not be shown on a log scale are drawn at its start
+        let y_floor = if self.style.log_scale {
+            y_start
+        } else {
+            f64::NEG_INFINITY
+        };
+        let color = self.style.color_scheme.color(0);
+
+        draw_on!(self.style, file_name, |root_area| {
+            root_area.fill(&WHITE)?;
+
+            with_y_range!(self.style.log_scale, y_start, y_end, |y_range| {
+                let mut ctx = ChartBuilder::on(&root_area)
+                    .set_label_area_size(LabelAreaPosition::Left, 100.0)
+                    .set_label_area_size(LabelAreaPosition::Bottom, 50.0)
+                    .caption(caption.unwrap_or(""), self.style.caption_font())
+                    .margin(5.0)
+                    .build_cartesian_2d(x_start..x_end, y_range)?;
+
+                ctx.configure_mesh()
+                    .label_style(self.style.label_font())
+                    .axis_desc_style(self.style.desc_font())
+                    .x_desc(x_label.unwrap_or(""))
+                    .x_label_formatter(&|x| {
+                        if *x >= 1000.0 {
+                            format!("{:e}", x)
+                        } else {
+                            x.to_string()
+                        }
+                    })
+                    .y_desc(y_label.unwrap_or(""))
+                    .y_label_formatter(&|y| {
+                        if *y >= 1000.0 {
+                            format!("{:e}", y)
+                        } else {
+                            y.to_string()
+                        }
+                    })
+                    .draw()?;
+
+                // draw the points
+                ctx.draw_series(x_axis.iter().zip(self.coordinates[0].y_axis.iter()).map(
+                    |(x, y_axis)| Circle::new((*x, y_axis.y.max(y_floor)), 2, color.filled()),
+                ))?;
+
+                // to avoid the IO failure being ignored silently, we manually call the present function
+                root_area.present()?;
+            });
+        });
+
+        Ok(())
+    }
+
+    ///
+    /// Draw the cumulative distribution functions of the distributions, with the values on a
+    /// logarithmic x axis
+    ///
+    pub fn cdf_chart<P: AsRef<Path> + std::convert::AsRef<std::ffi::OsStr>>(
+        &self,
+        x_label: Option<&str>,
+        caption: Option<&str>,
+        file_name: &P,
+    ) -> Result<(), Error> {
+        let distributions = self
+            .distributions
+            .iter()
+            .map(|distribution| (sorted_positive(&distribution.values), &distribution.label))
+            .filter(|(values, _)| !values.is_empty())
+            .collect::<Vec<_>>();
+        let (x_min, x_max) = log_range(distributions.iter().map(|(values, _)| &values[..]))?;
+
+        draw_on!(self.style, file_name, |root_area| {
+            root_area.fill(&WHITE)?;
+
+            let mut ctx = ChartBuilder::on(&root_area)
+                .set_label_area_size(LabelAreaPosition::Left, 100.0)
+                .set_label_area_size(LabelAreaPosition::Bottom, 50.0)
+                .margin(30.0)
+                .caption(caption.unwrap_or(""), self.style.caption_font())
+                .build_cartesian_2d((x_min..x_max).log_scale(), 0f64..1f64)?;
+
+            ctx.configure_mesh()
+                .label_style(self.style.label_font())
+                .axis_desc_style(self.style.desc_font())
+                .x_desc(x_label.unwrap_or(""))
+                .x_label_formatter(&|x| format!("{:e}", x))
+                .y_desc("Cumulative probability")
+                .draw()?;
+
+            let mut has_legend = false;
+            let mut colors = (0..).map(|idx| self.style.color_scheme.color(idx));
+            for (values, label) in distributions {
+                // the CDF is drawn with the quantiles rather than all the values
+                let len = values.len();
+                let points = (0..=CDF_POINTS).map(|point| {
+                    let probability = point as f64 / CDF_POINTS as f64;
+                    let rank = ((probability * len as f64).ceil() as usize).clamp(1, len);
+                    (values[rank - 1], probability)
+                });
+
+                let color = colors.next().unwrap();
+                let series = ctx.draw_series(LineSeries::new(points, &color))?;
+                if let Some(label) = label.clone() {
+                    series
+                        .label(label)
+                        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+                    has_legend = true;
                 }
+            }
+
+            if has_legend {
+                ctx.configure_series_labels()
+                    .position(SeriesLabelPosition::LowerRight)
+                    .border_style(&BLACK)
+                    .draw()?;
+            }
+
+            // to avoid the IO failure being ignored silently, we manually call the present function
+            root_area.present()?;
+        });
+
+        Ok(())
+    }
+
+    ///
+    /// Draw a box plot of each distribution, with the values on a logarithmic y axis. The boxes
+    /// span the first to the third quartile, and the whiskers reach the furthest values within
+    /// 1.5 IQR of the box.
+    ///
+    pub fn box_plot<P: AsRef<Path> + std::convert::AsRef<std::ffi::OsStr>>(
+        &self,
+        y_label: Option<&str>,
+        caption: Option<&str>,
+        file_name: &P,
+    ) -> Result<(), Error> {
+        let distributions = self
+            .distributions
+            .iter()
+            .map(|distribution| {
+                (
+                    sorted_positive(&distribution.values),
+                    distribution.label.clone().unwrap_or_default(),
+                )
             })
-            .draw()?;
+            .filter(|(values, _)| !values.is_empty())
+            .collect::<Vec<_>>();
+        let (y_min, y_max) = log_range(distributions.iter().map(|(values, _)| &values[..]))?;
+        let labels = distributions
+            .iter()
+            .map(|(_, label)| label.clone())
+            .collect::<Vec<_>>();
+        // each box gets ten units of the x axis, with the label at its center
+        let centers = (0..labels.len() as i64)
+            .map(|idx| idx * 10 + 5)
+            .collect::<Vec<_>>();
 
-        // draw the points
-        ctx.draw_series(
-            x_axis
-                .iter()
-                .zip(self.coordinates[0].y_axis.iter())
-                .map(|(x, y_axis)| Circle::new((*x, y_axis.y), 2, RED.filled())),
-        )?;
+        draw_on!(self.style, file_name, |root_area| {
+            root_area.fill(&WHITE)?;
+
+            let mut ctx = ChartBuilder::on(&root_area)
+                .set_label_area_size(LabelAreaPosition::Left, 100.0)
+                .set_label_area_size(LabelAreaPosition::Bottom, 50.0)
+                .margin(30.0)
+                .caption(caption.unwrap_or(""), self.style.caption_font())
+                .build_cartesian_2d(
+                    (0..labels.len() as i64 * 10).with_key_points(centers),
+                    (y_min..y_max).log_scale(),
+                )?;
+
+            ctx.configure_mesh()
+                .label_style(self.style.label_font())
+                .axis_desc_style(self.style.desc_font())
+                .x_label_formatter(&|x| labels.get(*x as usize / 10).cloned().unwrap_or_default())
+                .y_desc(y_label.unwrap_or(""))
+                .y_label_formatter(&|y| format!("{:e}", y))
+                .draw()?;
+
+            let mut colors = (0..).map(|idx| self.style.color_scheme.color(idx));
+            for (idx, (values, _)) in distributions.iter().enumerate() {
+                let x = idx as i64 * 10 + 5;
+                let quantile =
+                    |q: f64| values[((q * values.len() as f64) as usize).min(values.len() - 1)];
+                let (q1, median, q3) = (quantile(0.25), quantile(0.5), quantile(0.75));
+                let iqr = q3 - q1;
+                let lower = values
+                    .iter()
+                    .find(|value| **value >= q1 - 1.5 * iqr)
+                    .unwrap_or(&q1);
+                let upper = values
+                    .iter()
+                    .rev()
+                    .find(|value| **value <= q3 + 1.5 * iqr)
+                    .unwrap_or(&q3);
+
+                let color = colors.next().unwrap();
+                ctx.draw_series([
+                    Rectangle::new([(x - 3, q1), (x + 3, q3)], color.mix(0.3).filled()),
+                    Rectangle::new([(x - 3, q1), (x + 3, q3)], color.stroke_width(1)),
+                ])?;
+                ctx.draw_series([
+                    PathElement::new(
+                        vec![(x - 3, median), (x + 3, median)],
+                        BLACK.stroke_width(2),
+                    ),
+                    PathElement::new(vec![(x, q3), (x, *upper)], BLACK.stroke_width(1)),
+                    PathElement::new(vec![(x, q1), (x, *lower)], BLACK.stroke_width(1)),
+                    PathElement::new(
+                        vec![(x - 1, *upper), (x + 1, *upper)],
+                        BLACK.stroke_width(1),
+                    ),
+                    PathElement::new(
+                        vec![(x - 1, *lower), (x + 1, *lower)],
+                        BLACK.stroke_width(1),
+                    ),
+                ])?;
+            }
+
+            // to avoid the IO failure being ignored silently, we manually call the present function
+            root_area.present()?;
+        });
+
+        Ok(())
+    }
 
-        // to avoid the IO failure being ignored silently, we manually call the present function
-        root_area.present()?;
+    ///
+    /// Draw a heatmap of the values of the first distribution over time, with the values on a
+    /// logarithmic y axis. The darker a cell is, the more values fall into it.
+    ///
+    pub fn heatmap<P: AsRef<Path> + std::convert::AsRef<std::ffi::OsStr>>(
+        &self,
+        x_label: Option<&str>,
+        y_label: Option<&str>,
+        caption: Option<&str>,
+        file_name: &P,
+    ) -> Result<(), Error> {
+        let distribution = self
+            .distributions
+            .first()
+            .ok_or_else(|| Error::format("heatmap", "no distribution to plot"))?;
+        let times = distribution
+            .times
+            .as_ref()
+            .ok_or_else(|| Error::format("heatmap", "the values have no times"))?;
+        let points = times
+            .iter()
+            .zip(distribution.values.iter())
+            .filter(|(_, value)| **value > 0f64)
+            .map(|(time, value)| (*time, *value))
+            .collect::<Vec<_>>();
+        let values = points.iter().map(|(_, value)| *value).collect::<Vec<_>>();
+        let (y_min, y_max) = log_range([&values[..]].into_iter())?;
+        let x_min = points
+            .iter()
+            .fold(f64::INFINITY, |a, (time, _)| a.min(*time));
+        let x_max = points
+            .iter()
+            .fold(f64::NEG_INFINITY, |a, (time, _)| a.max(*time))
+            .max(x_min + f64::EPSILON);
+
+        // count the values in each cell, with the value bins equally wide on the log scale
+        let (log_min, log_max) = (y_min.ln(), y_max.ln());
+        let mut counts = vec![vec![0usize; HEATMAP_VALUE_BINS]; HEATMAP_TIME_BINS];
+        for (time, value) in points.iter() {
+            let x_bin = ((time - x_min) / (x_max - x_min) * HEATMAP_TIME_BINS as f64) as usize;
+            let y_bin =
+                ((value.ln() - log_min) / (log_max - log_min) * HEATMAP_VALUE_BINS as f64) as usize;
+            counts[x_bin.min(HEATMAP_TIME_BINS - 1)][y_bin.min(HEATMAP_VALUE_BINS - 1)] += 1;
+        }
+        let max_count = counts.iter().flatten().max().copied().unwrap_or(1).max(1);
+
+        draw_on!(self.style, file_name, |root_area| {
+            root_area.fill(&WHITE)?;
+
+            let mut ctx = ChartBuilder::on(&root_area)
+                .set_label_area_size(LabelAreaPosition::Left, 100.0)
+                .set_label_area_size(LabelAreaPosition::Bottom, 50.0)
+                .margin(30.0)
+                .caption(caption.unwrap_or(""), self.style.caption_font())
+                .build_cartesian_2d(x_min..x_max, (y_min..y_max).log_scale())?;
+
+            ctx.configure_mesh()
+                .label_style(self.style.label_font())
+                .disable_mesh()
+                .axis_desc_style(self.style.desc_font())
+                .x_desc(x_label.unwrap_or(""))
+                .y_desc(y_label.unwrap_or(""))
+                .y_label_formatter(&|y| format!("{:e}", y))
+                .draw()?;
+
+            let x_step = (x_max - x_min) / HEATMAP_TIME_BINS as f64;
+            let y_step = (log_max - log_min) / HEATMAP_VALUE_BINS as f64;
+            ctx.draw_series(counts.iter().enumerate().flat_map(|(x_bin, column)| {
+                column
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, count)| **count > 0)
+                    .map(move |(y_bin, count)| {
+                        // the intensity is logarithmic, so that the sparse cells are still visible
+                        let intensity = (*count as f64).ln_1p() / (max_count as f64).ln_1p();
+                        let color = RGBColor(
+                            255 - (55.0 * intensity) as u8,
+                            230 - (230.0 * intensity) as u8,
+                            180 - (180.0 * intensity) as u8,
+                        );
+                        Rectangle::new(
+                            [
+                                (
+                                    x_min + x_bin as f64 * x_step,
+                                    (log_min + y_bin as f64 * y_step).exp(),
+                                ),
+                                (
+                                    x_min + (x_bin + 1) as f64 * x_step,
+                                    (log_min + (y_bin + 1) as f64 * y_step).exp(),
+                                ),
+                            ],
+                            color.filled(),
+                        )
+                    })
+            }))?;
+
+            // to avoid the IO failure being ignored silently, we manually call the present function
+            root_area.present()?;
+        });
 
         Ok(())
     }
@@ -570,3 +1190,49 @@ impl Ranged for CustomXAxis {
         self.ticks[0].clone()..self.ticks[self.ticks.len() - 1].clone()
     }
 }
+
+// the positive values sorted, as the logarithmic axes can only show the positive values
+fn sorted_positive(values: &[f64]) -> Vec<f64> {
+    let mut values = values
+        .iter()
+        .filter(|value| **value > 0f64)
+        .copied()
+        .collect::<Vec<_>>();
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    values
+}
+
+// the range of a logarithmic axis showing all the positive values
+fn log_range<'a>(values: impl Iterator<Item = &'a [f64]>) -> Result<(f64, f64), Error> {
+    let (min, max) = values
+        .flatten()
+        .filter(|value| **value > 0f64)
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+            (min.min(*value), max.max(*value))
+        });
+    if !min.is_finite() {
+        return Err(Error::format("plot", "no positive values to plot"));
+    }
+
+    // a margin of a tenth of a decade on both sides
+    Ok((min / 1.25, (max * 1.25).max(min * 1.25 * 1.25)))
+}
+
+// convert an SVG plot to PDF, and remove the SVG
+fn svg_to_pdf(svg_path: &Path, pdf_path: &Path) -> Result<(), Error> {
+    let status = Command::new("rsvg-convert")
+        .arg("--format=pdf")
+        .arg("--output")
+        .arg(pdf_path)
+        .arg(svg_path)
+        .status()?;
+    std::fs::remove_file(svg_path)?;
+    if !status.success() {
+        return Err(Error::format(
+            "plot",
+            format!("failed to convert {} to PDF", svg_path.display()),
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,268 @@
+use crate::error::Error;
+use crate::micro::BenchFn;
+use crate::plotter::{Indexes, PlotStyle, Plotter};
+use crate::{BenchResult, Record};
+use std::fs::{create_dir_all, read_dir};
+use std::path::{Path, PathBuf};
+
+///
+/// Re-renders the charts of the static and realtime results logged to one or more result
+/// directories, e.g. to overlay the results of an earlier run on the results of today's run,
+/// without running the benchmarks again.
+///
+pub struct Replotter {
+    pub result_dirs: Vec<PathBuf>,
+    pub labels: Vec<String>, // a label per result directory, to tell the runs apart
+    pub renames: Vec<(String, String)>, // the logged names of filesystems, and their new labels
+    pub fs_names: Vec<String>, // the logged names of the filesystems to plot, all if empty
+    pub bench_fns: Vec<BenchFn>, // the benchmark functions to plot
+    pub title: Option<String>, // the title of the charts, followed by the name of each chart
+    pub log_path: PathBuf,   // the path to write the charts to
+    pub plot_style: PlotStyle,
+}
+
+impl Replotter {
+    pub fn new(result_dirs: Vec<PathBuf>, log_path: PathBuf) -> Self {
+        Self {
+            result_dirs,
+            labels: vec![],
+            renames: vec![],
+            fs_names: vec![],
+            bench_fns: BenchFn::all(),
+            title: None,
+            log_path,
+            plot_style: PlotStyle::new(),
+        }
+    }
+
+    ///
+    /// Read the results and write the charts, and return the number of charts written
+    ///
+    pub fn run(&self) -> Result<usize, Error> {
+        if !self.labels.is_empty() && self.labels.len() != self.result_dirs.len() {
+            return Err(Error::InvalidConfig(
+                "There should be one label per each result directory".to_string(),
+            ));
+        }
+        create_dir_all(&self.log_path)?;
+
+        let mut charts = 0;
+        let mut ops_s_records = vec![];
+        for bench_fn in self.bench_fns.iter() {
+            // the timelines of all the filesystems in all the result directories
+            let mut plotter = Plotter::with_style(self.plot_style.clone());
+            for (idx, result_dir) in self.result_dirs.iter().enumerate() {
+                for (fs_name, path) in self.timelines(result_dir, bench_fn)? {
+                    let result = BenchResult::read(&path)?;
+                    if result.header.first().map(|field| field.as_str()) != Some("time") {
+                        continue;
+                    }
+                    plotter.add_coordinates(
+                        result.records,
+                        Some(self.label(idx, &fs_name)),
+                        Indexes::new(0, false, 1, None, None),
+                    )?;
+                }
+            }
+
+            if !plotter.is_empty() {
+                let mut file_name = self.log_path.clone();
+                file_name.push(format!("{}.svg", bench_fn));
+                plotter.line_chart(
+                    Some("Time (s)"),
+                    Some("Ops/s"),
+                    Some(&self.caption(bench_fn.title())),
+                    false,
+                    false,
+                    &file_name,
+                )?;
+                charts += 1;
+            }
+        }
+
+        // the ops/s of all the filesystems in all the result directories side by side
+        for (idx, result_dir) in self.result_dirs.iter().enumerate() {
+            for (fs_name, path) in self.ops_per_second(result_dir)? {
+                let result = BenchResult::read(&path)?;
+                let label = self.label(idx, &fs_name);
+                for record in result.records {
+                    let selected = self
+                        .bench_fns
+                        .iter()
+                        .any(|bench_fn| bench_fn.to_string() == record.fields[0]);
+                    if selected && record.fields.len() > 4 {
+                        let mut fields = record.fields;
+                        fields[0] = format!("{}: {}", fields[0], label);
+                        ops_s_records.push(Record::from(fields));
+                    }
+                }
+            }
+        }
+        if !ops_s_records.is_empty() {
+            let mut plotter = Plotter::with_style(self.plot_style.clone());
+            plotter.add_coordinates(
+                ops_s_records,
+                None,
+                Indexes::new(0, true, 2, Some(3), Some(4)),
+            )?;
+            let mut file_name = self.log_path.clone();
+            file_name.push("ops_per_second.svg");
+            plotter.bar_chart(
+                Some("Operation"),
+                Some("Ops/s"),
+                Some(&self.caption("Ops/s")),
+                &file_name,
+            )?;
+            charts += 1;
+        }
+
+        if charts == 0 {
+            return Err(Error::InvalidPath(
+                "no static or realtime results found to plot".to_string(),
+            ));
+        }
+
+        Ok(charts)
+    }
+
+    // the ops/s timelines of the benchmark function in the result directory, by filesystem
+    fn timelines(
+        &self,
+        result_dir: &Path,
+        bench_fn: &BenchFn,
+    ) -> Result<Vec<(String, PathBuf)>, Error> {
+        let files = self
+            .result_files(result_dir)?
+            .into_iter()
+            .filter_map(|path| {
+                let (fs_name, file_fn) = timeline_name(&path)?;
+                if &file_fn == bench_fn {
+                    Some((fs_name, path))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Ok(files)
+    }
+
+    // the ops/s summaries in the result directory, by filesystem
+    fn ops_per_second(&self, result_dir: &Path) -> Result<Vec<(String, PathBuf)>, Error> {
+        let files = self
+            .result_files(result_dir)?
+            .into_iter()
+            .filter_map(|path| {
+                let name = path.file_name()?.to_str()?;
+                let fs_name = name.strip_suffix("_ops_per_second.csv")?.to_string();
+                Some((fs_name, path))
+            })
+            .collect();
+
+        Ok(files)
+    }
+
+    // the CSV files of the selected filesystems in the result directory, sorted by name
+    fn result_files(&self, result_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+        if !result_dir.is_dir() {
+            return Err(Error::InvalidPath(format!(
+                "{} is not a result directory",
+                result_dir.display()
+            )));
+        }
+
+        let mut files = read_dir(result_dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|path| path.extension().map(|ext| ext == "csv").unwrap_or(false))
+            .filter(|path| {
+                let name = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or("");
+                self.fs_names.is_empty()
+                    || self
+                        .fs_names
+                        .iter()
+                        .any(|fs_name| name.starts_with(&format!("{}_", fs_name)))
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+
+        Ok(files)
+    }
+
+    // the label of a filesystem in the result directory with the index
+    fn label(&self, idx: usize, fs_name: &str) -> String {
+        let fs_name = self
+            .renames
+            .iter()
+            .find(|(name, _)| name == fs_name)
+            .map(|(_, label)| label.as_str())
+            .unwrap_or(fs_name);
+
+        match self.labels.get(idx) {
+            Some(label) => format!("{} ({})", fs_name, label),
+            None => fs_name.to_string(),
+        }
+    }
+
+    fn caption(&self, chart: &str) -> String {
+        match &self.title {
+            Some(title) => format!("{}: {}", title, chart),
+            None => chart.to_string(),
+        }
+    }
+}
+
+// the filesystem and benchmark function of an ops/s timeline, named <fs-name>_<bench-fn>.csv, or
+// <fs-name>_<bench-fn>_run1.csv for the first of the repeated runs, which is the one plotted
+fn timeline_name(path: &Path) -> Option<(String, BenchFn)> {
+    let name = path.file_name()?.to_str()?;
+
+    // the longest matching benchmark function, as e.g. read is also the end of cold_read
+    BenchFn::all()
+        .into_iter()
+        .filter_map(|bench_fn| {
+            let fs_name = name
+                .strip_suffix(&format!("_{}.csv", bench_fn))
+                .or_else(|| name.strip_suffix(&format!("_{}_run1.csv", bench_fn)))?;
+            Some((fs_name.to_string(), bench_fn))
+        })
+        .min_by_key(|(fs_name, _)| fs_name.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timeline_name_test() {
+        let name = |file_name: &str| timeline_name(Path::new(file_name));
+
+        assert_eq!(
+            name("ext4_read.csv"),
+            Some(("ext4".to_string(), BenchFn::Read))
+        );
+        assert_eq!(
+            name("ext4_cold_read.csv"),
+            Some(("ext4".to_string(), BenchFn::ColdRead))
+        );
+        assert_eq!(
+            name("my_fs_write_sync_run1.csv"),
+            Some(("my_fs".to_string(), BenchFn::WriteSync))
+        );
+        assert_eq!(
+            name("ext4_mmap_rand_read.csv"),
+            Some(("ext4".to_string(), BenchFn::MmapRandRead))
+        );
+        assert_eq!(
+            name("ext4_create_write.csv"),
+            Some(("ext4".to_string(), BenchFn::CreateWrite))
+        );
+        assert_eq!(name("ext4_read_run2.csv"), None);
+        assert_eq!(name("ext4_ops_per_second.csv"), None);
+        assert_eq!(name("ext4_read_ops_s_period.csv"), None);
+    }
+}
@@ -0,0 +1,506 @@
+use crate::engine::IoEngine;
+use crate::error::Error;
+use crate::results::Manifest;
+use crate::{BenchMode, Config};
+use byte_unit::Byte;
+use std::collections::BTreeMap;
+use std::fs::{read, read_to_string, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// the name of the report in the log path
+const REPORT_NAME: &str = "report.html";
+
+// the result files with at most this many rows are shown as tables, the others are only linked
+const MAX_TABLE_ROWS: usize = 50;
+
+const STYLE: &str =
+    "body { font-family: sans-serif; margin: 2em auto; max-width: 1100px; color: #222; }
+h1 { border-bottom: 2px solid #444; }
+h2 { border-bottom: 1px solid #aaa; margin-top: 2em; }
+table { border-collapse: collapse; margin: 1em 0; font-size: 0.9em; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }
+th { background: #eee; }
+figure { margin: 1em 0; }
+figure svg, figure img { max-width: 100%; height: auto; }
+figcaption { font-size: 0.9em; color: #555; }";
+
+///
+/// A self-contained HTML report of a benchmark, with the configuration, the environment, the
+/// summary tables, the charts and links to the raw results logged in the log path
+///
+pub struct Report {
+    mode: String,
+    configuration: Vec<(String, String)>,
+    mount_paths: Vec<PathBuf>,
+    fs_names: Vec<String>,
+    log_path: PathBuf,
+}
+
+impl Report {
+    ///
+    /// Start a report of the benchmark mode with the configuration, before the benchmark is run
+    ///
+    pub fn new(mode: &BenchMode, config: &Config) -> Self {
+        let size = |bytes: usize| {
+            Byte::from_bytes(bytes as u128)
+                .get_appropriate_unit(true)
+                .to_string()
+        };
+
+        let mut configuration = vec![
+            ("mode".to_string(), mode.to_string()),
+            ("filesystems".to_string(), config.fs_names.join(", ")),
+            (
+                "mount paths".to_string(),
+                config
+                    .mount_paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            ("run time".to_string(), format!("{} s", config.run_time)),
+            ("io size".to_string(), size(config.io_size)),
+            ("file size".to_string(), size(config.file_size)),
+            ("fileset size".to_string(), config.fileset_size.to_string()),
+        ];
+        match mode {
+            BenchMode::Static | BenchMode::RealTime => {
+                configuration.push((
+                    "benchmark functions".to_string(),
+                    config
+                        .bench_fns
+                        .iter()
+                        .map(|bench_fn| bench_fn.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ));
+                configuration.push(("repetitions".to_string(), config.repetitions.to_string()));
+                configuration.push((
+                    "confidence level".to_string(),
+                    config.confidence_level.to_string(),
+                ));
+                configuration.push(("CI method".to_string(), config.ci_method.to_string()));
+                configuration.push((
+                    "bootstrap iterations".to_string(),
+                    config.bootstrap_iterations.to_string(),
+                ));
+                configuration.push((
+                    "time window".to_string(),
+                    format!("{} ms", config.window().as_millis()),
+                ));
+                if let Some(precision) = config.precision {
+                    configuration.push(("precision".to_string(), precision.to_string()));
+                }
+                configuration.push((
+                    "exclude outliers".to_string(),
+                    config.exclude_outliers.to_string(),
+                ));
+                configuration.push(("write sync".to_string(), config.write_sync.to_string()));
+                configuration.push(("directory sync".to_string(), config.dir_sync.to_string()));
+                configuration.push(("file layout".to_string(), config.file_layout.to_string()));
+            }
+            BenchMode::Trace => {
+                configuration.push((
+                    "workload".to_string(),
+                    config.workload.display().to_string(),
+                ));
+                configuration.push((
+                    "parallelism degree".to_string(),
+                    config.parallelism_degree.to_string(),
+                ));
+            }
+            BenchMode::Throughput => {}
+            BenchMode::Durability => {
+                configuration.push(("sync policy".to_string(), config.sync_policy.to_string()));
+                configuration.push(("verify only".to_string(), config.verify_only.to_string()));
+            }
+            BenchMode::Directory => {
+                configuration.push((
+                    "directory entries".to_string(),
+                    config.dir_entries.to_string(),
+                ));
+                configuration.push(("directory step".to_string(), config.dir_step().to_string()));
+            }
+        }
+        if let BenchMode::Static | BenchMode::Throughput = mode {
+            configuration.push(("io engine".to_string(), config.io_engine.to_string()));
+            if config.io_engine == IoEngine::IoUring {
+                configuration.push((
+                    "io_uring".to_string(),
+                    format!(
+                        "queue depth {}, batch {}, registered buffers: {}, registered files: {}",
+                        config.uring.queue_depth,
+                        config.uring.batch,
+                        config.uring.registered_buffers,
+                        config.uring.registered_files
+                    ),
+                ));
+            }
+        }
+        if let (BenchMode::Throughput, Some(io_threads)) = (mode, config.io_threads) {
+            configuration.push(("io threads".to_string(), io_threads.to_string()));
+        }
+        configuration.push(("verify reads".to_string(), config.verify.to_string()));
+        if config.verify {
+            configuration.push(("seed".to_string(), config.seed.to_string()));
+        }
+        if let Some(max_failures) = config.max_failures {
+            configuration.push(("max failures".to_string(), max_failures.to_string()));
+        }
+
+        Self {
+            mode: mode.to_string(),
+            configuration,
+            mount_paths: config.mount_paths.clone(),
+            fs_names: config.fs_names.clone(),
+            log_path: config.log_path.clone(),
+        }
+    }
+
+    ///
+    /// Generate the report from the files of the run listed by its manifest, and return its path
+    ///
+    pub fn generate(&self, manifest: &Manifest) -> Result<PathBuf, Error> {
+        let files = manifest
+            .paths()
+            .into_iter()
+            .filter(|path| path.file_name() != Some(REPORT_NAME.as_ref()))
+            .collect::<Vec<_>>();
+        let file_name = |path: &PathBuf| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        };
+
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!(
+            "<title>fs-bench {} report</title>\n<style>\n{}\n</style>\n</head>\n<body>\n",
+            escape(&self.mode),
+            STYLE
+        ));
+        html.push_str(&format!(
+            "<h1>fs-bench {} report</h1>\n<p>Generated on {} by fs-bench {}.</p>\n",
+            escape(&self.mode),
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            env!("CARGO_PKG_VERSION")
+        ));
+
+        html.push_str("<h2>Configuration</h2>\n");
+        html.push_str(&key_value_table(&self.configuration));
+
+        html.push_str("<h2>Environment</h2>\n");
+        html.push_str(&key_value_table(&self.environment()));
+
+        let comparison = self.comparison(&files)?;
+        if !comparison.is_empty() {
+            html.push_str("<h2>Comparison of the filesystems</h2>\n");
+            html.push_str(&comparison);
+        }
+
+        let mut tables = String::new();
+        for path in files.iter().filter(|path| has_extension(path, "csv")) {
+            let (header, rows) = read_csv(path)?;
+            if !rows.is_empty() && rows.len() <= MAX_TABLE_ROWS {
+                tables.push_str(&format!("<h3>{}</h3>\n", escape(&file_name(path))));
+                tables.push_str(&table(&header, &rows));
+            }
+        }
+        if !tables.is_empty() {
+            html.push_str("<h2>Summary tables</h2>\n");
+            html.push_str(&tables);
+        }
+
+        // the PDF charts can not be inlined, and are only linked with the raw data
+        let charts = files
+            .iter()
+            .filter(|path| has_extension(path, "svg") || has_extension(path, "png"))
+            .collect::<Vec<_>>();
+        if !charts.is_empty() {
+            html.push_str("<h2>Charts</h2>\n");
+            for path in charts {
+                let chart = if has_extension(path, "svg") {
+                    let svg = read_to_string(path)?;
+                    // the XML declaration is not allowed inside the HTML
+                    match svg.find("<svg") {
+                        Some(start) => svg[start..].to_string(),
+                        None => continue,
+                    }
+                } else {
+                    format!(
+                        "<img src=\"data:image/png;base64,{}\" alt=\"{}\">",
+                        base64(&read(path)?),
+                        escape(&file_name(path))
+                    )
+                };
+                html.push_str(&format!(
+                    "<figure>\n{}\n<figcaption>{}</figcaption>\n</figure>\n",
+                    chart,
+                    escape(&file_name(path))
+                ));
+            }
+        }
+
+        html.push_str("<h2>Raw data</h2>\n<ul>\n");
+        for path in files.iter() {
+            let name = escape(&file_name(path));
+            html.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n", name, name));
+        }
+        html.push_str("</ul>\n</body>\n</html>\n");
+
+        let mut report_path = self.log_path.clone();
+        report_path.push(REPORT_NAME);
+        let mut report = File::create(&report_path)?;
+        report.write_all(html.as_bytes())?;
+
+        Ok(report_path)
+    }
+
+    // the ops/s of each benchmark function on each filesystem, side by side
+    fn comparison(&self, files: &[PathBuf]) -> Result<String, Error> {
+        // operation -> filesystem -> ops/s with its confidence interval
+        let mut ops_per_second: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+        let mut fs_names = vec![];
+        for fs_name in self.fs_names.iter() {
+            let mut path = self.log_path.clone();
+            path.push(format!("{}_ops_per_second.csv", fs_name));
+            if !files.contains(&path) {
+                continue;
+            }
+            fs_names.push(fs_name.clone());
+
+            let (header, rows) = read_csv(&path)?;
+            let column = |name: &str| header.iter().position(|column| column == name);
+            if let (Some(op), Some(mean), Some(lb), Some(ub)) = (
+                column("operation"),
+                column("ops/s"),
+                column("ops/s_lb"),
+                column("ops/s_ub"),
+            ) {
+                for row in rows.iter() {
+                    ops_per_second.entry(row[op].clone()).or_default().insert(
+                        fs_name.clone(),
+                        format!("{} [{}, {}]", row[mean], row[lb], row[ub]),
+                    );
+                }
+            }
+        }
+
+        if fs_names.len() < 2 {
+            return Ok(String::new());
+        }
+
+        let mut header = vec!["operation (ops/s [CI])".to_string()];
+        header.append(&mut fs_names.clone());
+        let rows = ops_per_second
+            .into_iter()
+            .map(|(op, by_fs)| {
+                let mut row = vec![op];
+                for fs_name in fs_names.iter() {
+                    row.push(by_fs.get(fs_name).cloned().unwrap_or_default());
+                }
+                row
+            })
+            .collect::<Vec<_>>();
+
+        Ok(table(&header, &rows))
+    }
+
+    // the host, kernel, CPU, memory and the filesystems of the mount paths
+    fn environment(&self) -> Vec<(String, String)> {
+        let proc = |path: &str| {
+            read_to_string(path)
+                .map(|content| content.trim().to_string())
+                .unwrap_or_else(|_| "unknown".to_string())
+        };
+        let cpuinfo = read_to_string("/proc/cpuinfo").unwrap_or_default();
+        let cpu = cpuinfo
+            .lines()
+            .find(|line| line.starts_with("model name"))
+            .and_then(|line| line.split(':').nth(1))
+            .map(|model| model.trim().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        let cpus = cpuinfo
+            .lines()
+            .filter(|line| line.starts_with("processor"))
+            .count();
+        let memory = read_to_string("/proc/meminfo")
+            .unwrap_or_default()
+            .lines()
+            .find(|line| line.starts_with("MemTotal"))
+            .and_then(|line| line.split(':').nth(1))
+            .map(|total| total.trim().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let mut environment = vec![
+            ("host".to_string(), proc("/proc/sys/kernel/hostname")),
+            ("kernel".to_string(), proc("/proc/sys/kernel/osrelease")),
+            ("CPU".to_string(), format!("{} ({} CPUs)", cpu, cpus)),
+            ("memory".to_string(), memory),
+        ];
+        for (fs_name, mount_path) in self.fs_names.iter().zip(self.mount_paths.iter()) {
+            environment.push((format!("{} mount", fs_name), mount_info(mount_path)));
+        }
+
+        environment
+    }
+}
+
+// the device, type and options of the filesystem mounted at the longest prefix of the path
+fn mount_info(path: &Path) -> String {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mounts = read_to_string("/proc/mounts").unwrap_or_default();
+
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            match fields.as_slice() {
+                [device, mount_point, fs_type, options, ..] if path.starts_with(mount_point) => {
+                    Some((
+                        mount_point.len(),
+                        format!("{} on {} ({}, {})", device, mount_point, fs_type, options),
+                    ))
+                }
+                _ => None,
+            }
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, info)| info)
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension() == Some(extension.as_ref())
+}
+
+fn read_csv(path: &Path) -> Result<(Vec<String>, Vec<Vec<String>>), Error> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+    let header = reader.headers()?.iter().map(String::from).collect();
+    let mut rows = vec![];
+    for record in reader.records() {
+        rows.push(record?.iter().map(String::from).collect());
+    }
+
+    Ok((header, rows))
+}
+
+fn table(header: &[String], rows: &[Vec<String>]) -> String {
+    let mut table = String::from("<table>\n<tr>");
+    for column in header {
+        table.push_str(&format!("<th>{}</th>", escape(column)));
+    }
+    table.push_str("</tr>\n");
+    for row in rows {
+        table.push_str("<tr>");
+        for field in row {
+            table.push_str(&format!("<td>{}</td>", escape(field)));
+        }
+        table.push_str("</tr>\n");
+    }
+    table.push_str("</table>\n");
+
+    table
+}
+
+fn key_value_table(pairs: &[(String, String)]) -> String {
+    let mut table = String::from("<table>\n");
+    for (key, value) in pairs {
+        table.push_str(&format!(
+            "<tr><th>{}</th><td>{}</td></tr>\n",
+            escape(key),
+            escape(value)
+        ));
+    }
+    table.push_str("</table>\n");
+
+    table
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// encode the bytes as base64, to inline the bitmap charts
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len() / 3 * 4 + 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * idx) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fs::TempDir;
+    use std::fs::write;
+
+    #[test]
+    fn base64_test() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn generate_test() -> Result<(), Error> {
+        let dir = TempDir::new("report")?;
+        // a result of an earlier run in the log path is not reported
+        write(
+            dir.0.join("btrfs_ops_per_second.csv"),
+            "operation\nearlier\n",
+        )?;
+        let config = Config::builder(&dir.0)
+            .filesystem("ext4", dir.0.join("ext4"))
+            .filesystem("xfs", dir.0.join("xfs"))
+            .build()?;
+        let mut manifest = Manifest::new(&BenchMode::Static, &dir.0);
+        let report = Report::new(&BenchMode::Static, &config);
+
+        for (fs_name, ops_s) in [("ext4", "100,90,110"), ("xfs", "200,180,220")] {
+            write(
+                dir.0.join(format!("{}_ops_per_second.csv", fs_name)),
+                format!("operation,ops/s,ops/s_lb,ops/s_ub\nmknod,{}\n", ops_s),
+            )?;
+        }
+        write(
+            dir.0.join("ext4_ops_per_second.svg"),
+            "<?xml version=\"1.0\"?>\n<svg></svg>",
+        )?;
+        manifest.generate()?;
+        let html = read_to_string(report.generate(&manifest)?)?;
+
+        // the ops/s are compared side by side, and the charts are inlined without the XML
+        // declaration
+        assert!(html.contains("<td>mknod</td><td>100 [90, 110]</td><td>200 [180, 220]</td>"));
+        assert!(html.contains("<figure>\n<svg></svg>\n<figcaption>ext4_ops_per_second.svg"));
+        assert!(html.contains("<a href=\"xfs_ops_per_second.csv\">"));
+        assert!(!html.contains("earlier"));
+        assert!(!html.contains("btrfs_ops_per_second.csv"));
+
+        Ok(())
+    }
+}
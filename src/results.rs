@@ -0,0 +1,857 @@
+use crate::error::Error;
+use crate::micro::BenchFn;
+use crate::{BenchMode, BenchResult, ResultMode};
+use csv::{StringRecord, WriterBuilder};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{read_dir, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+///
+/// The version of the result schema, i.e. the result kinds and their columns. It is increased
+/// whenever a column is added, removed or changes its meaning, so that the results of another
+/// version are not read wrongly.
+///
+pub const SCHEMA_VERSION: u32 = 2;
+
+// the name of the manifest in the log path
+const MANIFEST_NAME: &str = "manifest.json";
+
+// the time units of the time columns, e.g. "time (ms)", and their length in seconds
+const TIME_UNITS: [(&str, f64); 4] = [("ns", 1e-9), ("us", 1e-6), ("ms", 1e-3), ("s", 1.0)];
+
+///
+/// The ops/s of a benchmark function on a filesystem, or of one of its repeated runs
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpsPerSecond {
+    pub operation: String, // the benchmark function, or the run
+    #[serde(rename = "runtime(s)")]
+    pub run_time: f64,
+    #[serde(rename = "ops/s")]
+    pub ops_per_second: f64,
+    #[serde(rename = "ops/s_lb")]
+    pub ops_per_second_lb: f64,
+    #[serde(rename = "ops/s_ub")]
+    pub ops_per_second_ub: f64,
+    pub ops: u64,
+    pub failures: u64,
+    pub confidence_level: f64,
+    pub ci_method: String,
+    pub median: f64,
+    pub iqr: f64,
+    pub trimmed_mean: f64,
+    pub min: f64,
+    pub max: f64,
+    pub outliers: usize,
+    pub outlier_fraction: f64,
+}
+
+///
+/// The read or write throughput of a file size
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Throughput {
+    #[serde(rename = "file_size (MiB)")]
+    pub file_size: f64,
+    #[serde(rename = "throughput (MiB/s)")]
+    pub throughput: f64,
+}
+
+///
+/// The operations finished in a time window of a run
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Behaviour {
+    pub time: f64, // the end of the time window, in seconds since the start
+    #[serde(rename = "ops")]
+    pub ops_per_second: f64,
+    #[serde(rename = "bytes/s")]
+    pub bytes_per_second: Option<f64>, // only for the operations that read or write
+    #[serde(rename = "ops_ma")]
+    pub ops_per_second_ma: f64,
+    #[serde(rename = "latency_p50(us)")]
+    pub latency_p50: f64,
+    #[serde(rename = "latency_p90(us)")]
+    pub latency_p90: f64,
+    #[serde(rename = "latency_p99(us)")]
+    pub latency_p99: f64,
+}
+
+///
+/// The time of a replayed trace operation
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpTime {
+    pub op: usize,
+    #[serde(rename = "time (s)")]
+    pub time: f64,
+}
+
+///
+/// The average ops/s of a sampling iteration
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SampleOpsPerSecond {
+    #[serde(rename = "iterations")]
+    pub iteration: usize,
+    #[serde(rename = "ops/s")]
+    pub ops_per_second: f64,
+}
+
+///
+/// The time of a replayed trace process, accumulated up to one of its operations
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccumulatedTime {
+    pub pid: String,
+    pub op: usize,
+    #[serde(rename = "accumulated_time (s)")]
+    pub accumulated_time: f64,
+}
+
+///
+/// The costs of the operations on a directory of a number of entries: the latencies of the
+/// creates that filled it up to the entries, of the lookups and of the unlinks that emptied it
+/// from the entries, and the readdir throughput
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DirectoryScaling {
+    pub entries: usize,
+    #[serde(rename = "create (s)")]
+    pub create: f64,
+    #[serde(rename = "create_p99 (s)")]
+    pub create_p99: f64,
+    #[serde(rename = "lookup (s)")]
+    pub lookup: f64,
+    #[serde(rename = "readdir (entries/s)")]
+    pub readdir: f64,
+    #[serde(rename = "unlink (s)")]
+    pub unlink: Option<f64>, // not measured if the benchmark is interrupted before the unlinks
+}
+
+///
+/// The ops/s of a benchmark function on a filesystem across its repeated runs, with the confidence
+/// interval of the mean of the runs
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Repetitions {
+    pub operation: String,
+    pub runs: usize,
+    #[serde(rename = "ops/s")]
+    pub ops_per_second: f64,
+    pub std: f64,
+    pub cv: f64,
+    #[serde(rename = "ops/s_lb")]
+    pub ops_per_second_lb: f64,
+    #[serde(rename = "ops/s_ub")]
+    pub ops_per_second_ub: f64,
+    pub confidence_level: f64,
+}
+
+///
+/// The comparison of the ops/s of a benchmark function on two filesystems and its verdict. The
+/// tests are only run on the ops/s of repeated runs, so they are missing for a single-run estimate.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Significance {
+    pub operation: String,
+    pub fs_a: String,
+    pub fs_b: String,
+    #[serde(rename = "ops/s_a")]
+    pub ops_per_second_a: f64,
+    #[serde(rename = "ops/s_b")]
+    pub ops_per_second_b: f64,
+    pub mann_whitney_u: Option<f64>,
+    pub mann_whitney_p: Option<f64>,
+    pub welch_t: Option<f64>,
+    pub welch_p: Option<f64>,
+    pub difference_lb: Option<f64>,
+    pub difference_ub: Option<f64>,
+    pub verdict: String,
+}
+
+///
+/// The typed records of a result file. The times are in seconds, whatever unit they were logged
+/// in.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "records", rename_all = "snake_case")]
+pub enum Results {
+    OpsPerSecond(Vec<OpsPerSecond>),
+    Throughput(Vec<Throughput>),
+    Behaviour(Vec<Behaviour>),
+    OpTimes(Vec<OpTime>),
+    SampleOpsPerSecond(Vec<SampleOpsPerSecond>),
+    AccumulatedTimes(Vec<AccumulatedTime>),
+    DirectoryScaling(Vec<DirectoryScaling>),
+    Repetitions(Vec<Repetitions>),
+    Significance(Vec<Significance>),
+}
+
+impl Results {
+    pub fn mode(&self) -> ResultMode {
+        match self {
+            Results::OpsPerSecond(_) => ResultMode::OpsPerSecond,
+            Results::Throughput(_) => ResultMode::Throughput,
+            Results::Behaviour(_) => ResultMode::Behaviour,
+            Results::OpTimes(_) => ResultMode::OpTimes,
+            Results::SampleOpsPerSecond(_) => ResultMode::SampleOpsPerSecond,
+            Results::AccumulatedTimes(_) => ResultMode::AccumulatedTimes,
+            Results::DirectoryScaling(_) => ResultMode::DirectoryScaling,
+            Results::Repetitions(_) => ResultMode::Repetitions,
+            Results::Significance(_) => ResultMode::Significance,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Results::OpsPerSecond(records) => records.len(),
+            Results::Throughput(records) => records.len(),
+            Results::Behaviour(records) => records.len(),
+            Results::OpTimes(records) => records.len(),
+            Results::SampleOpsPerSecond(records) => records.len(),
+            Results::AccumulatedTimes(records) => records.len(),
+            Results::DirectoryScaling(records) => records.len(),
+            Results::Repetitions(records) => records.len(),
+            Results::Significance(records) => records.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    ///
+    /// The columns of the result mode, with the times in seconds
+    ///
+    pub fn header(mode: ResultMode) -> Vec<String> {
+        match mode {
+            ResultMode::OpsPerSecond => header::<OpsPerSecond>(),
+            ResultMode::Throughput => header::<Throughput>(),
+            ResultMode::Behaviour => header::<Behaviour>(),
+            ResultMode::OpTimes => header::<OpTime>(),
+            ResultMode::SampleOpsPerSecond => header::<SampleOpsPerSecond>(),
+            ResultMode::AccumulatedTimes => header::<AccumulatedTime>(),
+            ResultMode::DirectoryScaling => header::<DirectoryScaling>(),
+            ResultMode::Repetitions => header::<Repetitions>(),
+            ResultMode::Significance => header::<Significance>(),
+        }
+    }
+
+    ///
+    /// The result mode of a result file, found by its columns, or None if it is not a typed result
+    ///
+    pub fn detect<P: AsRef<Path>>(path: P) -> Result<Option<ResultMode>, Error> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let (header, _) = normalize_header(reader.headers()?);
+
+        Ok([
+            ResultMode::OpsPerSecond,
+            ResultMode::Throughput,
+            ResultMode::Behaviour,
+            ResultMode::OpTimes,
+            ResultMode::SampleOpsPerSecond,
+            ResultMode::AccumulatedTimes,
+            ResultMode::DirectoryScaling,
+            ResultMode::Repetitions,
+            ResultMode::Significance,
+        ]
+        .into_iter()
+        .find(|mode| Self::header(*mode) == header))
+    }
+
+    ///
+    /// Read a result file logged by a benchmark, after checking its columns are those of the
+    /// result mode
+    ///
+    pub fn read_csv<P: AsRef<Path>>(path: P, mode: ResultMode) -> Result<Self, Error> {
+        let results = match mode {
+            ResultMode::OpsPerSecond => Results::OpsPerSecond(read_records(path, mode)?),
+            ResultMode::Throughput => Results::Throughput(read_records(path, mode)?),
+            ResultMode::Behaviour => Results::Behaviour(read_records(path, mode)?),
+            ResultMode::OpTimes => Results::OpTimes(read_records(path, mode)?),
+            ResultMode::SampleOpsPerSecond => {
+                Results::SampleOpsPerSecond(read_records(path, mode)?)
+            }
+            ResultMode::AccumulatedTimes => Results::AccumulatedTimes(read_records(path, mode)?),
+            ResultMode::DirectoryScaling => Results::DirectoryScaling(read_records(path, mode)?),
+            ResultMode::Repetitions => Results::Repetitions(read_records(path, mode)?),
+            ResultMode::Significance => Results::Significance(read_records(path, mode)?),
+        };
+
+        Ok(results)
+    }
+
+    ///
+    /// The typed records of the results of a benchmark, after checking its columns are those of
+    /// the result mode
+    ///
+    pub fn from_bench_result(result: &BenchResult, mode: ResultMode) -> Result<Self, Error> {
+        let header = StringRecord::from(result.header.clone());
+        let records = || {
+            result
+                .records
+                .iter()
+                .map(|record| Ok(StringRecord::from(record.fields.clone())))
+        };
+        let name = mode.to_string();
+        let results = match mode {
+            ResultMode::OpsPerSecond => {
+                Results::OpsPerSecond(parse_records(&name, &header, records(), mode)?)
+            }
+            ResultMode::Throughput => {
+                Results::Throughput(parse_records(&name, &header, records(), mode)?)
+            }
+            ResultMode::Behaviour => {
+                Results::Behaviour(parse_records(&name, &header, records(), mode)?)
+            }
+            ResultMode::OpTimes => {
+                Results::OpTimes(parse_records(&name, &header, records(), mode)?)
+            }
+            ResultMode::SampleOpsPerSecond => {
+                Results::SampleOpsPerSecond(parse_records(&name, &header, records(), mode)?)
+            }
+            ResultMode::AccumulatedTimes => {
+                Results::AccumulatedTimes(parse_records(&name, &header, records(), mode)?)
+            }
+            ResultMode::DirectoryScaling => {
+                Results::DirectoryScaling(parse_records(&name, &header, records(), mode)?)
+            }
+            ResultMode::Repetitions => {
+                Results::Repetitions(parse_records(&name, &header, records(), mode)?)
+            }
+            ResultMode::Significance => {
+                Results::Significance(parse_records(&name, &header, records(), mode)?)
+            }
+        };
+
+        Ok(results)
+    }
+
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        match self {
+            Results::OpsPerSecond(records) => write_records(path, self.mode(), records),
+            Results::Throughput(records) => write_records(path, self.mode(), records),
+            Results::Behaviour(records) => write_records(path, self.mode(), records),
+            Results::OpTimes(records) => write_records(path, self.mode(), records),
+            Results::SampleOpsPerSecond(records) => write_records(path, self.mode(), records),
+            Results::AccumulatedTimes(records) => write_records(path, self.mode(), records),
+            Results::DirectoryScaling(records) => write_records(path, self.mode(), records),
+            Results::Repetitions(records) => write_records(path, self.mode(), records),
+            Results::Significance(records) => write_records(path, self.mode(), records),
+        }
+    }
+
+    pub fn read_json<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+
+        Ok(())
+    }
+}
+
+///
+/// The typed results of a benchmark run, by the names of the files they are logged to, without
+/// the extension, e.g. ext4_ops_per_second. They are returned by the benchmarks to be used
+/// programmatically, e.g. to assert on the ops/s of a filesystem in its integration tests.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunResults {
+    results: BTreeMap<String, Results>,
+}
+
+impl RunResults {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Log the bench results to the file, and keep their typed records by the file's name
+    ///
+    pub fn log(
+        &mut self,
+        result: &BenchResult,
+        file_name: &PathBuf,
+        mode: ResultMode,
+    ) -> Result<(), Error> {
+        result.log(file_name)?;
+        let name = file_name
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| Error::InvalidPath(file_name.display().to_string()))?;
+        self.results
+            .insert(name.to_string(), Results::from_bench_result(result, mode)?);
+
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Results> {
+        self.results.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Results)> {
+        self.results.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    ///
+    /// The ops/s of the benchmark functions run on the filesystem in static mode
+    ///
+    pub fn ops_per_second(&self, fs_name: &str) -> Option<&[OpsPerSecond]> {
+        match self.get(&format!("{}_ops_per_second", fs_name)) {
+            Some(Results::OpsPerSecond(records)) => Some(records),
+            _ => None,
+        }
+    }
+
+    ///
+    /// The time windows of the benchmark function run on the filesystem
+    ///
+    pub fn behaviour(&self, fs_name: &str, bench_fn: &BenchFn) -> Option<&[Behaviour]> {
+        match self.get(&format!("{}_{}", fs_name, bench_fn)) {
+            Some(Results::Behaviour(records)) => Some(records),
+            _ => None,
+        }
+    }
+
+    ///
+    /// The read or write throughput of the filesystem per file size
+    ///
+    pub fn throughput(&self, fs_name: &str, bench_fn: &BenchFn) -> Option<&[Throughput]> {
+        match self.get(&format!("{}_{}_throughput", fs_name, bench_fn)) {
+            Some(Results::Throughput(records)) => Some(records),
+            _ => None,
+        }
+    }
+
+    ///
+    /// The ops/s of the benchmark functions on the filesystem across their repeated runs
+    ///
+    pub fn repetitions(&self, fs_name: &str) -> Option<&[Repetitions]> {
+        match self.get(&format!("{}_repetitions", fs_name)) {
+            Some(Results::Repetitions(records)) => Some(records),
+            _ => None,
+        }
+    }
+
+    ///
+    /// The comparisons of the filesystems for each benchmark function
+    ///
+    pub fn significance(&self) -> Option<&[Significance]> {
+        match self.get("significance") {
+            Some(Results::Significance(records)) => Some(records),
+            _ => None,
+        }
+    }
+
+    ///
+    /// The costs of the directory operations on the filesystem per directory size
+    ///
+    pub fn directory_scaling(&self, fs_name: &str) -> Option<&[DirectoryScaling]> {
+        match self.get(&format!("{}_directory", fs_name)) {
+            Some(Results::DirectoryScaling(records)) => Some(records),
+            _ => None,
+        }
+    }
+}
+
+///
+/// The manifest of the typed result files logged by a benchmark run, written to the log path as
+/// manifest.json, so that the results can be loaded without knowing the file names. The other
+/// files of the run, e.g. the charts, are listed by their names.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub schema_version: u32,
+    pub fs_bench_version: String,
+    pub bench_mode: String,
+    pub files: Vec<ManifestEntry>,
+    #[serde(default)]
+    pub other_files: Vec<String>,
+    #[serde(skip)]
+    log_path: PathBuf,
+    // the files in the log path before the run, with their modification times and lengths, so
+    // that only the files the run logged are listed
+    #[serde(skip)]
+    earlier_files: BTreeMap<PathBuf, (SystemTime, u64)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub file: String, // the file name in the log path
+    pub kind: ResultMode,
+    pub rows: usize,
+}
+
+impl Manifest {
+    ///
+    /// Start the manifest of a run logging to the log path, before the benchmark is run
+    ///
+    pub fn new<P: AsRef<Path>>(bench_mode: &BenchMode, log_path: P) -> Self {
+        let log_path = log_path.as_ref().to_path_buf();
+        // the log path may only be created by the run
+        let earlier_files = log_files(&log_path).unwrap_or_default();
+
+        Self {
+            schema_version: SCHEMA_VERSION,
+            fs_bench_version: env!("CARGO_PKG_VERSION").to_string(),
+            bench_mode: bench_mode.to_string(),
+            files: vec![],
+            other_files: vec![],
+            log_path,
+            earlier_files,
+        }
+    }
+
+    ///
+    /// List the files logged to the log path since the manifest was started, i.e. the new files
+    /// and those modified since, and write the manifest to the log path
+    ///
+    pub fn generate(&mut self) -> Result<PathBuf, Error> {
+        let manifest_path = self.log_path.join(MANIFEST_NAME);
+        self.files.clear();
+        self.other_files.clear();
+        for (path, modified) in log_files(&self.log_path)? {
+            if self.earlier_files.get(&path) == Some(&modified) || path == manifest_path {
+                continue;
+            }
+
+            let file = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| Error::InvalidPath(path.display().to_string()))?
+                .to_string();
+            let kind = if path.extension() == Some("csv".as_ref()) {
+                Results::detect(&path)?
+            } else {
+                None
+            };
+            match kind {
+                Some(kind) => {
+                    let rows = csv::Reader::from_path(&path)?.records().count();
+                    self.files.push(ManifestEntry { file, kind, rows });
+                }
+                None => self.other_files.push(file),
+            }
+        }
+
+        let writer = BufWriter::new(File::create(&manifest_path)?);
+        serde_json::to_writer_pretty(writer, &self)?;
+
+        Ok(manifest_path)
+    }
+
+    ///
+    /// The paths of the files of the run, the typed results and the others, sorted by name
+    ///
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let mut paths = self
+            .files
+            .iter()
+            .map(|entry| &entry.file)
+            .chain(self.other_files.iter())
+            .map(|file| self.log_path.join(file))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        paths
+    }
+
+    ///
+    /// Read the manifest of the results in the log path, after checking its schema version
+    ///
+    pub fn read<P: AsRef<Path>>(log_path: P) -> Result<Self, Error> {
+        let manifest_path = log_path.as_ref().join(MANIFEST_NAME);
+        let reader = BufReader::new(File::open(&manifest_path)?);
+        let mut manifest: Manifest = serde_json::from_reader(reader)?;
+        manifest.log_path = log_path.as_ref().to_path_buf();
+        if manifest.schema_version != SCHEMA_VERSION {
+            return Err(Error::format(
+                "manifest",
+                format!(
+                    "{} has the schema version {}, while this fs-bench reads version {}",
+                    manifest_path.display(),
+                    manifest.schema_version,
+                    SCHEMA_VERSION
+                ),
+            ));
+        }
+
+        Ok(manifest)
+    }
+
+    ///
+    /// Read the manifest of the results in the log path, and all the results it lists, by their
+    /// file names
+    ///
+    pub fn load<P: AsRef<Path>>(log_path: P) -> Result<Vec<(String, Results)>, Error> {
+        let log_path = log_path.as_ref();
+        Self::read(log_path)?
+            .files
+            .into_iter()
+            .map(|entry| {
+                let results = Results::read_csv(log_path.join(&entry.file), entry.kind)?;
+                if results.len() != entry.rows {
+                    return Err(Error::format(
+                        &entry.file,
+                        format!(
+                            "the manifest lists {} rows, but there are {}",
+                            entry.rows,
+                            results.len()
+                        ),
+                    ));
+                }
+                Ok((entry.file, results))
+            })
+            .collect()
+    }
+}
+
+// the files in the log path, with their modification times and lengths
+fn log_files(log_path: &Path) -> Result<BTreeMap<PathBuf, (SystemTime, u64)>, Error> {
+    let mut files = BTreeMap::new();
+    for entry in read_dir(log_path)? {
+        let path = entry?.path();
+        let metadata = path.metadata()?;
+        if metadata.is_file() {
+            files.insert(path, (metadata.modified()?, metadata.len()));
+        }
+    }
+
+    Ok(files)
+}
+
+// the header of the records of a type, as written by serde
+fn header<T: Serialize + Default>() -> Vec<String> {
+    let mut writer = WriterBuilder::new().from_writer(vec![]);
+    // serializing to memory only fails for the types that are not records
+    writer.serialize(T::default()).unwrap();
+    let data = writer.into_inner().unwrap();
+
+    let mut reader = csv::Reader::from_reader(data.as_slice());
+    reader
+        .headers()
+        .map(|header| header.iter().map(|column| column.to_string()).collect())
+        .unwrap_or_default()
+}
+
+// the header with the time columns in seconds, e.g. "time (s)" for "time (ms)", and the factor
+// to convert each column to seconds, and with the run column of the runs' ops/s named operation
+fn normalize_header(header: &StringRecord) -> (Vec<String>, Vec<Option<f64>>) {
+    header
+        .iter()
+        .enumerate()
+        .map(|(idx, column)| {
+            if idx == 0 && column == "run" {
+                return ("operation".to_string(), None);
+            }
+
+            let time_column = column.strip_suffix(')').and_then(|column| {
+                let (name, unit) = column.rsplit_once(" (")?;
+                let (_, factor) = TIME_UNITS
+                    .iter()
+                    .find(|(time_unit, _)| *time_unit == unit)?;
+                Some((format!("{} (s)", name), *factor))
+            });
+            match time_column {
+                Some((column, factor)) => (column, Some(factor)),
+                None => (column.to_string(), None),
+            }
+        })
+        .unzip()
+}
+
+fn read_records<T: DeserializeOwned, P: AsRef<Path>>(
+    path: P,
+    mode: ResultMode,
+) -> Result<Vec<T>, Error> {
+    let path = path.as_ref();
+    let mut reader = csv::Reader::from_path(path)?;
+    let header = reader.headers()?.clone();
+    let records = reader.into_records().map(|record| Ok(record?));
+
+    parse_records(&path.display().to_string(), &header, records, mode)
+}
+
+// the typed records of the header and records of a result, after checking the columns are those
+// of the result mode, with the time columns converted to seconds
+fn parse_records<T: DeserializeOwned>(
+    name: &str,
+    header: &StringRecord,
+    records: impl Iterator<Item = Result<StringRecord, Error>>,
+    mode: ResultMode,
+) -> Result<Vec<T>, Error> {
+    let (header, factors) = normalize_header(header);
+    let expected = Results::header(mode);
+    if header != expected {
+        return Err(Error::format(
+            name,
+            format!(
+                "the columns {:?} are not the {} columns {:?}",
+                header, mode, expected
+            ),
+        ));
+    }
+
+    let header = StringRecord::from(header);
+    records
+        .map(|record| {
+            let record = record?
+                .iter()
+                .zip(factors.iter())
+                .map(|(field, factor)| match factor {
+                    // an empty time is a time that was not measured
+                    Some(factor) if !field.is_empty() => {
+                        Ok((field.parse::<f64>()? * factor).to_string())
+                    }
+                    _ => Ok(field.to_string()),
+                })
+                .collect::<Result<StringRecord, Error>>()?;
+            Ok(record.deserialize(Some(&header))?)
+        })
+        .collect()
+}
+
+fn write_records<T: Serialize, P: AsRef<Path>>(
+    path: P,
+    mode: ResultMode,
+    records: &[T],
+) -> Result<(), Error> {
+    // the header is written separately, to be written for no records as well
+    let mut writer = WriterBuilder::new().has_headers(false).from_path(path)?;
+    writer.write_record(Results::header(mode))?;
+    for record in records {
+        writer.serialize(record)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn results_test() -> Result<(), Error> {
+        let dir = std::env::temp_dir().join(format!("fs-bench-results-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        // a file logged before the run is not listed in its manifest
+        std::fs::write(dir.join("earlier.svg"), "<svg></svg>")?;
+        let mut manifest = Manifest::new(&BenchMode::Trace, &dir);
+
+        // a trace's op times logged in milliseconds are read in seconds
+        let path = dir.join("ext4_op_times_trace_workload_j1.csv");
+        let mut file = File::create(&path)?;
+        file.write_all(b"op,time (ms)\n0,1.5\n1,2\n")?;
+        assert_eq!(Results::detect(&path)?, Some(ResultMode::OpTimes));
+        let results = Results::read_csv(&path, ResultMode::OpTimes)?;
+        assert_eq!(
+            results,
+            Results::OpTimes(vec![
+                OpTime {
+                    op: 0,
+                    time: 0.0015
+                },
+                OpTime { op: 1, time: 0.002 }
+            ])
+        );
+        assert!(Results::read_csv(&path, ResultMode::Throughput).is_err());
+
+        // the unlinks of an interrupted directory benchmark are not measured
+        let path = dir.join("ext4_directory.csv");
+        let mut file = File::create(&path)?;
+        file.write_all(
+            b"entries,create (s),create_p99 (s),lookup (s),readdir (entries/s),unlink (s)\n\
+            1000,0.5,2,0.25,1000000,\n",
+        )?;
+        assert_eq!(Results::detect(&path)?, Some(ResultMode::DirectoryScaling));
+        assert_eq!(
+            Results::read_csv(&path, ResultMode::DirectoryScaling)?,
+            Results::DirectoryScaling(vec![DirectoryScaling {
+                entries: 1000,
+                create: 0.5,
+                create_p99: 2.0,
+                lookup: 0.25,
+                readdir: 1e6,
+                unlink: None,
+            }])
+        );
+        std::fs::remove_file(&path)?;
+
+        // the tests of a single-run estimate of a comparison are not run
+        let path = dir.join("significance.csv");
+        let mut file = File::create(&path)?;
+        file.write_all(
+            b"operation,fs_a,fs_b,ops/s_a,ops/s_b,mann_whitney_u,mann_whitney_p,welch_t,welch_p,\
+            difference_lb,difference_ub,verdict\n\
+            read,ext4,xfs,1000,900,,,,,,,single-run estimate\n",
+        )?;
+        assert_eq!(Results::detect(&path)?, Some(ResultMode::Significance));
+        assert_eq!(
+            Results::read_csv(&path, ResultMode::Significance)?,
+            Results::Significance(vec![Significance {
+                operation: "read".to_string(),
+                fs_a: "ext4".to_string(),
+                fs_b: "xfs".to_string(),
+                ops_per_second_a: 1000.0,
+                ops_per_second_b: 900.0,
+                verdict: "single-run estimate".to_string(),
+                ..Default::default()
+            }])
+        );
+        std::fs::remove_file(&path)?;
+
+        // the typed results are written and read back as CSV and JSON
+        let results = Results::Behaviour(vec![Behaviour {
+            time: 0.5,
+            ops_per_second: 100.0,
+            bytes_per_second: None,
+            ops_per_second_ma: 90.0,
+            latency_p50: 10.0,
+            latency_p90: 20.0,
+            latency_p99: 30.0,
+        }]);
+        let path = dir.join("ext4_mknod.csv");
+        results.write_csv(&path)?;
+        assert_eq!(Results::read_csv(&path, ResultMode::Behaviour)?, results);
+        let path = dir.join("ext4_mknod.json");
+        results.write_json(&path)?;
+        assert_eq!(Results::read_json(&path)?, results);
+
+        // the manifest lists the typed results and the other files logged since it was started,
+        // and is only read with the same schema version
+        let manifest_path = manifest.generate()?;
+        assert_eq!(manifest.other_files, vec!["ext4_mknod.json".to_string()]);
+        let loaded = Manifest::load(&dir)?;
+        assert_eq!(loaded.len(), 2);
+        assert!(loaded.contains(&("ext4_mknod.csv".to_string(), results)));
+        let manifest = std::fs::read_to_string(&manifest_path)?.replace(
+            &format!("\"schema_version\": {}", SCHEMA_VERSION),
+            "\"schema_version\": 0",
+        );
+        std::fs::write(&manifest_path, manifest)?;
+        assert!(Manifest::read(&dir).is_err());
+
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
@@ -1,15 +1,61 @@
+use crate::clock::Timestamp;
 use crate::error::Error;
 use rand::Rng;
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use std::fmt::{Display, Formatter};
 use std::ops::Add;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, SystemTime};
+use std::time::Duration;
+
+// the proportion of the lowest and of the highest sample points left out of the trimmed mean
+pub const TRIM_PROPORTION: f64 = 0.1;
 
 /// A collection of data points with some statistical functions on the data
 pub struct Statistics {
     sample: Vec<f64>,
 }
 
+/// The method of calculating the confidence interval of mean
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CiMethod {
+    /// the percentiles of the bootstrap means
+    Percentile,
+    /// the bias-corrected and accelerated percentiles of the bootstrap means
+    Bca,
+    /// Student's t-distribution, which suits a few sample points
+    T,
+}
+
+impl FromStr for CiMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "percentile" => Ok(CiMethod::Percentile),
+            "bca" => Ok(CiMethod::Bca),
+            "t" => Ok(CiMethod::T),
+            _ => Err("valid confidence interval methods are: percentile, bca, t".to_string()),
+        }
+    }
+}
+
+impl Display for CiMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CiMethod::Percentile => write!(f, "percentile"),
+            CiMethod::Bca => write!(f, "bca"),
+            CiMethod::T => write!(f, "t"),
+        }
+    }
+}
+
+/// The result of a two-sample hypothesis test
+pub struct TestResult {
+    pub statistic: f64,
+    pub p_value: f64, // the probability of a difference at least this large if there is none
+}
+
 pub struct Quartiles {
     pub q1: f64, // first quartile, 25% of the data is below this point
     pub q3: f64, // third quartile, 75% of the data lies below this point
@@ -63,6 +109,19 @@ impl Statistics {
         self.variance().sqrt()
     }
 
+    /// Return the standard deviation of sample points with Bessel's correction, which estimates
+    /// the standard deviation of the population the sample points are drawn from
+    pub fn sample_std(&self) -> f64 {
+        let mean = self.mean();
+        let deviations_sum = self
+            .sample
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .fold(0f64, |acc, val| acc + val);
+
+        (deviations_sum / (self.sample.len() - 1) as f64).sqrt()
+    }
+
     ///  Return the coefficient of variation of sample points
     pub fn cv(&self) -> f64 {
         let mean = self.mean();
@@ -113,26 +172,98 @@ impl Statistics {
     /// In Tukey's Method, values less than (25th percentile - 1.5 * IQR) or
     /// greater than (75th percentile + 1.5 * IQR) are considered outliers.
     pub fn outliers(&self) -> Result<Vec<f64>, Error> {
+        let (lower_limit, upper_limit) = self.outlier_limits()?;
+
+        let mut outliers = self
+            .sample
+            .iter()
+            .filter(|val| **val < lower_limit || **val > upper_limit)
+            .cloned()
+            .collect::<Vec<_>>();
+        outliers.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        Ok(outliers)
+    }
+
+    /// Return the sample points without the outliers based on Tukey's Method
+    pub fn without_outliers(&self) -> Result<Statistics, Error> {
+        let (lower_limit, upper_limit) = self.outlier_limits()?;
+
+        let sample = self
+            .sample
+            .iter()
+            .filter(|val| **val >= lower_limit && **val <= upper_limit)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        Statistics::new(&sample)
+    }
+
+    /// Find the lower and upper limits of the non-outlier sample points in Tukey's Method
+    fn outlier_limits(&self) -> Result<(f64, f64), Error> {
         let quartiles = self.quartiles()?;
         let iqr = quartiles.q3 - quartiles.q1;
 
-        let lower_limit = quartiles.q1 - (iqr.clone() * 1.5);
-        let upper_limit = (iqr * 1.5) + quartiles.q3;
+        Ok((quartiles.q1 - iqr * 1.5, quartiles.q3 + iqr * 1.5))
+    }
 
+    /// Return the median of sample points
+    pub fn median(&self) -> f64 {
         let mut data = self.sample.clone();
-        data.push(lower_limit.clone());
-        data.push(upper_limit.clone());
         data.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mid = data.len() / 2;
 
-        let lower_idx = data.iter().position(|val| *val == lower_limit).unwrap();
-        let upper_idx = data.iter().position(|val| *val == upper_limit).unwrap();
+        if mid * 2 == data.len() {
+            (data[mid - 1] + data[mid]) / 2f64
+        } else {
+            data[mid]
+        }
+    }
 
-        // the data lower than the lower_idx and higher than the upper_idx are outliers
-        let mut outliers = vec![];
-        outliers.append(&mut data[0..lower_idx].to_vec());
-        outliers.append(&mut data[upper_idx + 1..].to_vec());
+    /// Return the mean of sample points after dropping the proportion of the smallest and the
+    /// largest points, e.g. 0.1 drops the lowest 10% and the highest 10%
+    pub fn trimmed_mean(&self, proportion: f64) -> f64 {
+        let mut data = self.sample.clone();
+        data.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let trim = (data.len() as f64 * proportion.clamp(0f64, 0.5)) as usize;
+        let trimmed = if trim * 2 < data.len() {
+            &data[trim..data.len() - trim]
+        } else {
+            &data[..]
+        };
 
-        Ok(outliers)
+        trimmed.iter().sum::<f64>() / trimmed.len() as f64
+    }
+
+    /// Return the smallest sample point
+    pub fn min(&self) -> f64 {
+        self.sample.iter().cloned().fold(f64::INFINITY, f64::min)
+    }
+
+    /// Return the largest sample point
+    pub fn max(&self) -> f64 {
+        self.sample
+            .iter()
+            .cloned()
+            .fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    /// Summarise the sample points by statistics that are not dominated by a few extreme points
+    pub fn robust(&self) -> RobustStatistics {
+        let (iqr, outliers) = match (self.iqr(), self.outliers()) {
+            (Ok(iqr), Ok(outliers)) => (iqr, outliers.len()),
+            _ => (0f64, 0),
+        };
+
+        RobustStatistics {
+            median: self.median(),
+            iqr,
+            trimmed_mean: self.trimmed_mean(TRIM_PROPORTION),
+            min: self.min(),
+            max: self.max(),
+            outliers,
+            outlier_fraction: outliers as f64 / self.sample.len() as f64,
+        }
     }
 
     /// Calculate the confidence interval of mean for the sample data using bootstrap sampling.
@@ -165,6 +296,252 @@ impl Statistics {
         Ok((means_sorted[lb_idx], means_sorted[ub_idx], means))
     }
 
+    /// Calculate the bias-corrected and accelerated (BCa) confidence interval of mean from the
+    /// bootstrap means. The percentiles of the bootstrap means are adjusted for the bias of the
+    /// bootstrap means and for the skewness of the sample points, which is estimated by jackknife.
+    pub fn bca_confidence_interval(
+        &self,
+        confidence_level: f64,
+        means: &[f64],
+    ) -> Result<(f64, f64), Error> {
+        if confidence_level <= 0f64 || confidence_level >= 1f64 {
+            return Err(Error::InvalidConfig(
+                "The confidence level should be in range (0, 1)".to_string(),
+            ));
+        }
+
+        let mut means_sorted = means.to_vec();
+        means_sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let len = means_sorted.len();
+
+        // the bias correction: how far the median of the bootstrap means is from the sample mean
+        let mean = self.mean();
+        let below = means_sorted.iter().filter(|m| **m < mean).count() as f64;
+        let equal = means_sorted.iter().filter(|m| **m == mean).count() as f64;
+        let proportion = ((below + equal / 2f64) / len as f64)
+            .clamp(1f64 / len as f64, 1f64 - 1f64 / len as f64);
+        let z0 = normal_quantile(proportion);
+
+        // the acceleration, from the jackknife means with each sample point left out
+        let n = self.sample.len() as f64;
+        let sum: f64 = self.sample.iter().sum();
+        let jackknife = self
+            .sample
+            .iter()
+            .map(|value| (sum - value) / (n - 1f64))
+            .collect::<Vec<_>>();
+        let jackknife_mean = jackknife.iter().sum::<f64>() / n;
+        let numerator: f64 = jackknife.iter().map(|m| (jackknife_mean - m).powi(3)).sum();
+        let denominator: f64 = jackknife
+            .iter()
+            .map(|m| (jackknife_mean - m).powi(2))
+            .sum::<f64>()
+            .powf(1.5);
+        let acceleration = if denominator == 0f64 {
+            0f64
+        } else {
+            numerator / (6f64 * denominator)
+        };
+
+        let adjusted = |alpha: f64| {
+            let z = normal_quantile(alpha);
+            normal_cdf(z0 + (z0 + z) / (1f64 - acceleration * (z0 + z)))
+        };
+        let tail = (1f64 - confidence_level) / 2f64;
+        let lb_idx = ((adjusted(tail) * len as f64).floor() as usize).min(len - 1);
+        let ub_idx = ((adjusted(1f64 - tail) * len as f64).ceil() as usize).min(len - 1);
+
+        Ok((means_sorted[lb_idx], means_sorted[ub_idx]))
+    }
+
+    /// Calculate the confidence interval of mean for the sample data using Student's t-distribution.
+    /// Unlike the bootstrap confidence interval, it is valid for a few independent sample points,
+    /// such as the results of repeated runs.
+    pub fn t_confidence_interval(&self, confidence_level: f64) -> Result<(f64, f64), Error> {
+        if confidence_level <= 0f64 || confidence_level >= 1f64 {
+            return Err(Error::InvalidConfig(
+                "The confidence level should be in range (0, 1)".to_string(),
+            ));
+        }
+
+        let len = self.sample.len() as f64;
+        let t = t_quantile(1f64 - (1f64 - confidence_level) / 2f64, len - 1f64);
+        let margin = t * self.sample_std() / len.sqrt();
+        let mean = self.mean();
+
+        Ok((mean - margin, mean + margin))
+    }
+
+    /// Return the relative half-width of the t-based confidence interval of mean, which is the
+    /// precision of the mean relative to the mean, e.g. 0.05 for mean +- 5%
+    pub fn relative_precision(&self, confidence_level: f64) -> Result<f64, Error> {
+        let (lb, ub) = self.t_confidence_interval(confidence_level)?;
+        let mean = self.mean();
+        if mean == 0f64 {
+            return Ok(f64::INFINITY);
+        }
+
+        Ok((ub - lb) / 2f64 / mean.abs())
+    }
+
+    /// Find the initial transient of the sample points by the MSER-5 rule, and return the number
+    /// of sample points to truncate from the start.
+    ///
+    /// The sample points are grouped into batches of 5, and the truncation point is the number of
+    /// batches d that minimizes the variance of the remaining batch means divided by (k - d)^2,
+    /// where k is the number of batches. Only the first half of the batches are considered.
+    pub fn mser5_truncation(&self) -> usize {
+        const BATCH: usize = 5;
+
+        let batch_means = self
+            .sample
+            .chunks_exact(BATCH)
+            .map(|batch| batch.iter().sum::<f64>() / BATCH as f64)
+            .collect::<Vec<_>>();
+        let batches = batch_means.len();
+        if batches < 2 {
+            return 0;
+        }
+
+        let mut truncation = 0;
+        let mut min_mser = f64::INFINITY;
+        for d in 0..=batches / 2 {
+            let remaining = &batch_means[d..];
+            let len = remaining.len() as f64;
+            let mean = remaining.iter().sum::<f64>() / len;
+            let deviations_sum = remaining
+                .iter()
+                .map(|value| (value - mean).powi(2))
+                .fold(0f64, |acc, val| acc + val);
+            let mser = deviations_sum / (len * len);
+            if mser < min_mser {
+                min_mser = mser;
+                truncation = d;
+            }
+        }
+
+        truncation * BATCH
+    }
+
+    /// Compare the sample points with another sample by the Mann-Whitney U test, which is a
+    /// non-parametric test of whether the values of one sample tend to be larger than the other.
+    /// The p-value is calculated by the normal approximation with tie correction.
+    pub fn mann_whitney_u(&self, other: &Statistics) -> TestResult {
+        let n1 = self.sample.len() as f64;
+        let n2 = other.sample.len() as f64;
+        let n = n1 + n2;
+
+        // rank the combined samples, giving the tied values the average of their ranks
+        let mut combined = self
+            .sample
+            .iter()
+            .map(|value| (*value, true))
+            .chain(other.sample.iter().map(|value| (*value, false)))
+            .collect::<Vec<_>>();
+        combined.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap());
+
+        let mut rank_sum = 0f64; // the sum of the ranks of this sample
+        let mut ties = 0f64; // sum of (t^3 - t) for each group of t tied values
+        let mut idx = 0;
+        while idx < combined.len() {
+            let mut end = idx + 1;
+            while end < combined.len() && combined[end].0 == combined[idx].0 {
+                end += 1;
+            }
+            let rank = (idx + end + 1) as f64 / 2f64;
+            rank_sum += rank * combined[idx..end].iter().filter(|(_, own)| *own).count() as f64;
+            let tied = (end - idx) as f64;
+            ties += tied.powi(3) - tied;
+            idx = end;
+        }
+
+        let u = rank_sum - n1 * (n1 + 1f64) / 2f64;
+        let mean = n1 * n2 / 2f64;
+        let std = (n1 * n2 / 12f64 * ((n + 1f64) - ties / (n * (n - 1f64)))).sqrt();
+        let p_value = if std == 0f64 {
+            1f64
+        } else {
+            // with continuity correction
+            let z = ((u - mean).abs() - 0.5).max(0f64) / std;
+            2f64 * (1f64 - normal_cdf(z))
+        };
+
+        TestResult {
+            statistic: u,
+            p_value: p_value.min(1f64),
+        }
+    }
+
+    /// Compare the mean of the sample points with the mean of another sample by Welch's t-test,
+    /// which does not assume that the two samples have the same variance
+    pub fn welch_t_test(&self, other: &Statistics) -> TestResult {
+        let n1 = self.sample.len() as f64;
+        let n2 = other.sample.len() as f64;
+        let v1 = self.sample_std().powi(2) / n1;
+        let v2 = other.sample_std().powi(2) / n2;
+        let diff = self.mean() - other.mean();
+
+        if v1 + v2 == 0f64 {
+            // no variation in either sample, so any difference is certain
+            return TestResult {
+                statistic: if diff == 0f64 { 0f64 } else { f64::INFINITY },
+                p_value: if diff == 0f64 { 1f64 } else { 0f64 },
+            };
+        }
+
+        let t = diff / (v1 + v2).sqrt();
+        // the Welch-Satterthwaite degrees of freedom
+        let df = (v1 + v2).powi(2) / (v1.powi(2) / (n1 - 1f64) + v2.powi(2) / (n2 - 1f64));
+
+        TestResult {
+            statistic: t,
+            p_value: 2f64 * (1f64 - t_cdf(t.abs(), df)),
+        }
+    }
+
+    /// Calculate the confidence interval of the difference between the mean of the sample points
+    /// and the mean of another sample by resampling both samples with replacement
+    pub fn difference_confidence_interval(
+        &self,
+        other: &Statistics,
+        confidence_level: f64,
+        iterations: usize,
+    ) -> Result<(f64, f64), Error> {
+        if confidence_level <= 0f64 || confidence_level >= 1f64 {
+            return Err(Error::InvalidConfig(
+                "The confidence level should be in range (0, 1)".to_string(),
+            ));
+        }
+        if iterations == 0 {
+            return Err(Error::InvalidConfig(
+                "The bootstrap iterations should be at least 1".to_string(),
+            ));
+        }
+
+        let mut differences = (0..iterations)
+            .into_par_iter()
+            .map(|_| self.resample_mean() - other.resample_mean())
+            .collect::<Vec<_>>();
+        differences.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let tail = (1f64 - confidence_level) / 2f64;
+        let lb_idx = (tail * iterations as f64).floor() as usize;
+        let ub_idx = (((1f64 - tail) * iterations as f64).ceil() as usize).min(iterations - 1);
+
+        Ok((differences[lb_idx], differences[ub_idx]))
+    }
+
+    // the mean of a resample of the sample points drawn with replacement
+    fn resample_mean(&self) -> f64 {
+        let len = self.sample.len();
+        let mut rng = rand::thread_rng();
+        let sum = (0..len)
+            .map(|_| self.sample[rng.gen_range(0..len)])
+            .fold(0f64, |acc, val| acc + val);
+
+        sum / len as f64
+    }
+
     /// Bootstrap Sampling
     /// Bootstrap Sampling is a method that involves drawing of sample data repeatedly with
     /// replacement, from the sample points to estimate a population parameter (https://www.analyticsvidhya.com/blog/2020/02/what-is-bootstrap-sampling-in-statistics-and-machine-learning/)
@@ -173,14 +550,6 @@ impl Statistics {
     fn bootstrap(&self, iterations: usize) -> Result<Vec<f64>, Error> {
         let len = self.sample.len();
 
-        // The output of this method is a vector of size at least 30 so that we can use the z-scores
-        // for calculating confidence interval, otherwise we have to use t-values.
-        if len < 30 {
-            return Err(Error::InvalidConfig(
-                "The sample size is less than 30".to_string(),
-            ));
-        }
-
         let resample_means = Arc::new(Mutex::new(vec![]));
         (0..iterations).into_par_iter().for_each(|_| {
             let mut resample = vec![];
@@ -198,8 +567,39 @@ impl Statistics {
         Ok(resample_means)
     }
 
-    pub fn analyse(&self) -> Result<AnalysedData, Error> {
-        let (mean_lb, mean_ub, sample_means) = self.mean_confidence_interval(0.95, 1000)?;
+    /// Analyse the sample points by the mean of bootstrap means and the confidence interval of
+    /// mean calculated by the method. The robust statistics are reported for all the sample
+    /// points, while the outliers are left out of the analysis if exclude_outliers is set.
+    pub fn analyse(
+        &self,
+        confidence_level: f64,
+        iterations: usize,
+        method: CiMethod,
+        exclude_outliers: bool,
+    ) -> Result<AnalysedData, Error> {
+        let robust = self.robust();
+        if exclude_outliers && robust.outliers > 0 {
+            let mut analysed_data =
+                self.without_outliers()?
+                    .analyse(confidence_level, iterations, method, false)?;
+            analysed_data.robust = robust;
+            analysed_data.outliers_excluded = true;
+            return Ok(analysed_data);
+        }
+
+        let (mean_lb, mean_ub, sample_means) = match method {
+            CiMethod::Percentile => self.mean_confidence_interval(confidence_level, iterations)?,
+            CiMethod::Bca => {
+                let sample_means = self.bootstrap(iterations)?;
+                let (mean_lb, mean_ub) =
+                    self.bca_confidence_interval(confidence_level, &sample_means)?;
+                (mean_lb, mean_ub, sample_means)
+            }
+            CiMethod::T => {
+                let (mean_lb, mean_ub) = self.t_confidence_interval(confidence_level)?;
+                (mean_lb, mean_ub, self.bootstrap(iterations)?)
+            }
+        };
 
         let mean = Statistics::new(&sample_means)?.mean();
 
@@ -208,30 +608,98 @@ impl Statistics {
             mean_lb: mean_lb.floor(),
             mean_ub: mean_ub.floor(),
             sample_means,
+            confidence_level,
+            method,
+            robust,
+            outliers_excluded: false,
         })
     }
 
     ///
     /// count the number of operations in a time window
-    /// the time window length is in milliseconds
-    /// the input times contains the timestamps in unix_time format. The first 10 digits are
-    /// date and time in seconds and the last 9 digits show the milliseconds
+    /// the input times contains the raw monotonic timestamps of the finished operations
     ///
     /// The output is a list of tuples including operation per seconds in a specific time: (time, ops_per_second)
     ///
     pub fn ops_in_window(
-        times: &Vec<SystemTime>,
+        times: &Vec<Timestamp>,
+        duration: Duration,
+        window: Duration,
+    ) -> Result<Vec<(f64, f64)>, Error> {
+        Ok(Statistics::time_windows(times, duration, window)?
+            .iter()
+            .map(|time_window| (time_window.time, time_window.ops_per_second))
+            .collect())
+    }
+
+    ///
+    /// Split the timeline of the finished operations into time windows of the window length,
+    /// over at most the duration from the first operation. The latencies of the operations in a
+    /// window are the times between the consecutive finished operations, as the operations run
+    /// one after another.
+    ///
+    pub fn time_windows(
+        times: &[Timestamp],
         duration: Duration,
-    ) -> Result<Vec<(f64, usize)>, Error> {
+        window: Duration,
+    ) -> Result<Vec<TimeWindow>, Error> {
+        if times.is_empty() || window.is_zero() {
+            return Err(Error::InvalidConfig(
+                "no operations or an empty time window to count the operations in".to_string(),
+            ));
+        }
+
         let len = times.len();
         let first = times[0]; // first timestamp
         let mut last = times[len - 1]; // last timestamp
-        if last.duration_since(first)? > duration {
+        if last.duration_since(first) > duration {
             last = first.add(duration);
         }
 
-        // decide about the window length in millis
-        let duration = last.duration_since(first)?.as_secs_f64();
+        let mut time_windows = vec![];
+        let window_length = window.as_secs_f64();
+        let latencies = |from: usize, to: usize| {
+            (from.max(1)..to)
+                .map(|idx| times[idx].duration_since(times[idx - 1]).as_secs_f64())
+                .collect::<Vec<_>>()
+        };
+
+        let mut next = first.add(window);
+        let mut idx = 0;
+        while next < last {
+            let from = idx;
+            while times[idx] < next {
+                // count ops in this time window
+                idx += 1;
+            }
+            time_windows.push(TimeWindow {
+                time: next.duration_since(first).as_secs_f64(),
+                ops_per_second: (idx - from) as f64 / window_length,
+                latencies: latencies(from, idx),
+            });
+
+            // go the next time window
+            next = next.add(window);
+        }
+
+        // count the remaining
+        if idx < len {
+            time_windows.push(TimeWindow {
+                time: last.duration_since(first).as_secs_f64(),
+                ops_per_second: (len - idx) as f64 / window_length,
+                latencies: latencies(idx, len),
+            });
+        }
+
+        Ok(time_windows)
+    }
+
+    ///
+    /// The default time window length for a timeline of the duration, from 2 ms for the timelines
+    /// shorter than 0.5 s to 5 s for the timelines longer than 300 s
+    ///
+    pub fn window_length(duration: Duration) -> Duration {
+        let duration = duration.as_secs_f64();
         let window = if duration < 0.5 {
             2
         } else if duration < 1f64 {
@@ -258,38 +726,204 @@ impl Statistics {
             5000
         };
 
-        let mut ops_in_window = vec![];
+        Duration::from_millis(window)
+    }
+}
 
-        let mut next = first.add(Duration::from_millis(window));
-        let mut idx = 0;
-        let mut ops = 0;
-        while next < last {
-            while times[idx] < next {
-                // count ops in this time window
-                ops += 1;
-                idx += 1;
-            }
-            let time = next.duration_since(first)?.as_secs_f64();
-            // we have counted ops in a window length milliseconds, so the ops in
-            // a second is (ops * 1000) / window
-            let ops_per_second = (ops * 1000) / window as usize;
-            ops_in_window.push((time, ops_per_second));
+///
+/// The cumulative distribution function of the standard normal distribution
+///
+pub fn normal_cdf(z: f64) -> f64 {
+    0.5 * erfc(-z / 2f64.sqrt())
+}
 
-            // go the next time window
-            next = next.add(Duration::from_millis(window));
-            ops = 0;
+// the complementary error function with fractional error less than 1.2e-7
+// (Numerical Recipes, section 6.2)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1f64 / (1f64 + 0.5 * z);
+    let ans = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if x >= 0f64 {
+        ans
+    } else {
+        2f64 - ans
+    }
+}
+
+///
+/// The quantile function (inverse of the cdf) of the standard normal distribution
+///
+pub fn normal_quantile(p: f64) -> f64 {
+    if p == 0.5 {
+        return 0f64;
+    }
+    if p < 0.5 {
+        return -normal_quantile(1f64 - p);
+    }
+
+    // the cdf is monotonic, so the quantile is found by bisection
+    let mut lb = 0f64;
+    let mut ub = 40f64;
+    for _ in 0..200 {
+        let mid = (lb + ub) / 2f64;
+        if normal_cdf(mid) < p {
+            lb = mid;
+        } else {
+            ub = mid;
         }
+    }
 
-        // count the remaining
-        if idx < len {
-            ops = len - idx;
-            let time = last.duration_since(first)?.as_secs_f64();
-            let ops_per_second = (ops * 1000) / window as usize;
-            ops_in_window.push((time, ops_per_second));
+    (lb + ub) / 2f64
+}
+
+///
+/// The cumulative distribution function of Student's t-distribution with df degrees of freedom
+///
+pub fn t_cdf(t: f64, df: f64) -> f64 {
+    let x = df / (df + t * t);
+    let tail = 0.5 * incomplete_beta(df / 2f64, 0.5, x);
+    if t > 0f64 {
+        1f64 - tail
+    } else {
+        tail
+    }
+}
+
+///
+/// The quantile function (inverse of the cdf) of Student's t-distribution with df degrees of freedom
+///
+pub fn t_quantile(p: f64, df: f64) -> f64 {
+    if p == 0.5 {
+        return 0f64;
+    }
+    if p < 0.5 {
+        return -t_quantile(1f64 - p, df);
+    }
+
+    // the cdf is monotonic, so the quantile is found by bisection
+    let mut lb = 0f64;
+    let mut ub = 1f64;
+    while t_cdf(ub, df) < p {
+        ub *= 2f64;
+    }
+    for _ in 0..200 {
+        let mid = (lb + ub) / 2f64;
+        if t_cdf(mid, df) < p {
+            lb = mid;
+        } else {
+            ub = mid;
         }
+    }
 
-        Ok(ops_in_window)
+    (lb + ub) / 2f64
+}
+
+// the regularized incomplete beta function I_x(a, b), evaluated by its continued fraction
+// (Numerical Recipes, section 6.4)
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0f64 {
+        return 0f64;
     }
+    if x >= 1f64 {
+        return 1f64;
+    }
+
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1f64 - x).ln()).exp();
+    // the continued fraction converges fast for x < (a + 1) / (a + b + 2), otherwise use the
+    // symmetry I_x(a, b) = 1 - I_(1-x)(b, a)
+    if x < (a + 1f64) / (a + b + 2f64) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1f64 - front * beta_continued_fraction(b, a, 1f64 - x) / b
+    }
+}
+
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+
+    let mut c = 1f64;
+    let mut d = 1f64 - (a + b) * x / (a + 1f64);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1f64 / d;
+    let mut h = d;
+
+    for m in 1..300 {
+        let m = m as f64;
+        // the even step
+        let num = m * (b - m) * x / ((a + 2f64 * m - 1f64) * (a + 2f64 * m));
+        d = 1f64 + num * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1f64 + num / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1f64 / d;
+        h *= d * c;
+
+        // the odd step
+        let num = -(a + m) * (a + b + m) * x / ((a + 2f64 * m) * (a + 2f64 * m + 1f64));
+        d = 1f64 + num * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1f64 + num / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1f64 / d;
+        let delta = d * c;
+        h *= delta;
+
+        if (delta - 1f64).abs() < 1e-15 {
+            break;
+        }
+    }
+
+    h
+}
+
+// the natural logarithm of the gamma function, using the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+
+    let mut y = x;
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000000000190015;
+    for coefficient in COEFFICIENTS.iter() {
+        y += 1f64;
+        series += coefficient / y;
+    }
+
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// The operations finished in a time window of a timeline
+pub struct TimeWindow {
+    pub time: f64, // the end of the window in seconds from the first operation
+    pub ops_per_second: f64,
+    pub latencies: Vec<f64>, // the latencies of the operations in seconds
 }
 
 pub struct AnalysedData {
@@ -297,4 +931,200 @@ pub struct AnalysedData {
     pub mean_lb: f64,
     pub mean_ub: f64,
     pub sample_means: Vec<f64>,
+    pub confidence_level: f64,
+    pub method: CiMethod, // the method of calculating the confidence interval
+    pub robust: RobustStatistics, // the robust statistics of all the sample points
+    pub outliers_excluded: bool, // whether the outliers are left out of the mean and CI
+}
+
+pub struct RobustStatistics {
+    pub median: f64,
+    pub iqr: f64,
+    pub trimmed_mean: f64, // the mean without the lowest and highest TRIM_PROPORTION of points
+    pub min: f64,
+    pub max: f64,
+    pub outliers: usize,       // the number of outliers by Tukey's Method
+    pub outlier_fraction: f64, // the fraction of the sample points that are outliers
+}
+
+impl AnalysedData {
+    ///
+    /// The confidence interval label, e.g. "95% CI"
+    ///
+    pub fn ci_label(&self) -> String {
+        ci_label(self.confidence_level)
+    }
+}
+
+///
+/// The percentile of the values by the nearest-rank method, e.g. 0.99 for the 99th percentile,
+/// or 0 if there are no values
+///
+pub fn percentile(values: &[f64], percentile: f64) -> f64 {
+    if values.is_empty() {
+        return 0f64;
+    }
+    let mut values = values.to_vec();
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let rank = (percentile * values.len() as f64).ceil() as usize;
+
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+///
+/// The trailing moving average of the values over the length, which is shorter at the start
+///
+pub fn moving_average(values: &[f64], length: usize) -> Vec<f64> {
+    let length = length.max(1);
+    let mut sum = 0f64;
+    let mut averages = vec![];
+    for (idx, value) in values.iter().enumerate() {
+        sum += value;
+        if idx >= length {
+            sum -= values[idx - length];
+        }
+        averages.push(sum / (idx + 1).min(length) as f64);
+    }
+
+    averages
+}
+
+///
+/// The label of a confidence interval with the confidence level, e.g. "95% CI"
+///
+pub fn ci_label(confidence_level: f64) -> String {
+    // rounded to avoid the floating point artifacts, e.g. 0.9 * 100 = 90.00000000000001
+    format!("{}% CI", (confidence_level * 1e6).round() / 1e4)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::clock::Timestamp;
+    use crate::stats::{
+        moving_average, normal_cdf, normal_quantile, percentile, t_cdf, t_quantile, CiMethod,
+        Statistics,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn t_distribution_test() {
+        assert!((t_cdf(0f64, 5f64) - 0.5).abs() < 1e-9);
+        assert!((t_cdf(2.228139, 10f64) - 0.975).abs() < 1e-6);
+        assert!((t_quantile(0.975, 1f64) - 12.706205).abs() < 1e-4);
+        assert!((t_quantile(0.975, 10f64) - 2.228139).abs() < 1e-5);
+        assert!((t_quantile(0.025, 30f64) + 2.042272).abs() < 1e-5);
+    }
+
+    #[test]
+    fn t_confidence_interval_test() {
+        let statistics = Statistics::new(&[10f64, 12f64, 14f64]).unwrap();
+        assert!((statistics.sample_std() - 2f64).abs() < 1e-9);
+
+        // 12 +- 4.302653 * 2 / sqrt(3)
+        let (lb, ub) = statistics.t_confidence_interval(0.95).unwrap();
+        assert!((lb - 7.031725).abs() < 1e-5);
+        assert!((ub - 16.968275).abs() < 1e-5);
+    }
+
+    #[test]
+    fn two_sample_test() {
+        assert!((normal_cdf(1.959964) - 0.975).abs() < 1e-6);
+
+        let a = Statistics::new(&[19f64, 22f64, 16f64, 29f64, 24f64]).unwrap();
+        let b = Statistics::new(&[20f64, 11f64, 17f64, 12f64]).unwrap();
+
+        // t = 2.2831, df = 6.9288, p = 0.0568
+        let welch = a.welch_t_test(&b);
+        assert!((welch.statistic - 2.2831).abs() < 1e-4);
+        assert!((welch.p_value - 0.0568).abs() < 1e-4);
+
+        // U = 17, z = (|17 - 10| - 0.5) / sqrt(20 * 10 / 12) = 1.5922, p = 0.1113
+        let mann_whitney = a.mann_whitney_u(&b);
+        assert_eq!(mann_whitney.statistic, 17f64);
+        assert!((mann_whitney.p_value - 0.1113).abs() < 1e-4);
+
+        let (lb, ub) = a.difference_confidence_interval(&b, 0.95, 1000).unwrap();
+        assert!(lb < 7f64 && 7f64 < ub);
+        assert!(a.difference_confidence_interval(&b, 0.95, 0).is_err());
+    }
+
+    #[test]
+    fn confidence_interval_test() {
+        assert!((normal_quantile(0.975) - 1.959964).abs() < 1e-5);
+
+        // fewer than 30 sample points can be analysed by each method
+        let statistics =
+            Statistics::new(&[12f64, 15f64, 11f64, 14f64, 13f64, 30f64, 12f64, 14f64]).unwrap();
+        for method in [CiMethod::Percentile, CiMethod::Bca, CiMethod::T] {
+            let analysed_data = statistics.analyse(0.9, 2000, method, false).unwrap();
+            assert!(analysed_data.mean_lb <= statistics.mean());
+            assert!(statistics.mean() <= analysed_data.mean_ub);
+            assert_eq!(analysed_data.sample_means.len(), 2000);
+        }
+    }
+
+    #[test]
+    fn mser5_truncation_test() {
+        // a slow start of 10 sample points before the steady state
+        let mut sample = vec![
+            10f64, 20f64, 30f64, 40f64, 50f64, 60f64, 70f64, 80f64, 90f64, 95f64,
+        ];
+        for idx in 0..50 {
+            sample.push(if idx % 2 == 0 { 99f64 } else { 101f64 });
+        }
+        let statistics = Statistics::new(&sample).unwrap();
+        assert_eq!(statistics.mser5_truncation(), 10);
+
+        let steady = Statistics::new(&sample[10..]).unwrap();
+        assert!(steady.relative_precision(0.95).unwrap() < 0.01);
+    }
+
+    #[test]
+    fn robust_statistics_test() {
+        // a single slow window, e.g. a GC pause, among steady windows
+        let sample = [
+            12f64, 10f64, 13f64, 11f64, 100f64, 12f64, 14f64, 11f64, 13f64, 12f64,
+        ];
+        let statistics = Statistics::new(&sample).unwrap();
+        let robust = statistics.robust();
+        assert_eq!(robust.median, 12f64);
+        assert_eq!(robust.iqr, 2f64);
+        assert_eq!(robust.trimmed_mean, 12.25);
+        assert_eq!((robust.min, robust.max), (10f64, 100f64));
+        assert_eq!(robust.outliers, 1);
+        assert_eq!(robust.outlier_fraction, 0.1);
+
+        let analysed_data = statistics.analyse(0.95, 1000, CiMethod::T, true).unwrap();
+        assert!(analysed_data.outliers_excluded);
+        assert_eq!(analysed_data.robust.outliers, 1);
+        assert!(analysed_data.mean_lb <= 12f64 && analysed_data.mean_ub <= 14f64);
+    }
+
+    #[test]
+    fn time_windows_test() {
+        // an op finished every millisecond, counted in 5 ms windows
+        let start = Timestamp::now();
+        let times = (0..10)
+            .map(|ms| start + Duration::from_millis(ms))
+            .collect::<Vec<_>>();
+        let time_windows =
+            Statistics::time_windows(&times, Duration::from_secs(1), Duration::from_millis(5))
+                .unwrap();
+        assert_eq!(time_windows.len(), 2);
+        for time_window in time_windows.iter() {
+            assert!((time_window.ops_per_second - 1000f64).abs() < 1e-9);
+        }
+        assert_eq!(time_windows[0].latencies.len(), 4);
+        assert_eq!(time_windows[1].latencies.len(), 5);
+        assert!((time_windows[1].latencies[0] - 0.001).abs() < 1e-9);
+
+        let values = [5f64, 1f64, 4f64, 2f64, 3f64];
+        assert_eq!(percentile(&values, 0.5), 3f64);
+        assert_eq!(percentile(&values, 0.99), 5f64);
+        assert_eq!(percentile(&[], 0.5), 0f64);
+        assert_eq!(
+            moving_average(&[1f64, 2f64, 3f64, 4f64], 2),
+            vec![1f64, 1.5, 2.5, 3.5]
+        );
+    }
 }
@@ -1,17 +1,23 @@
+use crate::clock::{self, Timestamp};
 use crate::error::Error;
+use crate::failures::Failures;
 use crate::format::{percent_format, time_format, time_format_by_unit, time_unit};
-use crate::fs::Fs;
+use crate::fs::{FileSystem, Fs};
+use crate::integrity::{log_mismatches, Mismatch, Verifier};
+use crate::interrupt;
+use crate::output::{self, outputln};
 use crate::plotter::{Indexes, Plotter};
 use crate::progress::Progress;
-use crate::{Bench, BenchFn, BenchResult, Config, Record};
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::results::RunResults;
+use crate::{Bench, BenchFn, BenchResult, Config, Record, ResultMode};
+use indicatif::ProgressStyle;
+use log::error;
 use rand::RngCore;
 use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 use std::io::{BufWriter, Write};
 use std::path::PathBuf;
 use std::sync::mpsc::channel;
-use std::time::SystemTime;
 use strace_parser::{FileType, Operation, OperationType, Parser, Process};
 use threadpool::ThreadPool;
 
@@ -23,11 +29,12 @@ pub struct TraceWorkloadRunner {
 
 impl Bench for TraceWorkloadRunner {
     fn new(config: Config) -> Result<Self, Error> {
+        output::set_quiet(config.quiet);
         // parse the trace log file and extract the operations
         let mut parser = Parser::new(config.workload.clone());
 
         let style = ProgressStyle::default_bar().template("[{elapsed_precise}] {msg}");
-        let bar = ProgressBar::new_spinner();
+        let bar = output::spinner();
         bar.set_style(style);
         bar.set_message(format!("parsing {}", Fs::path_to_str(&config.workload)?));
         let progress = Progress::start(bar.clone());
@@ -56,14 +63,16 @@ impl Bench for TraceWorkloadRunner {
 
     // create the directory hierarchy of the workload
     fn setup(&self, path: &PathBuf, _invalidate_cache: bool) -> Result<(), Error> {
-        Fs::cleanup(path)?;
+        let fs = self.config.backend.as_ref();
+        Fs::cleanup(fs, path)?;
 
         let style = ProgressStyle::default_bar().template("[{elapsed_precise}] {msg}");
-        let bar = ProgressBar::new_spinner();
+        let bar = output::spinner();
         bar.set_style(style);
         bar.set_message(format!("setting up {}", Fs::path_to_str(path)?));
         let progress = Progress::start(bar.clone());
 
+        let verifier = self.config.verifier();
         for file_type in self.files.iter() {
             match file_type {
                 FileType::File(file_path, size) => {
@@ -73,23 +82,29 @@ impl Bench for TraceWorkloadRunner {
                     parents.pop();
 
                     // create the parent directory hierarchy
-                    if !parents.exists() {
-                        Fs::make_dir_all(&parents)?;
+                    if !fs.exists(&parents) {
+                        fs.mkdir_all(&parents)?;
                     }
 
-                    // create the file and fill it with random content
-                    let mut rand_content = vec![0u8; *size];
-                    let mut rng = rand::thread_rng();
-                    rng.fill_bytes(&mut rand_content);
+                    // create the file and fill it with random content, or with the content
+                    // derived from the seed if the reads are going to be verified
+                    let rand_content = match verifier.as_ref() {
+                        Some(verifier) => verifier.content(file_path, 0, *size)?,
+                        None => {
+                            let mut rand_content = vec![0u8; *size];
+                            let mut rng = rand::thread_rng();
+                            rng.fill_bytes(&mut rand_content);
+                            rand_content
+                        }
+                    };
 
-                    let mut file = Fs::make_file(&new_path)?;
-                    file.write(&mut rand_content)?;
+                    fs.create(&new_path)?.write_all_at(&rand_content, 0)?;
                 }
                 FileType::Dir(dir_path, _) => {
                     let new_path = Fs::map_path(path, &dir_path)?;
                     // create the directory
-                    if !new_path.exists() {
-                        Fs::make_dir_all(&new_path)?;
+                    if !fs.exists(&new_path) {
+                        fs.mkdir_all(&new_path)?;
                     }
                 }
                 _ => {}
@@ -101,7 +116,7 @@ impl Bench for TraceWorkloadRunner {
         Ok(())
     }
 
-    fn run(&self, _bench_fn: Option<BenchFn>) -> Result<(), Error> {
+    fn run(&self, _bench_fn: Option<BenchFn>) -> Result<RunResults, Error> {
         let progress_style = ProgressStyle::default_bar().template("[{elapsed_precise}] {msg}");
 
         let mount_paths = self.config.mount_paths.clone();
@@ -117,7 +132,12 @@ impl Bench for TraceWorkloadRunner {
             .create(true)
             .open(output_path)?;
 
+        let mut run_results = RunResults::new();
         for (idx, mount_path) in mount_paths.iter().enumerate() {
+            if interrupt::interrupted() {
+                break;
+            }
+
             let mut base_path = mount_path.clone();
             base_path.push("trace_workload");
             base_path.push("files");
@@ -148,6 +168,8 @@ impl Bench for TraceWorkloadRunner {
             let summary_header = [
                 "operation".to_string(),
                 format!("time ({})", summary_time_unit),
+                "ops".to_string(),
+                "failures".to_string(),
             ]
             .to_vec();
 
@@ -159,9 +181,20 @@ impl Bench for TraceWorkloadRunner {
                 "{}_op_times_trace_workload_j{}.csv",
                 self.config.fs_names[idx], thread_num
             ));
-            results.log(&file_name)?;
+            run_results.log(&results, &file_name, ResultMode::OpTimes)?;
+
+            let op_times = results
+                .records
+                .iter()
+                .map(|record| record.fields[1].parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()?;
+            let threads = if thread_num == 1 {
+                "1 thread".to_string()
+            } else {
+                format!("{} threads", thread_num)
+            };
 
-            let mut op_times_plotter = Plotter::new();
+            let mut op_times_plotter = Plotter::with_style(self.config.plot_style.clone());
             op_times_plotter.add_coordinates(
                 results.records,
                 None,
@@ -173,18 +206,30 @@ impl Bench for TraceWorkloadRunner {
                 Some(&format!("Time ({})", op_time_unit)),
                 Some(&format!(
                     "Operation times from replayed logs with {} ({})",
-                    if thread_num == 1 {
-                        "1 thread".to_string()
-                    } else {
-                        format!("{} threads", thread_num)
-                    },
-                    self.config.fs_names[idx]
+                    threads, self.config.fs_names[idx]
                 )),
                 false,
                 false,
                 &file_name,
             )?;
 
+            // plot the distribution of the operation times
+            let mut op_times_cdf_plotter = Plotter::with_style(self.config.plot_style.clone());
+            op_times_cdf_plotter.add_distribution(op_times, None, None);
+            let mut file_name = self.config.log_path.clone();
+            file_name.push(format!(
+                "{}_op_times_cdf_j{}.svg",
+                self.config.fs_names[idx], thread_num
+            ));
+            op_times_cdf_plotter.cdf_chart(
+                Some(&format!("Time ({})", op_time_unit)),
+                Some(&format!(
+                    "Operation time distribution with {} ({})",
+                    threads, self.config.fs_names[idx]
+                )),
+                &file_name,
+            )?;
+
             let mut summary_results = BenchResult::new(summary_header.clone());
             summary_results.add_records(op_summary_records)?;
             let mut file_name = self.config.log_path.clone();
@@ -194,7 +239,7 @@ impl Bench for TraceWorkloadRunner {
             ));
             summary_results.log(&file_name)?;
 
-            let mut op_summary_plotter = Plotter::new();
+            let mut op_summary_plotter = Plotter::with_style(self.config.plot_style.clone());
             op_summary_plotter.add_coordinates(
                 summary_results.records,
                 None,
@@ -216,7 +261,7 @@ impl Bench for TraceWorkloadRunner {
                 &file_name,
             )?;
 
-            let mut accumulated_times_plotter = Plotter::new();
+            let mut accumulated_times_plotter = Plotter::with_style(self.config.plot_style.clone());
             let mut accumulated_times_results = BenchResult::new(accumulated_times_header.clone());
             for accumulated_times_records in accumulated_times_records.into_iter() {
                 accumulated_times_results.add_records(accumulated_times_records.clone())?;
@@ -233,7 +278,11 @@ impl Bench for TraceWorkloadRunner {
                 "{}_accumulated_times_j{}.csv",
                 self.config.fs_names[idx], thread_num
             ));
-            accumulated_times_results.log(&file_name)?;
+            run_results.log(
+                &accumulated_times_results,
+                &file_name,
+                ResultMode::AccumulatedTimes,
+            )?;
 
             file_name.set_extension("svg");
             accumulated_times_plotter.line_chart(
@@ -254,12 +303,12 @@ impl Bench for TraceWorkloadRunner {
             )?;
         }
 
-        println!(
+        outputln!(
             "results logged to: {}",
             Fs::path_to_str(&self.config.log_path)?
         );
 
-        Ok(())
+        Ok(run_results)
     }
 }
 
@@ -286,7 +335,7 @@ impl TraceWorkloadRunner {
     > {
         self.setup(&base_path, false)?;
 
-        let bar = ProgressBar::new_spinner();
+        let bar = output::spinner();
         bar.set_style(style);
         bar.set_message(format!(
             "replaying logs with {} ({})",
@@ -299,7 +348,7 @@ impl TraceWorkloadRunner {
         ));
         let progress = Progress::start(bar);
 
-        let start = SystemTime::now();
+        let start = Timestamp::now();
         let mut op_times = vec![];
         let mut accumulated_times = vec![];
         let mut op_summaries: HashMap<String, (f64, u16)> = HashMap::new();
@@ -311,32 +360,55 @@ impl TraceWorkloadRunner {
         let pool = ThreadPool::new(thread_num);
 
         let mut execution_results = vec![];
-        let start_time = SystemTime::now();
+        let mut failures = Failures::new();
+        let verifier = self.config.verifier();
+        // calibrate the timer overhead before the ops are timed
+        clock::overhead();
+        let start_time = Timestamp::now();
         // run the set of processes
+        let sets_num = available_sets.len();
+        let mut replayed_sets = 0;
         for available_set in available_sets {
+            // stop replaying if interrupted, but replay at least one set to have some results
+            if replayed_sets > 0 && interrupt::interrupted() {
+                break;
+            }
+            replayed_sets += 1;
+
             let len = available_set.len();
             for process in available_set {
                 let base_path = base_path.clone();
+                let verifier = verifier.clone();
+                let fs = self.config.backend.clone();
 
                 let tx = tx.clone();
                 pool.execute(move || {
-                    let execution_result = process.run(&base_path, start_time);
+                    let execution_result =
+                        process.run(fs.as_ref(), &base_path, start_time, verifier.as_ref());
                     tx.send(execution_result).unwrap();
                 });
             }
 
             // get the results of the finished processes
             let mut results = rx.iter().take(len).collect::<Vec<_>>();
+            for result in results.iter().flatten() {
+                failures.merge(&result.failures);
+            }
             execution_results.append(&mut results);
+
+            // stop replaying if too many operations have failed so far
+            failures.check(self.config.max_failures)?;
         }
 
         let mut total_op_time = 0f64;
         let mut total_ops = 0;
+        let mut mismatches = vec![];
         for execution_result in execution_results {
             match execution_result {
                 Ok(mut execution_result) => {
                     // let mut execution_result = execution_result?;
                     op_times.append(&mut execution_result.op_times);
+                    mismatches.append(&mut execution_result.mismatches);
 
                     accumulated_times.push((
                         execution_result.pid,
@@ -365,13 +437,20 @@ impl TraceWorkloadRunner {
                     process_summaries
                         .push((execution_result.pid, execution_result.op_summaries.clone()));
                 }
-                Err(_err) => {}
+                Err(err) => {
+                    error!("error: {:?}", err);
+                    failures.add("process", &err);
+                }
             }
         }
 
-        let end = start.elapsed()?.as_secs_f64();
+        let end = start.elapsed().as_secs_f64();
         progress.finish()?;
 
+        if interrupt::interrupted() && !self.config.keep_fileset {
+            Fs::cleanup(self.config.backend.as_ref(), base_path)?;
+        }
+
         let mut op_times_records = vec![];
         let op_time_unit = time_unit(op_times[0]);
         for (idx, time) in op_times.iter().enumerate() {
@@ -384,13 +463,21 @@ impl TraceWorkloadRunner {
             );
         }
 
+        // the operations that have only failed are also included in the summaries
+        for op_name in failures.ops() {
+            op_summaries.entry(op_name).or_insert((0f64, 0));
+        }
+
         let mut op_summaries_records = vec![];
         let summary_time_unit = time_unit(max_summary_time);
-        for (op_name, (t, _n)) in op_summaries {
+        for (op_name, (t, n)) in op_summaries {
+            let failed = failures.op_count(&op_name);
             op_summaries_records.push(
                 vec![
                     op_name,
                     time_format_by_unit(t, summary_time_unit)?.to_string(),
+                    n.to_string(),
+                    failed.to_string(),
                 ]
                 .into(),
             );
@@ -421,14 +508,38 @@ impl TraceWorkloadRunner {
         let mut writer = BufWriter::new(output);
         writer.write(format!("{}\n", fs_name).as_ref())?;
 
-        println!("{:25} {}", "replay time:", time_format(end));
-        println!(
+        if replayed_sets < sets_num {
+            outputln!(
+                "{:25} {} of {} process sets",
+                "interrupted after:",
+                replayed_sets,
+                sets_num
+            );
+            writer.write(
+                format!(
+                    "{:25} {} of {} process sets\n",
+                    "interrupted after:", replayed_sets, sets_num
+                )
+                .as_ref(),
+            )?;
+        }
+        outputln!("{:25} {}", "replay time:", time_format(end));
+        outputln!(
             "{:25} {}",
             "total operations time:",
             time_format(total_op_time)
         );
-        println!("{:25} {}", "total operations: ", total_ops);
-        println!("{:25} {}\n", "total processes: ", process_summaries.len());
+        outputln!(
+            "{:25} {}",
+            "timer overhead:",
+            time_format(clock::overhead().as_secs_f64())
+        );
+        outputln!("{:25} {}", "total operations: ", total_ops);
+        outputln!("{:25} {}", "failed operations: ", failures.count());
+        if verifier.is_some() {
+            outputln!("{:25} {}", "data mismatches: ", mismatches.len());
+        }
+        outputln!("{:25} {}\n", "total processes: ", process_summaries.len());
 
         writer.write(format!("{:25} {}\n", "replay time:", time_format(end)).as_ref())?;
         writer.write(
@@ -439,7 +550,19 @@ impl TraceWorkloadRunner {
             )
             .as_ref(),
         )?;
+        writer.write(
+            format!(
+                "{:25} {}\n",
+                "timer overhead:",
+                time_format(clock::overhead().as_secs_f64())
+            )
+            .as_ref(),
+        )?;
         writer.write(format!("{:25} {}\n", "total operations: ", total_ops).as_ref())?;
+        writer.write(format!("{:25} {}\n", "failed operations: ", failures.count()).as_ref())?;
+        if verifier.is_some() {
+            writer.write(format!("{:25} {}\n", "data mismatches: ", mismatches.len()).as_ref())?;
+        }
         writer.write(
             format!("{:25} {}\n\n", "total processes: ", process_summaries.len()).as_ref(),
         )?;
@@ -463,9 +586,27 @@ impl TraceWorkloadRunner {
             }
         }
 
+        if failures.count() > 0 {
+            failures.print();
+            writer.write(format!("\nfailed operations\n").as_ref())?;
+            for op in failures.ops() {
+                writer.write(format!("{:7} {}\n", failures.op_count(&op), op).as_ref())?;
+            }
+        }
+
         writer.write(format!("\n---------------\n").as_ref())?;
         writer.flush()?;
 
+        if verifier.is_some() {
+            let mut file_name = self.config.log_path.clone();
+            file_name.push(format!("{}_mismatches_j{}.csv", fs_name, thread_num));
+            log_mismatches(mismatches, &file_name)?;
+        }
+
+        let mut file_name = self.config.log_path.clone();
+        file_name.push(format!("{}_failures_j{}.csv", fs_name, thread_num));
+        failures.log(&file_name)?;
+
         Ok((
             op_times_records,
             op_summaries_records,
@@ -482,23 +623,39 @@ struct ExecutionResult {
     op_times: Vec<f64>,
     accumulated_times: Vec<f64>,
     op_summaries: HashMap<String, (f64, u16)>,
+    mismatches: Vec<Mismatch>,
+    failures: Failures,
 }
 
 trait Runner {
-    fn run(&self, base_path: &PathBuf, start_time: SystemTime) -> Result<ExecutionResult, Error>;
+    fn run(
+        &self,
+        fs: &dyn FileSystem,
+        base_path: &PathBuf,
+        start_time: Timestamp,
+        verifier: Option<&Verifier>,
+    ) -> Result<ExecutionResult, Error>;
 }
 
 impl Runner for Process {
-    fn run(&self, base_path: &PathBuf, start_time: SystemTime) -> Result<ExecutionResult, Error> {
+    fn run(
+        &self,
+        fs: &dyn FileSystem,
+        base_path: &PathBuf,
+        start_time: Timestamp,
+        verifier: Option<&Verifier>,
+    ) -> Result<ExecutionResult, Error> {
         let mut op_times = vec![];
         let mut accumulated_times = vec![];
+        let mut mismatches = vec![];
+        let mut failures = Failures::new();
         // summary of operations:
         //      key: operation name
         //      value: a pair of (time spend for this operation so far, number of this operation)
         let mut op_summaries: HashMap<String, (f64, u16)> = HashMap::new();
 
         for op in self.ops() {
-            match op.execute(base_path, start_time) {
+            match op.execute(fs, base_path, start_time, verifier, &mut mismatches) {
                 Ok((op_time, system_time)) => {
                     op_times.push(op_time);
                     accumulated_times.push(system_time);
@@ -509,7 +666,12 @@ impl Runner for Process {
                         op_summaries.insert(op.name(), (op_time, 1));
                     }
                 }
-                Err(_err) => {}
+                // the operations that are not timed are not replayed
+                Err(Error::NoTimeRecord(_)) => {}
+                Err(err) => {
+                    error!("error: {:?}", err);
+                    failures.add(&op.name(), &err);
+                }
             }
         }
 
@@ -518,53 +680,86 @@ impl Runner for Process {
             op_times,
             accumulated_times,
             op_summaries,
+            mismatches,
+            failures,
         })
     }
 }
 
+// the op time in seconds between two timestamps, without the overhead of reading the clock
+fn op_time(begin: Timestamp, end: Timestamp) -> f64 {
+    end.duration_since(begin)
+        .saturating_sub(clock::overhead())
+        .as_secs_f64()
+}
+
 trait Executer {
-    fn execute(&self, base_path: &PathBuf, start_time: SystemTime) -> Result<(f64, f64), Error>;
+    fn execute(
+        &self,
+        fs: &dyn FileSystem,
+        base_path: &PathBuf,
+        start_time: Timestamp,
+        verifier: Option<&Verifier>,
+        mismatches: &mut Vec<Mismatch>,
+    ) -> Result<(f64, f64), Error>;
 }
 
 impl Executer for Operation {
-    fn execute(&self, base_path: &PathBuf, start_time: SystemTime) -> Result<(f64, f64), Error> {
+    fn execute(
+        &self,
+        fs: &dyn FileSystem,
+        base_path: &PathBuf,
+        start_time: Timestamp,
+        verifier: Option<&Verifier>,
+        mismatches: &mut Vec<Mismatch>,
+    ) -> Result<(f64, f64), Error> {
         let (op_time, system_time) = match self.op_type() {
             &OperationType::Mkdir(ref file, ref _mode) => {
                 let path = Fs::map_path(base_path, file.path()?)?;
-                let begin = SystemTime::now();
-                Fs::make_dir(path)?;
-                let now = SystemTime::now();
-                let end = now.duration_since(begin)?.as_secs_f64();
-                let system_time = now.duration_since(start_time)?.as_secs_f64();
+                let begin = Timestamp::now();
+                fs.mkdir(&path)?;
+                let now = Timestamp::now();
+                let end = op_time(begin, now);
+                let system_time = now.duration_since(start_time).as_secs_f64();
                 (end, system_time)
             }
             &OperationType::Mknod(ref file) => {
                 let path = Fs::map_path(base_path, file.path()?)?;
+                // the parent directories missing from the traced tree are created before the
+                // create is timed
+                if let Some(parents) = path.parent() {
+                    if !fs.exists(parents) {
+                        fs.mkdir_all(parents)?;
+                    }
+                }
                 // create a file and sets its size and offset
-                let begin = SystemTime::now();
-                let file = Fs::make_file(path)?;
+                let begin = Timestamp::now();
+                let file_key = file.path()?;
+                let mut file = fs.create(&path)?;
                 file.set_len(0)?;
-                let now = SystemTime::now();
-                let end = now.duration_since(begin)?.as_secs_f64();
-                let system_time = now.duration_since(start_time)?.as_secs_f64();
+                let now = Timestamp::now();
+                let end = op_time(begin, now);
+                let system_time = now.duration_since(start_time).as_secs_f64();
+                if let Some(verifier) = verifier {
+                    verifier.created(file_key)?;
+                }
                 (end, system_time)
             }
             &OperationType::Remove(ref file) => {
                 let path = Fs::map_path(base_path, file.path()?)?;
-                let path = PathBuf::from(path);
-
-                let begin = SystemTime::now();
-                if path.is_dir() {
-                    Fs::remove_dir(&path)?;
-                    let now = SystemTime::now();
-                    let end = now.duration_since(begin)?.as_secs_f64();
-                    let system_time = now.duration_since(start_time)?.as_secs_f64();
+
+                let begin = Timestamp::now();
+                if matches!(fs.stat(&path), Ok(stat) if stat.is_dir) {
+                    fs.remove_dir(&path)?;
+                    let now = Timestamp::now();
+                    let end = op_time(begin, now);
+                    let system_time = now.duration_since(start_time).as_secs_f64();
                     (end, system_time)
                 } else {
-                    Fs::remove_file(&path)?;
-                    let now = SystemTime::now();
-                    let end = now.duration_since(begin)?.as_secs_f64();
-                    let system_time = now.duration_since(start_time)?.as_secs_f64();
+                    fs.remove_file(&path)?;
+                    let now = Timestamp::now();
+                    let end = op_time(begin, now);
+                    let system_time = now.duration_since(start_time).as_secs_f64();
                     (end, system_time)
                 }
             }
@@ -572,80 +767,96 @@ impl Executer for Operation {
                 let path = Fs::map_path(base_path, file.path()?)?;
                 let mut buffer = vec![0u8; *len];
 
-                let mut file = Fs::open_file(path)?;
-                let begin = SystemTime::now();
-                Fs::read_at(&mut file, &mut buffer, *offset as u64)?;
-                let now = SystemTime::now();
-                let end = now.duration_since(begin)?.as_secs_f64();
-                let system_time = now.duration_since(start_time)?.as_secs_f64();
+                let file_key = file.path()?;
+                let mut file = fs.open(&path)?;
+                let begin = Timestamp::now();
+                let read = file.read_at(&mut buffer, *offset as u64)?;
+                let now = Timestamp::now();
+                let end = op_time(begin, now);
+                let system_time = now.duration_since(start_time).as_secs_f64();
+                if let Some(verifier) = verifier {
+                    if let Some(mismatch) =
+                        verifier.verify(file_key, *offset as u64, &buffer[..read])?
+                    {
+                        mismatches.push(mismatch);
+                    }
+                }
                 (end, system_time)
             }
             &OperationType::Write(ref file, ref offset, ref len, ref _content) => {
                 let path = Fs::map_path(base_path, file.path()?)?;
-                let mut rand_content = vec![0u8; *len];
-                let mut rng = rand::thread_rng();
-                rng.fill_bytes(&mut rand_content);
+                // the written content is derived from the seed if the reads are verified, so
+                // that the written range still has the content the reads expect
+                let rand_content = match verifier {
+                    Some(verifier) => verifier.content(file.path()?, *offset as u64, *len)?,
+                    None => {
+                        let mut rand_content = vec![0u8; *len];
+                        let mut rng = rand::thread_rng();
+                        rng.fill_bytes(&mut rand_content);
+                        rand_content
+                    }
+                };
 
-                let mut file = Fs::open_file(path)?;
+                let mut file = fs.open(&path)?;
 
-                let begin = SystemTime::now();
-                Fs::write_at(&mut file, &mut rand_content, *offset as u64)?;
-                let now = SystemTime::now();
-                let end = now.duration_since(begin)?.as_secs_f64();
-                let system_time = now.duration_since(start_time)?.as_secs_f64();
+                let begin = Timestamp::now();
+                file.write_all_at(&rand_content, *offset as u64)?;
+                let now = Timestamp::now();
+                let end = op_time(begin, now);
+                let system_time = now.duration_since(start_time).as_secs_f64();
                 (end, system_time)
             }
             &OperationType::OpenAt(ref file, ref _offset) => {
                 let path = Fs::map_path(base_path, file.path()?)?;
-                let begin = SystemTime::now();
-                if path.is_file() {
-                    Fs::open_file(path)?;
-                    let now = SystemTime::now();
-                    let end = now.duration_since(begin)?.as_secs_f64();
-                    let system_time = now.duration_since(start_time)?.as_secs_f64();
+                let begin = Timestamp::now();
+                if !matches!(fs.stat(&path), Ok(stat) if stat.is_dir) {
+                    fs.open(&path)?;
+                    let now = Timestamp::now();
+                    let end = op_time(begin, now);
+                    let system_time = now.duration_since(start_time).as_secs_f64();
                     (end, system_time)
                 } else {
-                    Fs::open_dir(path)?;
-                    let now = SystemTime::now();
-                    let end = now.duration_since(begin)?.as_secs_f64();
-                    let system_time = now.duration_since(start_time)?.as_secs_f64();
+                    fs.open_dir(&path)?;
+                    let now = Timestamp::now();
+                    let end = op_time(begin, now);
+                    let system_time = now.duration_since(start_time).as_secs_f64();
                     (end, system_time)
                 }
             }
             &OperationType::Truncate(ref file) => {
                 let path = Fs::map_path(base_path, file.path()?)?;
-                let begin = SystemTime::now();
-                Fs::truncate(path)?;
-                let now = SystemTime::now();
-                let end = now.duration_since(begin)?.as_secs_f64();
-                let system_time = now.duration_since(start_time)?.as_secs_f64();
+                let begin = Timestamp::now();
+                fs.truncate(&path, 0)?;
+                let now = Timestamp::now();
+                let end = op_time(begin, now);
+                let system_time = now.duration_since(start_time).as_secs_f64();
                 (end, system_time)
             }
             &OperationType::Stat(ref file) => {
                 let path = Fs::map_path(base_path, file.path()?)?;
-                let begin = SystemTime::now();
-                Fs::metadata(path)?;
-                let now = SystemTime::now();
-                let end = now.duration_since(begin)?.as_secs_f64();
-                let system_time = now.duration_since(start_time)?.as_secs_f64();
+                let begin = Timestamp::now();
+                fs.stat(&path)?;
+                let now = Timestamp::now();
+                let end = op_time(begin, now);
+                let system_time = now.duration_since(start_time).as_secs_f64();
                 (end, system_time)
             }
             &OperationType::Fstat(ref file) => {
                 let path = Fs::map_path(base_path, file.path()?)?;
-                let begin = SystemTime::now();
-                Fs::metadata(path)?;
-                let now = SystemTime::now();
-                let end = now.duration_since(begin)?.as_secs_f64();
-                let system_time = now.duration_since(start_time)?.as_secs_f64();
+                let begin = Timestamp::now();
+                fs.stat(&path)?;
+                let now = Timestamp::now();
+                let end = op_time(begin, now);
+                let system_time = now.duration_since(start_time).as_secs_f64();
                 (end, system_time)
             }
             &OperationType::Statx(ref file) => {
                 let path = Fs::map_path(base_path, file.path()?)?;
-                let begin = SystemTime::now();
-                Fs::metadata(path)?;
-                let now = SystemTime::now();
-                let end = now.duration_since(begin)?.as_secs_f64();
-                let system_time = now.duration_since(start_time)?.as_secs_f64();
+                let begin = Timestamp::now();
+                fs.stat(&path)?;
+                let now = Timestamp::now();
+                let end = op_time(begin, now);
+                let system_time = now.duration_since(start_time).as_secs_f64();
                 (end, system_time)
             }
             &OperationType::StatFS(ref file) => {
@@ -655,34 +866,38 @@ impl Executer for Operation {
             }
             &OperationType::Fstatat(ref file) => {
                 let path = Fs::map_path(base_path, file.path()?)?;
-                let begin = SystemTime::now();
-                Fs::metadata(path)?;
-                let now = SystemTime::now();
-                let end = now.duration_since(begin)?.as_secs_f64();
-                let system_time = now.duration_since(start_time)?.as_secs_f64();
+                let begin = Timestamp::now();
+                fs.stat(&path)?;
+                let now = Timestamp::now();
+                let end = op_time(begin, now);
+                let system_time = now.duration_since(start_time).as_secs_f64();
                 (end, system_time)
             }
             &OperationType::Rename(ref file, ref to) => {
+                let to_key = to;
                 let from = Fs::map_path(base_path, file.path()?)?;
                 let to = Fs::map_path(base_path, to)?;
 
-                let begin = SystemTime::now();
-                Fs::rename(&from, &to)?;
-                let now = SystemTime::now();
-                let end = now.duration_since(begin)?.as_secs_f64();
-                let system_time = now.duration_since(start_time)?.as_secs_f64();
+                let begin = Timestamp::now();
+                fs.rename(&from, &to)?;
+                let now = Timestamp::now();
+                let end = op_time(begin, now);
+                let system_time = now.duration_since(start_time).as_secs_f64();
+                if let Some(verifier) = verifier {
+                    verifier.renamed(file.path()?, to_key)?;
+                }
                 (end, system_time)
             }
             &OperationType::GetRandom(ref len) => {
-                let begin = SystemTime::now();
+                let begin = Timestamp::now();
 
                 let mut rand_content = vec![0u8; *len];
                 let mut rng = rand::thread_rng();
                 rng.fill_bytes(&mut rand_content);
 
-                let now = SystemTime::now();
-                let end = now.duration_since(begin)?.as_secs_f64();
-                let system_time = now.duration_since(start_time)?.as_secs_f64();
+                let now = Timestamp::now();
+                let end = op_time(begin, now);
+                let system_time = now.duration_since(start_time).as_secs_f64();
                 (end, system_time)
             }
             _ => {
@@ -0,0 +1,327 @@
+use crate::clock::Timestamp;
+use crate::fs::{FileHandle, FileSystem};
+use byte_unit::Byte;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+// the maximum number of files kept open until they are synced, well below the default limit of
+// open files, so that a long interval over a large fileset syncs early rather than fails
+const MAX_PENDING_FILES: usize = 256;
+
+///
+/// How the writes of the write_sync benchmark function are synced
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncMethod {
+    Fsync,         // the data and metadata of the written files
+    Fdatasync,     // the data of the written files
+    SyncFileRange, // the written ranges of the files, without the metadata
+    ODsync,        // every write, by opening the files with O_DSYNC
+}
+
+impl FromStr for SyncMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fsync" => Ok(SyncMethod::Fsync),
+            "fdatasync" => Ok(SyncMethod::Fdatasync),
+            "sync_file_range" => Ok(SyncMethod::SyncFileRange),
+            "o_dsync" => Ok(SyncMethod::ODsync),
+            _ => Err(
+                "valid sync methods are: fsync, fdatasync, sync_file_range, o_dsync".to_string(),
+            ),
+        }
+    }
+}
+
+impl Display for SyncMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyncMethod::Fsync => write!(f, "fsync"),
+            SyncMethod::Fdatasync => write!(f, "fdatasync"),
+            SyncMethod::SyncFileRange => write!(f, "sync_file_range"),
+            SyncMethod::ODsync => write!(f, "o_dsync"),
+        }
+    }
+}
+
+///
+/// When the writes of the write_sync benchmark function are synced
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncInterval {
+    Writes(u64),    // after every n writes
+    Bytes(u64),     // once n bytes are written since the last sync
+    Time(Duration), // once the time has passed since the last sync
+}
+
+impl FromStr for SyncInterval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = "valid sync intervals are: writes:<n>, bytes:<size>, ms:<t>".to_string();
+        let (unit, n) = s.split_once(':').ok_or_else(|| err.clone())?;
+        let interval = match unit {
+            "writes" => SyncInterval::Writes(n.parse().map_err(|_| err.clone())?),
+            "bytes" => {
+                SyncInterval::Bytes(Byte::from_str(n).map_err(|_| err.clone())?.get_bytes() as u64)
+            }
+            "ms" => SyncInterval::Time(Duration::from_millis(n.parse().map_err(|_| err.clone())?)),
+            _ => return Err(err),
+        };
+
+        match interval {
+            SyncInterval::Writes(0) | SyncInterval::Bytes(0) => Err(err),
+            SyncInterval::Time(time) if time.is_zero() => Err(err),
+            interval => Ok(interval),
+        }
+    }
+}
+
+impl Display for SyncInterval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyncInterval::Writes(n) => write!(f, "writes:{}", n),
+            SyncInterval::Bytes(n) => write!(f, "bytes:{}", n),
+            SyncInterval::Time(time) => write!(f, "ms:{}", time.as_millis()),
+        }
+    }
+}
+
+///
+/// The sync policy of the write_sync benchmark function: the files written since the last sync
+/// are synced by the method once the interval is reached. With O_DSYNC, every write is synced by
+/// itself, and the interval does not apply.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WriteSyncPolicy {
+    pub method: SyncMethod,
+    pub interval: SyncInterval,
+}
+
+impl Default for WriteSyncPolicy {
+    // an fdatasync after each write
+    fn default() -> Self {
+        Self {
+            method: SyncMethod::Fdatasync,
+            interval: SyncInterval::Writes(1),
+        }
+    }
+}
+
+impl Display for WriteSyncPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.method {
+            SyncMethod::ODsync => write!(f, "{}", self.method),
+            _ => write!(f, "{} every {}", self.method, self.interval),
+        }
+    }
+}
+
+///
+/// Keeps the files written since the last sync, and syncs them based on the write sync policy
+///
+pub struct Syncer {
+    policy: WriteSyncPolicy,
+    pending: HashMap<PathBuf, Pending>, // the written files
+    writes: u64,
+    bytes: u64,
+    last_sync: Timestamp,
+    syncs: u64,
+}
+
+// a file written since the last sync, kept open until it is synced, and the range written to it
+struct Pending {
+    file: Box<dyn FileHandle>,
+    start: u64,
+    end: u64,
+}
+
+impl Syncer {
+    pub fn new(policy: WriteSyncPolicy) -> Self {
+        Self {
+            policy,
+            pending: HashMap::new(),
+            writes: 0,
+            bytes: 0,
+            last_sync: Timestamp::now(),
+            syncs: 0,
+        }
+    }
+
+    ///
+    /// Write the buffer to the file at the offset, and sync the files written since the last sync
+    /// if the interval is reached, or if too many files are kept open. A file written several times
+    /// before a sync is opened once, and synced once over the range of all its writes. With
+    /// O_DSYNC, the file is opened with it.
+    ///
+    pub fn write(
+        &mut self,
+        fs: &dyn FileSystem,
+        path: &Path,
+        buffer: &[u8],
+        offset: u64,
+    ) -> io::Result<()> {
+        let len = buffer.len() as u64;
+        if self.policy.method == SyncMethod::ODsync {
+            return fs.open_dsync(path)?.write_all_at(buffer, offset);
+        }
+
+        let pending = match self.pending.entry(path.to_path_buf()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(Pending {
+                file: fs.open(path)?,
+                start: offset,
+                end: offset + len,
+            }),
+        };
+        pending.file.write_all_at(buffer, offset)?;
+        pending.start = pending.start.min(offset);
+        pending.end = pending.end.max(offset + len);
+
+        self.writes += 1;
+        self.bytes += len;
+        let sync = match self.policy.interval {
+            SyncInterval::Writes(n) => self.writes >= n,
+            SyncInterval::Bytes(n) => self.bytes >= n,
+            SyncInterval::Time(time) => self.last_sync.elapsed() >= time,
+        } || self.pending.len() >= MAX_PENDING_FILES;
+        if sync {
+            self.sync()?;
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Sync the files written since the last sync. All of them are synced even if one fails, and
+    /// the first error is returned.
+    ///
+    pub fn sync(&mut self) -> io::Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+        self.writes = 0;
+        self.bytes = 0;
+        self.last_sync = Timestamp::now();
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        self.syncs += 1;
+        let mut result = Ok(());
+        for (_, mut pending) in pending {
+            let synced = match self.policy.method {
+                SyncMethod::Fsync => pending.file.sync_all(),
+                SyncMethod::Fdatasync => pending.file.sync_data(),
+                SyncMethod::SyncFileRange => pending
+                    .file
+                    .sync_range(pending.start, pending.end - pending.start),
+                SyncMethod::ODsync => Ok(()),
+            };
+            if result.is_ok() {
+                result = synced;
+            }
+        }
+
+        result
+    }
+
+    ///
+    /// The number of syncs so far
+    ///
+    pub fn syncs(&self) -> u64 {
+        self.syncs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fs::StdFs;
+
+    #[test]
+    fn sync_interval_test() {
+        assert_eq!(
+            "writes:10".parse::<SyncInterval>(),
+            Ok(SyncInterval::Writes(10))
+        );
+        assert_eq!(
+            "bytes:1MiB".parse::<SyncInterval>(),
+            Ok(SyncInterval::Bytes(1024 * 1024))
+        );
+        assert_eq!(
+            "ms:100".parse::<SyncInterval>(),
+            Ok(SyncInterval::Time(Duration::from_millis(100)))
+        );
+        assert!("writes:0".parse::<SyncInterval>().is_err());
+        assert!("every:10".parse::<SyncInterval>().is_err());
+        assert_eq!(SyncInterval::Bytes(4096).to_string(), "bytes:4096");
+    }
+
+    #[test]
+    fn syncer_test() -> io::Result<()> {
+        let fs = StdFs;
+        let path = std::env::temp_dir().join(format!("fs-bench-sync-{}", std::process::id()));
+        let other = path.with_extension("other");
+        fs.create(&path)?;
+        fs.create(&other)?;
+
+        let mut syncer = Syncer::new(WriteSyncPolicy {
+            method: SyncMethod::SyncFileRange,
+            interval: SyncInterval::Bytes(16),
+        });
+        syncer.write(&fs, &path, &[1u8; 4], 0)?;
+        syncer.write(&fs, &path, &[1u8; 4], 4)?;
+        syncer.write(&fs, &other, &[1u8; 4], 0)?;
+        // a file written twice is kept once, with the range of both writes
+        assert_eq!(syncer.pending.len(), 2);
+        assert_eq!(
+            (syncer.pending[&path].start, syncer.pending[&path].end),
+            (0, 8)
+        );
+        assert_eq!(syncer.syncs(), 0);
+
+        // the fourth write reaches the interval, and the fifth is pending
+        syncer.write(&fs, &path, &[1u8; 4], 8)?;
+        assert_eq!(syncer.syncs(), 1);
+        assert!(syncer.pending.is_empty());
+        syncer.write(&fs, &path, &[1u8; 4], 12)?;
+        syncer.sync()?;
+        assert_eq!(syncer.syncs(), 2);
+        assert_eq!(fs.stat(&path)?.len, 16);
+
+        fs.remove_file(&path)?;
+        fs.remove_file(&other)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn syncer_max_pending_test() -> io::Result<()> {
+        let fs = StdFs;
+        let dir = std::env::temp_dir().join(format!("fs-bench-pending-{}", std::process::id()));
+        fs.mkdir_all(&dir)?;
+
+        let mut syncer = Syncer::new(WriteSyncPolicy {
+            method: SyncMethod::Fdatasync,
+            interval: SyncInterval::Writes(u64::MAX),
+        });
+        // the files are synced once too many of them are kept open, whatever the interval
+        for file in 0..MAX_PENDING_FILES {
+            let path = dir.join(file.to_string());
+            fs.create(&path)?;
+            syncer.write(&fs, &path, &[1u8; 4], 0)?;
+        }
+        assert_eq!(syncer.syncs(), 1);
+        assert!(syncer.pending.is_empty());
+
+        fs.remove_dir(&dir)?;
+
+        Ok(())
+    }
+}